@@ -1,328 +1,2583 @@
 use eframe::egui;
-use std::path::PathBuf;
-use std::fs;
-use crossbeam_channel::{Receiver, unbounded};
-use similar::{ChangeTag, TextDiff};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::ops::Range;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use crossbeam_channel::{Receiver, Sender, unbounded};
+use similar::ChangeTag;
 use std::thread;
-use crate::scanner::{self, ScanStatus, CompareResult, FileEntry};
+use crate::diff::{self, DiffLine, Segment, SideBySideRow};
+use crate::export::{self, ExportFormat};
+use crate::duplicates::{self, DuplicateGroup, DuplicateScan};
+use crate::threeway::{self, Side, ThreeWayResult, ThreeWayStatus};
+use crate::tree::{self, NodeStatus, TreeDir};
+use crate::content::{self, ContentResult};
+use crate::archive;
+use crate::manifest::{self, ManifestReport};
+use crate::logging;
+use crate::scanner::{self, ScanStatus, CompareOptions, CompareResult, FileEntry, FileFilter, HashMode, SyncAction, SyncDirection, SyncOptions};
 use humansize::{format_size, DECIMAL};
-use chrono::DateTime;
-use std::time::Duration;
+use chrono::{DateTime, Local, NaiveDate};
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
 
-#[derive(PartialEq, Clone, Copy)]
+const SETTINGS_KEY: &str = "omnidiff_settings";
+const MAX_RECENT_PAIRS: usize = 5;
+pub const APP_ID: &str = "OmniDiff Pro";
+const RATE_WINDOW: Duration = Duration::from_secs(3);
+const DEFAULT_SCAN_LIMIT: usize = 1_000_000;
+
+/// (source, destination) Blake3 hashes, or why one side couldn't be read
+type PairHashes = Result<(String, String), String>;
+
+// COMMAND is Ctrl on Windows/Linux and Cmd on macOS
+const SHORTCUT_COMPARE: egui::KeyboardShortcut = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::Enter);
+const SHORTCUT_SYNC: egui::KeyboardShortcut = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::S);
+const SHORTCUT_FILTER: egui::KeyboardShortcut = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::F);
+
+/// Options restored between sessions, and the contents of a saved profile
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct Settings {
+    source: String,
+    dest: String,
+    base: String,
+    check_content: bool,
+    delete_extra: bool,
+    sync_direction: SyncDirection,
+    only_overwrite_older: bool,
+    preserve_timestamps: bool,
+    verify_copies: bool,
+    stage_copies: bool,
+    backup_overwritten: bool,
+    sync_retries: u32,
+    bandwidth_limit_mb: f64,
+    case_insensitive: bool,
+    use_hash_cache: bool,
+    verbose_logging: bool,
+    threads: usize,
+    scan_limit: usize,
+    hash_mode: HashMode,
+    partial_chunk_kb: usize,
+    detect_renames: bool,
+    ignore_line_endings: bool,
+    follow_symlinks: bool,
+    compare_link_targets: bool,
+    compare_permissions: bool,
+    compare_owner: bool,
+    fix_permissions: bool,
+    apply_permissions: bool,
+    apply_dir_permissions: bool,
+    track_dirs: bool,
+    min_size_enabled: bool,
+    min_size_mb: f64,
+    max_size_enabled: bool,
+    max_size_mb: f64,
+    max_depth_enabled: bool,
+    max_depth: usize,
+    skip_hidden: bool,
+    modified_after_enabled: bool,
+    modified_after: NaiveDate,
+    include_extensions: String,
+    exclude_extensions: String,
+    active_tab: Tab,
+    tree_view: bool,
+    theme: ThemePreference,
+    mtime_tolerance: u64,
+    diff_side_by_side: bool,
+    diff_collapse: bool,
+    diff_context: usize,
+    diff_ignore_trailing_whitespace: bool,
+    diff_ignore_blank_lines: bool,
+    exclude_patterns: String,
+    recent_pairs: Vec<(String, String)>, // Most recent first
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        let app = FolderCompareApp::default();
+        app.settings()
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Serialize, Deserialize)]
+enum ThemePreference {
+    Dark,
+    Light,
+    System,
+}
+
+impl ThemePreference {
+    fn is_dark(self, system: Option<eframe::Theme>) -> bool {
+        match self {
+            ThemePreference::Dark => true,
+            ThemePreference::Light => false,
+            ThemePreference::System => system != Some(eframe::Theme::Light),
+        }
+    }
+
+    fn next(self) -> Self {
+        match self {
+            ThemePreference::Dark => ThemePreference::Light,
+            ThemePreference::Light => ThemePreference::System,
+            ThemePreference::System => ThemePreference::Dark,
+        }
+    }
+
+    fn icon(self) -> &'static str {
+        match self {
+            ThemePreference::Dark => "🌙",
+            ThemePreference::Light => "☀",
+            ThemePreference::System => "💻",
+        }
+    }
+}
+
+/// A loaded quick-look preview, with images already uploaded as textures
+enum QuickLook {
+    Image(egui::TextureHandle),
+    Text(String),
+    Unavailable(String),
+}
+
+#[derive(PartialEq, Clone, Copy, Serialize, Deserialize)]
 enum Tab {
     MissingInDest,
     MissingInSource,
     Different,
+    TimestampOnly,
+    Permissions,
+    Identical,
 }
 
 pub struct FolderCompareApp {
     source: String,
     dest: String,
+    base: String, // Optional third folder; set means a three-way comparison
     check_content: bool,
+    exclude_patterns: String, // One gitignore-style pattern per line
+    case_insensitive: bool,
+    use_hash_cache: bool, // Reuse full hashes from previous runs for unchanged files
+    verbose_logging: bool, // Append debug logs to log_path() for support requests
+    threads: usize, // Scan/hash worker threads, 1..=max_threads()
+    scan_limit: usize, // Files listed before asking whether to go on, 0 = never ask
+    hash_mode: HashMode,
+    partial_chunk_kb: usize, // Head/tail sample size of the Smart short-circuit
+    detect_renames: bool,
+    ignore_line_endings: bool, // Text pairs differing only in CRLF vs LF count as identical
+    follow_symlinks: bool,
+    compare_link_targets: bool, // Unfollowed links pointing elsewhere count as different
+    compare_permissions: bool, // Unix only: identical files with a different mode get their own tab
+    compare_owner: bool,
+    fix_permissions: bool, // Sync applies the sending side's mode (and owner, if compared) to those
+    apply_permissions: bool, // Unix only: copied files, and with apply_dir_permissions their folders, get the source's mode
+    apply_dir_permissions: bool,
+    track_dirs: bool, // Compare folders too, so empty ones are synced
+    // Scan filters: each bound only applies while its checkbox is ticked
+    min_size_enabled: bool,
+    min_size_mb: f64,
+    max_size_enabled: bool,
+    max_size_mb: f64,
+    max_depth_enabled: bool,
+    max_depth: usize,
+    skip_hidden: bool,
+    modified_after_enabled: bool,
+    modified_after: NaiveDate,
+    include_extensions: String, // Comma-separated, e.g. "jpg, raw, mp4"
+    exclude_extensions: String,
+    recent_pairs: Vec<(String, String)>,
+    session_excludes: BTreeSet<String>, // Folders excluded from result rows; not persisted
+    profiles: BTreeMap<String, Settings>, // Named comparison setups, kept in their own file
+    profile_name: String,
     
+    theme: ThemePreference,
+    mtime_tolerance: u64, // Seconds, covers FAT's 2s timestamp granularity by default
+
     // State
     status_msg: String,
     is_scanning: bool,
     progress: f32,
+    rate_samples: VecDeque<(Instant, u64)>, // Recent (time, bytes processed) for MB/s and ETA
+    run_started: Instant, // When the current (or last) worker thread was spawned
+    file_progress: Option<(String, u64, u64)>, // Huge file being hashed: rel_path, bytes done, size
+    bytes_progress: (u64, u64), // processed, total in the current phase
     
     // Results
     results: Option<CompareResult>,
+    live_different: Vec<(FileEntry, FileEntry)>, // Streamed in while a content comparison runs
+    results_key: Option<String>, // comparison_key() behind `results`; a refresh needs it unchanged
+    three_way: Option<ThreeWayResult>,
+    three_way_hide_identical: bool,
+    tree: Option<TreeDir>, // Built from results on first use of the tree view
+    tree_view: bool,
+    tree_hide_identical: bool,
+    duplicates: Option<DuplicateScan>, // Find Duplicates results for the source folder
+    content_matches: Option<ContentResult>, // Match by Content results
+    summary: Option<ResultSummary>,
+    selected: HashSet<String>, // rel_paths included in the next sync
     active_tab: Tab,
+    export_format: ExportFormat,
+    sort_column: SortColumn,
+    sort_ascending: bool,
+    filter_text: String,
     
     // Sync logic
     is_syncing: bool,
     delete_extra: bool,
+    sync_direction: SyncDirection,
+    only_overwrite_older: bool,
+    permanent_delete: bool, // Deliberately not persisted
+    preserve_timestamps: bool,
+    verify_copies: bool,
+    stage_copies: bool,
+    backup_overwritten: bool,
+    sync_retries: u32,
+    bandwidth_limit_mb: f64, // MB/s shared by all copy workers, 0 = unlimited
     confirm_sync_open: bool,
+    sync_deletions: Vec<FileEntry>, // What the sync awaiting confirmation would delete
+    sync_understood: bool, // "I understand" ticked in the confirmation
+    confirm_delete_extra: Option<Vec<FileEntry>>, // "Delete all extra" waiting for confirmation
+    limit_prompt: Option<(usize, Sender<bool>)>, // A scan past scan_limit, held until answered
+    file_pair: Option<(PathBuf, PathBuf)>, // Two files to open in the diff viewer once a context is at hand
+    focus_filter: bool, // Set by the shortcut, consumed when the filter box is drawn
+    sync_ops: (usize, usize), // Last reported (done, total) sync operations
+    sync_errors: Vec<(PathBuf, String)>,
+    sync_verified: Option<usize>, // Set when the last sync re-hashed its copies
+    sync_staged: Vec<PathBuf>, // Staging folders the last sync copied into
+    sync_backed_up: Option<usize>, // Set when the last sync backed up the files it overwrote
+    sync_dry_run: bool,
+    preview_open: bool,
+    preview_ops: Vec<(SyncAction, SyncDirection, String)>,
     
     // Thread communication
+    cancel_flag: Arc<AtomicBool>,
+    pause_flag: Arc<AtomicBool>, // Holds sync workers between operations
     rx: Option<Receiver<ScanStatus>>,
     result_rx: Option<Receiver<Result<CompareResult, String>>>,
+    three_way_rx: Option<Receiver<Result<ThreeWayResult, String>>>,
+    duplicates_rx: Option<Receiver<Result<DuplicateScan, String>>>,
+    content_rx: Option<Receiver<Result<ContentResult, String>>>,
+    manifest_rx: Option<Receiver<Result<ManifestReport, String>>>,
+    found_rx: Option<Receiver<(FileEntry, FileEntry)>>,
+    // Hashes computed on demand for pairs the comparison never fully hashed,
+    // keyed by rel_path; None while still hashing
+    pair_hashes: HashMap<String, Option<PairHashes>>,
+    hash_tx: Sender<(String, PairHashes)>,
+    hash_rx: Receiver<(String, PairHashes)>,
+
+    // Quick look: the clicked row's (side, path) pairs; empty while the panel is closed
+    preview_paths: Vec<(&'static str, PathBuf)>,
+    previews: Vec<QuickLook>, // Indexed like preview_paths once loaded
+    preview_rx: Option<Receiver<Vec<diff::Preview>>>,
 
     // Diff View State
     diff_open: bool,
+    diff_rx: Option<Receiver<diff::Loaded>>, // Set while a file pair is being read and diffed
     diff_file_name: String,
     
     // Text Diff
-    diff_content: Vec<(String, ChangeTag)>,
+    diff_content: Vec<DiffLine>,
+    diff_rows: Vec<SideBySideRow>,
+    diff_side_by_side: bool,
+    diff_collapse: bool,
+    diff_context: usize, // Unchanged lines kept around each change when collapsing
+    diff_text_options: diff::TextOptions, // Re-diffs the open file when changed
+    diff_paths: Option<(PathBuf, PathBuf)>, // (source, destination) of the open diff
     diff_error: Option<String>,
+    diff_link_targets: [Option<PathBuf>; 2], // Where the source / destination path points, for symlinks
+    diff_encoding: String, // Detected text encoding(s), shown in the diff header
+    diff_text_note: Option<String>, // Set when only the head of a large file was diffed
+    diff_search: String,
+    diff_match: usize, // Index into the current search hits
+    diff_scroll_to_match: bool,
     
     // Image Diff
     diff_mode: DiffMode,
     diff_texture_src: Option<egui::TextureHandle>,
     diff_texture_dest: Option<egui::TextureHandle>,
+    diff_texture_diff: Option<egui::TextureHandle>, // Heatmap, only when dimensions match
+    diff_image_note: String, // Pixel stats or the dimension mismatch
+    image_view: ImageView,
+    image_blend: f32, // Source opacity in overlay view
+
+    // Binary Diff (first HEX_PREVIEW_BYTES of each file)
+    diff_bytes_src: Vec<u8>,
+    diff_bytes_dest: Vec<u8>,
+}
+
+/// Totals derived from a `CompareResult` once it lands
+struct ResultSummary {
+    copy_bytes: u64,   // Missing in dest + source side of different and timestamp-only files
+    delete_bytes: u64, // Extra in dest (removed in Mirror Mode)
+    identical: usize,
+    by_extension: Vec<ExtensionStats>, // Largest total first
+}
+
+/// (count, bytes) per category for one file extension
+#[derive(Default)]
+struct ExtensionStats {
+    extension: String, // Lowercased, without the dot; empty for none
+    missing: (usize, u64),
+    different: (usize, u64),
+    extra: (usize, u64),
+}
+
+impl ExtensionStats {
+    fn total_bytes(&self) -> u64 {
+        self.missing.1 + self.different.1 + self.extra.1
+    }
+}
+
+/// Extensions shown before the breakdown folds the rest into one line
+const MAX_EXTENSION_ROWS: usize = 15;
+
+/// Per-extension table with a bar scaled to the largest total
+fn show_extension_breakdown(ui: &mut egui::Ui, stats: &[ExtensionStats]) {
+    egui::CollapsingHeader::new(format!("📊 Differences by extension ({})", stats.len()))
+        .id_source("extension_breakdown")
+        .show(ui, |ui| {
+            let largest = stats.first().map_or(1, |s| s.total_bytes().max(1));
+            let cell = |(count, bytes): (usize, u64)| {
+                if count == 0 { "-".to_owned() } else { format!("{} · {}", format_count(count), format_size(bytes, DECIMAL)) }
+            };
+            egui::Grid::new("extension_grid").striped(true).spacing([20.0, 4.0]).show(ui, |ui| {
+                ui.strong("Extension");
+                ui.strong("Missing");
+                ui.strong("Different");
+                ui.strong("Extra");
+                ui.strong("Total");
+                ui.end_row();
+                for stat in stats.iter().take(MAX_EXTENSION_ROWS) {
+                    ui.label(if stat.extension.is_empty() { "(none)".to_owned() } else { format!(".{}", stat.extension) });
+                    ui.label(cell(stat.missing));
+                    ui.label(cell(stat.different));
+                    ui.label(cell(stat.extra));
+                    ui.horizontal(|ui| {
+                        let width = 150.0 * stat.total_bytes() as f32 / largest as f32;
+                        let (rect, _) = ui.allocate_exact_size(egui::vec2(width.max(1.0), 10.0), egui::Sense::hover());
+                        ui.painter().rect_filled(rect, 2.0, egui::Color32::from_rgb(52, 152, 219));
+                        ui.label(format_size(stat.total_bytes(), DECIMAL));
+                    });
+                    ui.end_row();
+                }
+            });
+            if stats.len() > MAX_EXTENSION_ROWS {
+                let rest: u64 = stats[MAX_EXTENSION_ROWS..].iter().map(ExtensionStats::total_bytes).sum();
+                ui.label(format!("… and {} more extensions, {} in total", stats.len() - MAX_EXTENSION_ROWS, format_size(rest, DECIMAL)));
+            }
+        });
+}
+
+/// Group the copy/delete work by extension, so big media or build outputs stand out
+fn extension_breakdown(results: &CompareResult) -> Vec<ExtensionStats> {
+    let mut stats: HashMap<String, ExtensionStats> = HashMap::new();
+    let mut add = |file: &FileEntry, pick: fn(&mut ExtensionStats) -> &mut (usize, u64)| {
+        let extension = Path::new(&file.rel_path).extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let entry = stats.entry(extension.clone()).or_insert_with(|| ExtensionStats { extension, ..Default::default() });
+        let slot = pick(entry);
+        slot.0 += 1;
+        slot.1 += file.size;
+    };
+    for file in results.missing_in_dest.iter().chain(results.renamed.iter().map(|(src, _)| src)) {
+        add(file, |s| &mut s.missing);
+    }
+    for (src, _) in results.different_content.iter().chain(&results.timestamp_only) {
+        add(src, |s| &mut s.different);
+    }
+    for file in &results.missing_in_source {
+        add(file, |s| &mut s.extra);
+    }
+    let mut stats: Vec<ExtensionStats> = stats.into_values().collect();
+    stats.sort_by(|a, b| b.total_bytes().cmp(&a.total_bytes()).then_with(|| a.extension.cmp(&b.extension)));
+    stats
+}
+
+impl ResultSummary {
+    fn from_result(results: &CompareResult) -> Self {
+        let missing: u64 = results.missing_in_dest.iter()
+            .chain(results.renamed.iter().map(|(src, _)| src))
+            .map(|f| f.size)
+            .sum();
+        let different: u64 = results.different_content.iter()
+            .chain(&results.timestamp_only)
+            .map(|(src, _)| src.size)
+            .sum();
+        Self {
+            copy_bytes: missing + different,
+            delete_bytes: results.missing_in_source.iter()
+                .chain(results.renamed.iter().map(|(_, dest)| dest))
+                .map(|f| f.size)
+                .sum(),
+            identical: results.identical_count,
+            by_extension: extension_breakdown(results),
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy)]
+enum SortColumn {
+    Path,
+    Size,
+    Modified,
+    DestSize,
+}
+
+#[derive(PartialEq, Clone, Copy)]
+enum ImageView {
+    SideBySide,
+    Difference,
+    Overlay,
 }
 
 #[derive(PartialEq, Clone, Copy)]
 enum DiffMode {
     Text,
     Image,
+    Binary,
 }
 
 impl Default for FolderCompareApp {
     fn default() -> Self {
+        let (hash_tx, hash_rx) = unbounded();
         Self {
             source: "".to_owned(),
             dest: "".to_owned(),
+            base: "".to_owned(),
             check_content: true,
+            exclude_patterns: "".to_owned(),
+            case_insensitive: false,
+            use_hash_cache: true,
+            verbose_logging: false,
+            threads: max_threads(),
+            scan_limit: DEFAULT_SCAN_LIMIT,
+            hash_mode: HashMode::Smart,
+            partial_chunk_kb: scanner::DEFAULT_PARTIAL_CHUNK / 1024,
+            detect_renames: false,
+            ignore_line_endings: false,
+            follow_symlinks: false,
+            compare_link_targets: false,
+            compare_permissions: false,
+            compare_owner: false,
+            fix_permissions: false,
+            apply_permissions: false,
+            apply_dir_permissions: false,
+            track_dirs: false,
+            min_size_enabled: false,
+            min_size_mb: 100.0,
+            max_size_enabled: false,
+            max_size_mb: 1000.0,
+            max_depth_enabled: false,
+            max_depth: 2,
+            skip_hidden: true,
+            modified_after_enabled: false,
+            modified_after: Local::now().date_naive() - chrono::Days::new(7),
+            include_extensions: "".to_owned(),
+            exclude_extensions: "".to_owned(),
+            recent_pairs: Vec::new(),
+            session_excludes: BTreeSet::new(),
+            profiles: BTreeMap::new(),
+            profile_name: String::new(),
+            theme: ThemePreference::Dark,
+            mtime_tolerance: 2,
             status_msg: "Ready".to_owned(),
             is_scanning: false,
             progress: 0.0,
+            rate_samples: VecDeque::new(),
+            run_started: Instant::now(),
+            file_progress: None,
+            bytes_progress: (0, 0),
             results: None,
+            live_different: Vec::new(),
+            results_key: None,
+            three_way: None,
+            three_way_hide_identical: true,
+            tree: None,
+            tree_view: false,
+            tree_hide_identical: true,
+            duplicates: None,
+            content_matches: None,
+            summary: None,
+            selected: HashSet::new(),
             active_tab: Tab::MissingInDest,
+            export_format: ExportFormat::Csv,
+            sort_column: SortColumn::Path,
+            sort_ascending: true,
+            filter_text: "".to_owned(),
             rx: None,
             result_rx: None,
+            three_way_rx: None,
+            duplicates_rx: None,
+            content_rx: None,
+            manifest_rx: None,
+            found_rx: None,
+            pair_hashes: HashMap::new(),
+            hash_tx,
+            hash_rx,
             is_syncing: false,
             delete_extra: false,
+            sync_direction: SyncDirection::SourceToDest,
+            only_overwrite_older: false,
+            permanent_delete: false,
+            preserve_timestamps: true,
+            verify_copies: false,
+            stage_copies: false,
+            backup_overwritten: false,
+            sync_retries: SyncOptions::default().retries,
+            bandwidth_limit_mb: 0.0,
             confirm_sync_open: false,
+            sync_deletions: Vec::new(),
+            sync_understood: false,
+            confirm_delete_extra: None,
+            limit_prompt: None,
+            file_pair: None,
+            focus_filter: false,
+            sync_ops: (0, 0),
+            sync_errors: Vec::new(),
+            sync_verified: None,
+            sync_staged: Vec::new(),
+            sync_backed_up: None,
+            sync_dry_run: false,
+            preview_open: false,
+            preview_ops: Vec::new(),
+            cancel_flag: Arc::new(AtomicBool::new(false)),
+            pause_flag: Arc::new(AtomicBool::new(false)),
             diff_open: false,
             diff_file_name: "".to_owned(),
             diff_content: Vec::new(),
+            diff_rows: Vec::new(),
+            diff_side_by_side: false,
+            diff_collapse: true,
+            diff_context: 3,
+            diff_text_options: diff::TextOptions::default(),
+            diff_paths: None,
             diff_error: None,
+            diff_link_targets: [None, None],
+            preview_paths: Vec::new(),
+            previews: Vec::new(),
+            preview_rx: None,
+            diff_rx: None,
+            diff_encoding: "".to_owned(),
+            diff_text_note: None,
+            diff_search: "".to_owned(),
+            diff_match: 0,
+            diff_scroll_to_match: false,
             diff_mode: DiffMode::Text,
             diff_texture_src: None,
             diff_texture_dest: None,
+            diff_texture_diff: None,
+            diff_image_note: "".to_owned(),
+            image_view: ImageView::SideBySide,
+            image_blend: 0.5,
+            diff_bytes_src: Vec::new(),
+            diff_bytes_dest: Vec::new(),
         }
     }
 }
 
 impl FolderCompareApp {
     pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
-        // Modern Premium Styling
-        let mut visuals = egui::Visuals::dark();
-        visuals.window_rounding = egui::Rounding::same(12.0);
-        visuals.widgets.noninteractive.rounding = egui::Rounding::same(8.0);
-        visuals.widgets.active.rounding = egui::Rounding::same(8.0);
-        visuals.widgets.inactive.rounding = egui::Rounding::same(8.0);
-        visuals.selection.bg_fill = egui::Color32::from_rgb(52, 152, 219); // Premium Blue
-        cc.egui_ctx.set_visuals(visuals);
-        
         let mut style = (*cc.egui_ctx.style()).clone();
         style.spacing.item_spacing = egui::vec2(10.0, 10.0);
         style.text_styles.insert(egui::TextStyle::Heading, egui::FontId::new(24.0, egui::FontFamily::Proportional));
         cc.egui_ctx.set_style(style);
 
-        Self::default()
+        let mut app = Self::default();
+        if let Some(settings) = cc.storage.and_then(|s| eframe::get_value::<Settings>(s, SETTINGS_KEY)) {
+            app.apply_settings(settings);
+        }
+        app.profiles = load_profiles();
+        cc.egui_ctx.set_visuals(themed_visuals(app.theme.is_dark(cc.integration_info.system_theme)));
+        app
+    }
+
+    fn settings(&self) -> Settings {
+        Settings {
+            source: self.source.clone(),
+            dest: self.dest.clone(),
+            base: self.base.clone(),
+            check_content: self.check_content,
+            delete_extra: self.delete_extra,
+            sync_direction: self.sync_direction,
+            only_overwrite_older: self.only_overwrite_older,
+            preserve_timestamps: self.preserve_timestamps,
+            verify_copies: self.verify_copies,
+            stage_copies: self.stage_copies,
+            backup_overwritten: self.backup_overwritten,
+            sync_retries: self.sync_retries,
+            bandwidth_limit_mb: self.bandwidth_limit_mb,
+            case_insensitive: self.case_insensitive,
+            use_hash_cache: self.use_hash_cache,
+            verbose_logging: self.verbose_logging,
+            threads: self.threads,
+            scan_limit: self.scan_limit,
+            hash_mode: self.hash_mode,
+            partial_chunk_kb: self.partial_chunk_kb,
+            detect_renames: self.detect_renames,
+            ignore_line_endings: self.ignore_line_endings,
+            follow_symlinks: self.follow_symlinks,
+            compare_link_targets: self.compare_link_targets,
+            compare_permissions: self.compare_permissions,
+            compare_owner: self.compare_owner,
+            fix_permissions: self.fix_permissions,
+            apply_permissions: self.apply_permissions,
+            apply_dir_permissions: self.apply_dir_permissions,
+            track_dirs: self.track_dirs,
+            min_size_enabled: self.min_size_enabled,
+            min_size_mb: self.min_size_mb,
+            max_size_enabled: self.max_size_enabled,
+            max_size_mb: self.max_size_mb,
+            max_depth_enabled: self.max_depth_enabled,
+            max_depth: self.max_depth,
+            skip_hidden: self.skip_hidden,
+            modified_after_enabled: self.modified_after_enabled,
+            modified_after: self.modified_after,
+            include_extensions: self.include_extensions.clone(),
+            exclude_extensions: self.exclude_extensions.clone(),
+            active_tab: self.active_tab,
+            theme: self.theme,
+            mtime_tolerance: self.mtime_tolerance,
+            diff_side_by_side: self.diff_side_by_side,
+            tree_view: self.tree_view,
+            diff_collapse: self.diff_collapse,
+            diff_context: self.diff_context,
+            diff_ignore_trailing_whitespace: self.diff_text_options.ignore_trailing_whitespace,
+            diff_ignore_blank_lines: self.diff_text_options.ignore_blank_lines,
+            exclude_patterns: self.exclude_patterns.clone(),
+            recent_pairs: self.recent_pairs.clone(),
+        }
+    }
+
+    fn apply_settings(&mut self, settings: Settings) {
+        self.source = settings.source;
+        self.dest = settings.dest;
+        self.base = settings.base;
+        self.check_content = settings.check_content;
+        self.delete_extra = settings.delete_extra;
+        self.sync_direction = settings.sync_direction;
+        self.only_overwrite_older = settings.only_overwrite_older;
+        self.preserve_timestamps = settings.preserve_timestamps;
+        self.verify_copies = settings.verify_copies;
+        self.stage_copies = settings.stage_copies;
+        self.backup_overwritten = settings.backup_overwritten;
+        self.sync_retries = settings.sync_retries;
+        self.bandwidth_limit_mb = settings.bandwidth_limit_mb;
+        self.case_insensitive = settings.case_insensitive;
+        self.use_hash_cache = settings.use_hash_cache;
+        self.verbose_logging = settings.verbose_logging;
+        self.apply_verbose_logging();
+        self.threads = settings.threads.clamp(1, max_threads());
+        self.scan_limit = settings.scan_limit;
+        self.hash_mode = settings.hash_mode;
+        self.partial_chunk_kb = settings.partial_chunk_kb.max(4);
+        self.detect_renames = settings.detect_renames;
+        self.ignore_line_endings = settings.ignore_line_endings;
+        self.follow_symlinks = settings.follow_symlinks;
+        self.compare_link_targets = settings.compare_link_targets;
+        self.compare_permissions = settings.compare_permissions;
+        self.compare_owner = settings.compare_owner;
+        self.fix_permissions = settings.fix_permissions;
+        self.apply_permissions = settings.apply_permissions;
+        self.apply_dir_permissions = settings.apply_dir_permissions;
+        self.track_dirs = settings.track_dirs;
+        self.min_size_enabled = settings.min_size_enabled;
+        self.min_size_mb = settings.min_size_mb;
+        self.max_size_enabled = settings.max_size_enabled;
+        self.max_size_mb = settings.max_size_mb;
+        self.max_depth_enabled = settings.max_depth_enabled;
+        self.max_depth = settings.max_depth;
+        self.skip_hidden = settings.skip_hidden;
+        self.modified_after_enabled = settings.modified_after_enabled;
+        self.modified_after = settings.modified_after;
+        self.include_extensions = settings.include_extensions;
+        self.exclude_extensions = settings.exclude_extensions;
+        self.active_tab = settings.active_tab;
+        self.theme = settings.theme;
+        self.mtime_tolerance = settings.mtime_tolerance;
+        self.diff_side_by_side = settings.diff_side_by_side;
+        self.tree_view = settings.tree_view;
+        self.diff_collapse = settings.diff_collapse;
+        self.diff_context = settings.diff_context;
+        self.diff_text_options = diff::TextOptions {
+            ignore_trailing_whitespace: settings.diff_ignore_trailing_whitespace,
+            ignore_blank_lines: settings.diff_ignore_blank_lines,
+        };
+        self.exclude_patterns = settings.exclude_patterns;
+        self.recent_pairs = settings.recent_pairs;
+    }
+
+    /// Store the current folders and options under `profile_name`, replacing any namesake
+    fn save_profile(&mut self) {
+        let name = self.profile_name.trim().to_owned();
+        let mut profile = self.settings();
+        profile.recent_pairs.clear();
+        self.profiles.insert(name.clone(), profile);
+        self.status_msg = match save_profiles(&self.profiles) {
+            Ok(()) => format!("💾 Saved profile \"{}\"", name),
+            Err(e) => format!("❌ Could not save profiles: {}", e),
+        };
+    }
+
+    fn load_profile(&mut self, name: &str) {
+        let Some(profile) = self.profiles.get(name).cloned() else { return };
+        // A profile describes the job, not the window, so view preferences stay as they are
+        let current = self.settings();
+        self.apply_settings(Settings {
+            active_tab: current.active_tab,
+            tree_view: current.tree_view,
+            theme: current.theme,
+            diff_side_by_side: current.diff_side_by_side,
+            diff_collapse: current.diff_collapse,
+            diff_context: current.diff_context,
+            diff_ignore_trailing_whitespace: current.diff_ignore_trailing_whitespace,
+            diff_ignore_blank_lines: current.diff_ignore_blank_lines,
+            recent_pairs: current.recent_pairs,
+            verbose_logging: current.verbose_logging,
+            ..profile
+        });
+        self.profile_name = name.to_owned();
+        self.status_msg = format!("Loaded profile \"{}\"", name);
+    }
+
+    /// Open or close the log file to match the "Verbose logging" setting
+    fn apply_verbose_logging(&mut self) {
+        let path = log_path().filter(|_| self.verbose_logging);
+        if let Err(e) = logging::set_file(path.as_deref()) {
+            self.verbose_logging = false;
+            self.status_msg = format!("❌ Could not open the log file: {}", e);
+        }
+    }
+
+    fn delete_profile(&mut self) {
+        if self.profiles.remove(self.profile_name.trim()).is_some() {
+            self.status_msg = match save_profiles(&self.profiles) {
+                Ok(()) => format!("🗑 Deleted profile \"{}\"", self.profile_name.trim()),
+                Err(e) => format!("❌ Could not save profiles: {}", e),
+            };
+        }
+    }
+
+    fn profile_bar(&mut self, ui: &mut egui::Ui) {
+        let mut picked = None;
+        ui.add_enabled_ui(!self.profiles.is_empty(), |ui| {
+            egui::ComboBox::from_id_source("profile_picker")
+                .selected_text("📋 Load profile")
+                .show_ui(ui, |ui| {
+                    for name in self.profiles.keys() {
+                        if ui.selectable_label(*name == self.profile_name, name).clicked() {
+                            picked = Some(name.clone());
+                        }
+                    }
+                });
+        });
+        if let Some(name) = picked {
+            self.load_profile(&name);
+        }
+        ui.add(egui::TextEdit::singleline(&mut self.profile_name)
+            .desired_width(140.0)
+            .hint_text("Profile name"));
+        let name = self.profile_name.trim().to_owned();
+        if ui.add_enabled(!name.is_empty(), egui::Button::new("💾 Save"))
+            .on_hover_text("Save the folders, filters and options under this name")
+            .clicked()
+        {
+            self.save_profile();
+        }
+        if ui.add_enabled(self.profiles.contains_key(&name), egui::Button::new("🗑")).on_hover_text("Delete this profile").clicked() {
+            self.delete_profile();
+        }
+    }
+
+    fn remember_pair(&mut self) {
+        let pair = (self.source.clone(), self.dest.clone());
+        self.recent_pairs.retain(|p| *p != pair);
+        self.recent_pairs.insert(0, pair);
+        self.recent_pairs.truncate(MAX_RECENT_PAIRS);
+    }
+
+    fn recent_menu(&mut self, ui: &mut egui::Ui, id: &str) {
+        ui.push_id(id, |ui| {
+            ui.add_enabled_ui(!self.recent_pairs.is_empty(), |ui| {
+                ui.menu_button("🕘", |ui| {
+                    let mut picked = None;
+                    for (source, dest) in &self.recent_pairs {
+                        if ui.button(format!("{}  →  {}", source, dest)).clicked() {
+                            picked = Some((source.clone(), dest.clone()));
+                            ui.close_menu();
+                        }
+                    }
+                    if let Some((source, dest)) = picked {
+                        self.source = source;
+                        self.dest = dest;
+                    }
+                }).response.on_hover_text("Recent folder pairs");
+            });
+        });
     }
 
     fn start_comparison(&mut self) {
         let source = PathBuf::from(&self.source);
         let dest = PathBuf::from(&self.dest);
+        let base = (!self.base.trim().is_empty()).then(|| PathBuf::from(self.base.trim()));
+
+        // Two plain files skip the scan and go straight to the diff viewer
+        let is_file = |p: &Path| p.is_file() && !archive::is_archive(p) && !manifest::is_manifest(p);
+        if is_file(&source) && is_file(&dest) {
+            self.remember_pair();
+            // Folder results would otherwise be synced against these paths
+            self.results = None;
+            self.tree = None;
+            self.summary = None;
+            self.three_way = None;
+            self.duplicates = None;
+            self.content_matches = None;
+            self.status_msg = "📄 File-to-file comparison".to_owned();
+            self.file_pair = Some((source, dest));
+            return;
+        }
+        if is_file(&source) || is_file(&dest) {
+            self.status_msg = "Error: Pick two files or two folders".to_owned();
+            return;
+        }
         
-        if !source.exists() || !dest.exists() {
+        if !source.exists() || !dest.exists() || base.as_ref().is_some_and(|b| !b.exists()) {
             self.status_msg = "Error: Paths do not exist".to_owned();
             return;
         }
+        if let Err(e) = scanner::check_distinct_roots(&source, &dest) {
+            self.status_msg = format!("Error: {}", e);
+            return;
+        }
 
+        self.remember_pair();
         self.is_scanning = true;
         self.progress = 0.0;
+        self.rate_samples.clear();
         self.results = None;
+        self.tree = None;
+        self.summary = None;
+        self.three_way = None;
+        self.duplicates = None;
+        self.content_matches = None;
         self.status_msg = "Starting...".to_owned();
 
         let (tx, rx) = unbounded();
-        let (res_tx, res_rx) = unbounded();
-        
         self.rx = Some(rx);
-        self.result_rx = Some(res_rx);
+        self.run_started = Instant::now();
+        self.file_progress = None;
         
-        let check = self.check_content;
+        // Fresh flag per run so a still-unwinding cancelled worker can't affect this one
+        self.cancel_flag = Arc::new(AtomicBool::new(false));
+        let cancel = self.cancel_flag.clone();
+        let options = self.compare_options();
+
+        if let Some(base) = base {
+            // Source is "mine", destination is "theirs"
+            let (res_tx, res_rx) = unbounded();
+            self.three_way_rx = Some(res_rx);
+            thread::spawn(move || {
+                let res = threeway::run_three_way(base, source, dest, &options, &cancel, tx);
+                res_tx.send(res).ok();
+            });
+            return;
+        }
 
+        let (res_tx, res_rx) = unbounded();
+        let (found_tx, found_rx) = unbounded();
+        self.result_rx = Some(res_rx);
+        self.found_rx = Some(found_rx);
+        self.live_different.clear();
+        self.pair_hashes.clear();
+        self.results_key = Some(self.comparison_key());
         thread::spawn(move || {
-            let res = scanner::run_comparison(source, dest, check, tx);
+            let res = scanner::run_comparison(source, dest, &options, &cancel, tx, Some(found_tx));
             res_tx.send(res).ok();
         });
     }
 
-    fn start_sync(&mut self) {
-        let results = match &self.results {
-            Some(r) => r.clone(),
-            None => return,
-        };
-        
+    /// Folders and options of a two-way comparison, so a refresh can tell they're unchanged
+    fn comparison_key(&self) -> String {
+        format!("{}|{}|{:?}", self.source, self.dest, self.compare_options())
+    }
+
+    fn can_refresh(&self) -> bool {
+        !self.is_scanning && !self.is_syncing && self.results.is_some()
+            && self.results_key.as_ref() == Some(&self.comparison_key())
+            && !self.read_only_side()
+    }
+
+    /// Either side is an archive or manifest, which can be compared but not synced
+    fn read_only_side(&self) -> bool {
+        [&self.source, &self.dest].iter().any(|p| archive::is_archive(Path::new(p)) || manifest::is_manifest(Path::new(p)))
+    }
+
+    /// Re-check only the folders that changed since the last comparison
+    fn start_refresh(&mut self) {
+        let Some(previous) = self.results.take() else { return };
         let source = PathBuf::from(&self.source);
         let dest = PathBuf::from(&self.dest);
-        let delete_extra = self.delete_extra;
 
-        self.is_syncing = true;
+        self.is_scanning = true;
         self.progress = 0.0;
-        self.status_msg = "♻️ Starting Sync...".to_owned();
+        self.rate_samples.clear();
+        self.tree = None;
+        self.summary = None;
+        self.live_different.clear();
+        self.pair_hashes.clear();
+        self.status_msg = "Starting...".to_owned();
 
         let (tx, rx) = unbounded();
+        let (res_tx, res_rx) = unbounded();
         self.rx = Some(rx);
+        self.run_started = Instant::now();
+        self.file_progress = None;
+        self.result_rx = Some(res_rx);
+        self.found_rx = None;
+
+        self.cancel_flag = Arc::new(AtomicBool::new(false));
+        let cancel = self.cancel_flag.clone();
+        let options = self.compare_options();
 
         thread::spawn(move || {
-            let _ = scanner::run_sync(source, dest, &results, delete_extra, tx);
+            let res = scanner::run_refresh(source, dest, &previous, &options, &cancel, tx);
+            res_tx.send(res).ok();
         });
     }
-    
-    fn format_time(&self, ts: u64) -> String {
-        // Convert timestamp to readable date
-        if let Some(dt) = DateTime::from_timestamp(ts as i64, 0) {
-            dt.format("%Y-%m-%d %H:%M").to_string()
-        } else {
-            "-".into()
+
+    fn start_duplicate_scan(&mut self) {
+        let source = PathBuf::from(&self.source);
+        if !source.exists() {
+            self.status_msg = "Error: Source folder does not exist".to_owned();
+            return;
         }
-    }
 
+        self.is_scanning = true;
+        self.progress = 0.0;
+        self.rate_samples.clear();
+        self.results = None;
+        self.tree = None;
+        self.summary = None;
+        self.three_way = None;
+        self.duplicates = None;
+        self.content_matches = None;
+        self.status_msg = "Starting...".to_owned();
 
-    fn show_file_list(&self, ui: &mut egui::Ui, files: &[FileEntry]) {
-        use egui_extras::{TableBuilder, Column};
-        
-        TableBuilder::new(ui)
-            .striped(true)
-            .column(Column::initial(400.0).resizable(true)) // Path
-            .column(Column::exact(100.0)) // Size
-            .column(Column::remainder()) // Date
-            .header(20.0, |mut header| {
-                header.col(|ui| { ui.strong("Path"); });
-                header.col(|ui| { ui.strong("Size"); });
-                header.col(|ui| { ui.strong("Modified"); });
-            })
-            .body(|mut body| {
-                for file in files {
-                    body.row(18.0, |mut row| {
-                        row.col(|ui| { ui.label(&file.rel_path); });
-                        row.col(|ui| { ui.label(format_size(file.size, DECIMAL)); });
-                        row.col(|ui| { ui.label(self.format_time(file.modified)); });
-                    });
-                }
-            });
-    }
-    
-    fn show_diff_list(&mut self, ui: &mut egui::Ui, files: &[(FileEntry, FileEntry)]) {
-        use egui_extras::{TableBuilder, Column};
-        
-        TableBuilder::new(ui)
-            .striped(true)
-            .column(Column::initial(300.0).resizable(true)) // Path
-            .column(Column::exact(80.0)) // Src Size
-            .column(Column::exact(80.0)) // Dest Size
-            .column(Column::remainder()) // Actions
-            .header(20.0, |mut header| {
-                header.col(|ui| { ui.strong("Path"); });
-                header.col(|ui| { ui.strong("Src Size"); });
-                header.col(|ui| { ui.strong("Dest Size"); });
-                header.col(|ui| { ui.strong("Actions"); });
-            })
-            .body(|mut body| {
-                for (src, dest) in files {
-                    body.row(18.0, |mut row| {
-                        row.col(|ui| { ui.label(&src.rel_path); });
-                        row.col(|ui| { ui.label(format_size(src.size, DECIMAL)); });
-                        row.col(|ui| { ui.label(format_size(dest.size, DECIMAL)); });
-                        row.col(|ui| { 
-                            if ui.button("View Diff").clicked() {
-                                self.open_diff_viewer(ui.ctx(), &src.path, &dest.path, &src.rel_path);
-                            }
-                        });
-                    });
-                }
-            });
-    }
+        let (tx, rx) = unbounded();
+        let (res_tx, res_rx) = unbounded();
+        self.rx = Some(rx);
+        self.run_started = Instant::now();
+        self.file_progress = None;
+        self.duplicates_rx = Some(res_rx);
 
-    fn open_diff_viewer(&mut self, ctx: &egui::Context, src_path: &PathBuf, dest_path: &PathBuf, name: &str) {
-        self.diff_open = true;
-        self.diff_file_name = name.to_owned();
-        self.diff_error = None;
-        self.diff_content.clear();
-        
-        // Reset image state
-        self.diff_texture_src = None;
-        self.diff_texture_dest = None;
-        self.diff_mode = DiffMode::Text;
+        self.cancel_flag = Arc::new(AtomicBool::new(false));
+        let cancel = self.cancel_flag.clone();
+        let options = self.compare_options();
+
+        thread::spawn(move || {
+            let res = duplicates::find_duplicates(source, &options, &cancel, tx);
+            res_tx.send(res).ok();
+        });
+    }
 
-        // Check for specific system files
-        if name.ends_with(".DS_Store") || name.ends_with("Thumbs.db") {
-            self.diff_error = Some("System file (Binary). Comparison skipped.".into());
+    /// Write a b3sum manifest of the source folder, for verifying it (or a copy) later
+    fn start_manifest_export(&mut self) {
+        let source = PathBuf::from(&self.source);
+        if !source.is_dir() {
+            self.status_msg = "Error: Source folder does not exist".to_owned();
             return;
         }
-        
-        // Check for Image
-        let ext = name.split('.').last().unwrap_or("").to_lowercase();
-        let img_exts = ["png", "jpg", "jpeg", "bmp", "gif", "webp", "ico", "tiff"];
-        
-        if img_exts.contains(&ext.as_str()) {
-            self.diff_mode = DiffMode::Image;
-            
-            // Helper to load texture
-            let load_tex = |path: &PathBuf, label: &str| -> Option<egui::TextureHandle> {
-                 let img = image::io::Reader::open(path).ok()?.decode().ok()?;
-                 let size = [img.width() as _, img.height() as _];
-                 let image_buffer = img.to_rgba8();
-                 let pixels = image_buffer.as_flat_samples();
-                 let color_image = egui::ColorImage::from_rgba_unmultiplied(size, pixels.as_slice());
-                 Some(ctx.load_texture(label, color_image, Default::default()))
-            };
-            
-            self.diff_texture_src = load_tex(src_path, "src_img");
-            self.diff_texture_dest = load_tex(dest_path, "dest_img");
-            
-            if self.diff_texture_src.is_none() || self.diff_texture_dest.is_none() {
-                 self.diff_error = Some("Failed to load one or both images.".into());
-            }
+        let name = source.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_else(|| "omnidiff".into());
+        let Some(out) = rfd::FileDialog::new()
+            .add_filter("Blake3 manifest", &["b3sum"])
+            .set_file_name(format!("{}.b3sum", name))
+            .save_file() else { return };
+
+        self.is_scanning = true;
+        self.progress = 0.0;
+        self.rate_samples.clear();
+        self.status_msg = "Starting...".to_owned();
+
+        let (tx, rx) = unbounded();
+        let (res_tx, res_rx) = unbounded();
+        self.rx = Some(rx);
+        self.run_started = Instant::now();
+        self.file_progress = None;
+        self.manifest_rx = Some(res_rx);
+
+        self.cancel_flag = Arc::new(AtomicBool::new(false));
+        let cancel = self.cancel_flag.clone();
+        let options = self.compare_options();
+
+        thread::spawn(move || {
+            let res = manifest::write_manifest(source, out, &options, &cancel, tx);
+            res_tx.send(res).ok();
+        });
+    }
+
+    fn start_content_match(&mut self) {
+        let source = PathBuf::from(&self.source);
+        let dest = PathBuf::from(&self.dest);
+        if !source.exists() || !dest.exists() {
+            self.status_msg = "Error: Paths do not exist".to_owned();
             return;
         }
 
-        // 1. Try reading as text
-        let src_txt = match fs::read_to_string(src_path) {
-            Ok(s) => s,
-            Err(_) => {
-                self.diff_error = Some("Binary file detected (or invalid encoding). Text comparison unavailable.".into());
-                return;
-            }
-        };
-        let dest_txt = match fs::read_to_string(dest_path) {
-             Ok(s) => s,
-            Err(_) => {
-                self.diff_error = Some("Binary file detected (or invalid encoding). Text comparison unavailable.".into());
-                return;
-            }
-        };
+        self.remember_pair();
+        self.is_scanning = true;
+        self.progress = 0.0;
+        self.rate_samples.clear();
+        self.results = None;
+        self.tree = None;
+        self.summary = None;
+        self.three_way = None;
+        self.duplicates = None;
+        self.content_matches = None;
+        self.status_msg = "Starting...".to_owned();
 
-        let diff = TextDiff::from_lines(&src_txt, &dest_txt);
-        
-        for change in diff.iter_all_changes() {
-            let line = change.value();
-            self.diff_content.push((line.trim_end().to_owned(), change.tag()));
-        }
+        let (tx, rx) = unbounded();
+        let (res_tx, res_rx) = unbounded();
+        self.rx = Some(rx);
+        self.run_started = Instant::now();
+        self.file_progress = None;
+        self.content_rx = Some(res_rx);
+
+        self.cancel_flag = Arc::new(AtomicBool::new(false));
+        let cancel = self.cancel_flag.clone();
+        let options = self.compare_options();
+
+        thread::spawn(move || {
+            let res = content::run_content_match(source, dest, &options, &cancel, tx);
+            res_tx.send(res).ok();
+        });
     }
-}
 
-impl eframe::App for FolderCompareApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        // Poll for updates
-        if let Some(rx) = &self.rx {
-            while let Ok(status) = rx.try_recv() {
-                match status {
-                    ScanStatus::ScanningSource => { self.status_msg = "📂 Scanning Source...".into(); self.progress = 0.1; },
-                    ScanStatus::ScanningDest => { self.status_msg = "📂 Scanning Destination...".into(); self.progress = 0.2; },
-                    ScanStatus::ScanningBoth => { self.status_msg = "📂 Scanning Both Folders...".into(); self.progress = 0.15; },
-                    ScanStatus::Hashing(current, total) => {
-                        self.status_msg = format!("⚡ Verifying Content (Blake3) - {}/{}", current, total);
-                        self.progress = 0.4 + (0.6 * (current as f32 / total as f32));
-                    },
-                    ScanStatus::Syncing(current, total) => {
-                        self.status_msg = format!("♻️ Syncing - {}/{} operations", current, total);
-                        self.progress = current as f32 / total as f32;
-                    },
-                    ScanStatus::Complete => { 
-                        if self.is_syncing {
-                            self.status_msg = "✅ Sync Complete".into();
+    fn show_content_matches(&mut self, ui: &mut egui::Ui) {
+        use egui_extras::{TableBuilder, Column};
+        let Some(matches) = &self.content_matches else { return };
+
+        let moved_color = egui::Color32::from_rgb(241, 196, 15);
+        let source_color = egui::Color32::from_rgb(46, 204, 113);
+        let dest_color = egui::Color32::from_rgb(52, 152, 219);
+        ui.horizontal(|ui| {
+            ui.label(egui::RichText::new(format!("🧭 {} moved or renamed", format_count(matches.moved.len()))).strong().color(moved_color));
+            ui.separator();
+            ui.label(egui::RichText::new(format!("{} only in source", format_count(matches.only_in_source.len()))).strong().color(source_color));
+            ui.separator();
+            ui.label(egui::RichText::new(format!("{} only in destination", format_count(matches.only_in_dest.len()))).strong().color(dest_color));
+            ui.separator();
+            ui.label(egui::RichText::new(format!("✅ {} unchanged", format_count(matches.unchanged_count))).strong());
+        });
+        if !matches.scan_errors.is_empty() {
+            scan_errors_header(ui, &matches.scan_errors);
+        }
+        ui.horizontal(|ui| {
+            ui.label("🔎 Filter:");
+            ui.add(egui::TextEdit::singleline(&mut self.filter_text)
+                .desired_width(300.0)
+                .hint_text("path or extension"));
+        });
+
+        // (source path, dest path, size, status, color)
+        let filter = self.filter_text.to_lowercase();
+        let matches_filter = |f: Option<&FileEntry>| f.is_some_and(|f| f.rel_path.to_lowercase().contains(&filter));
+        let rows: Vec<_> = matches.moved.iter().map(|(src, dest)| (Some(src), Some(dest), "Moved / renamed", moved_color))
+            .chain(matches.only_in_source.iter().map(|src| (Some(src), None, "Only in source", source_color)))
+            .chain(matches.only_in_dest.iter().map(|dest| (None, Some(dest), "Only in destination", dest_color)))
+            .filter(|(src, dest, _, _)| filter.is_empty() || matches_filter(*src) || matches_filter(*dest))
+            .collect();
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            TableBuilder::new(ui)
+                .striped(true)
+                .column(Column::initial(320.0).resizable(true)) // Source path
+                .column(Column::initial(320.0).resizable(true)) // Dest path
+                .column(Column::exact(100.0)) // Size
+                .column(Column::remainder()) // Status
+                .header(20.0, |mut header| {
+                    header.col(|ui| { ui.strong("Source"); });
+                    header.col(|ui| { ui.strong("Destination"); });
+                    header.col(|ui| { ui.strong("Size"); });
+                    header.col(|ui| { ui.strong("Status"); });
+                })
+                .body(|mut body| {
+                    for (src, dest, status, color) in rows {
+                        body.row(18.0, |mut row| {
+                            for file in [src, dest] {
+                                row.col(|ui| { ui.label(file.map_or("—", |f| f.rel_path.as_str())); });
+                            }
+                            row.col(|ui| { ui.label(format_size(src.or(dest).map_or(0, |f| f.size), DECIMAL)); });
+                            row.col(|ui| { ui.colored_label(color, status); });
+                        });
+                    }
+                });
+        });
+    }
+
+    /// Keep the first file of each group and remove the rest, dropping what was removed
+    fn remove_duplicates(&mut self, only: Option<usize>) {
+        let Some(groups) = self.duplicates.as_mut().map(|d| &mut d.groups) else { return };
+        let targets: Vec<DuplicateGroup> = match only {
+            Some(i) => groups.get(i).cloned().into_iter().collect(),
+            None => groups.clone(),
+        };
+        let errors = duplicates::remove_extras(&targets, self.permanent_delete);
+        let failed: HashSet<&PathBuf> = errors.iter().map(|(path, _)| path).collect();
+
+        let removed: Vec<&FileEntry> = targets.iter()
+            .flat_map(|g| g.files.iter().skip(1))
+            .filter(|f| !failed.contains(&f.path))
+            .collect();
+        let removed_paths: HashSet<&PathBuf> = removed.iter().map(|f| &f.path).collect();
+        for group in groups.iter_mut() {
+            group.files.retain(|f| !removed_paths.contains(&f.path));
+        }
+        groups.retain(|g| g.files.len() > 1);
+
+        let freed: u64 = removed.iter().map(|f| f.size).sum();
+        self.status_msg = format!("🗑 Removed {} duplicate files, freed {}", format_count(removed.len()), format_size(freed, DECIMAL));
+        self.sync_errors.extend(errors);
+    }
+
+    fn show_duplicates(&mut self, ui: &mut egui::Ui) {
+        let Some(DuplicateScan { groups, errors, scan_errors }) = &self.duplicates else { return };
+        let wasted: u64 = groups.iter().map(|g| g.wasted()).sum();
+        let mut remove = None;
+
+        ui.horizontal(|ui| {
+            ui.label(egui::RichText::new(format!(
+                "🧬 {} duplicate groups · {} reclaimable", format_count(groups.len()), format_size(wasted, DECIMAL)
+            )).strong());
+            let label = if self.permanent_delete { "🗑 Delete all extras" } else { "🗑 Trash all extras" };
+            if ui.add_enabled(!groups.is_empty(), egui::Button::new(label))
+                .on_hover_text("Keeps the first file of every group")
+                .clicked()
+            {
+                remove = Some(None);
+            }
+        });
+
+        if !scan_errors.is_empty() {
+            scan_errors_header(ui, scan_errors);
+        }
+        if !errors.is_empty() {
+            egui::CollapsingHeader::new(egui::RichText::new(format!(
+                "⚠ {} files could not be read and were left out", format_count(errors.len())
+            )).color(egui::Color32::from_rgb(230, 126, 34)))
+                .id_source("duplicate_errors")
+                .show(ui, |ui| {
+                    egui::ScrollArea::vertical().id_source("duplicate_error_list").max_height(120.0).show(ui, |ui| {
+                        for (rel_path, msg) in errors {
+                            ui.label(format!("{}: {}", rel_path, msg));
+                        }
+                    });
+                });
+        }
+
+        egui::ScrollArea::vertical().id_source("duplicates").show(ui, |ui| {
+            for (i, group) in groups.iter().enumerate() {
+                egui::CollapsingHeader::new(format!(
+                    "{} × {} · {} wasted · {}",
+                    group.files.len(), format_size(group.size, DECIMAL), format_size(group.wasted(), DECIMAL), group.files[0].rel_path
+                ))
+                    .id_source(("duplicate_group", &group.hash))
+                    .show(ui, |ui| {
+                        for (n, file) in group.files.iter().enumerate() {
+                            let marker = if n == 0 { "✔ keep" } else { "✕" };
+                            ui.label(format!("{}  {}", marker, file.rel_path));
+                        }
+                        if ui.button("Keep first, remove others").clicked() {
+                            remove = Some(Some(i));
+                        }
+                    });
+            }
+        });
+
+        if let Some(only) = remove {
+            self.remove_duplicates(only);
+        }
+    }
+
+    fn compare_options(&self) -> CompareOptions {
+        CompareOptions {
+            check_content: self.check_content,
+            exclude_patterns: self.exclude_patterns
+                .lines()
+                .map(|l| l.trim())
+                .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                .map(|l| l.to_owned())
+                // Anchored to the root, and only matching that exact folder
+                .chain(self.session_excludes.iter().map(|dir| format!("/{}/", glob_escape(dir))))
+                .collect(),
+            case_insensitive: self.case_insensitive,
+            hash_cache: if self.use_hash_cache { hash_cache_path() } else { None },
+            threads: self.threads,
+            hash_mode: self.hash_mode,
+            follow_symlinks: self.follow_symlinks,
+            compare_link_targets: self.compare_link_targets,
+            compare_permissions: self.compare_permissions,
+            compare_owner: self.compare_owner,
+            filter: self.file_filter(),
+            mtime_tolerance: self.mtime_tolerance,
+            track_dirs: self.track_dirs,
+            partial_chunk: self.partial_chunk_kb * 1024,
+            detect_renames: self.detect_renames,
+            ignore_line_endings: self.ignore_line_endings,
+            scan_limit: self.scan_limit,
+        }
+    }
+
+    fn file_filter(&self) -> FileFilter {
+        let mb = |v: f64| (v * 1_000_000.0) as u64;
+        let extensions = |list: &str| list.split(',')
+            .map(|e| e.trim().trim_start_matches('.').to_lowercase())
+            .filter(|e| !e.is_empty())
+            .collect();
+        FileFilter {
+            min_size: self.min_size_enabled.then(|| mb(self.min_size_mb)),
+            max_size: self.max_size_enabled.then(|| mb(self.max_size_mb)),
+            modified_after: if self.modified_after_enabled {
+                self.modified_after.and_hms_opt(0, 0, 0)
+                    .and_then(|dt| dt.and_local_timezone(Local).earliest())
+                    .map(|dt| dt.timestamp().max(0) as u64)
+            } else {
+                None
+            },
+            include_extensions: extensions(&self.include_extensions),
+            exclude_extensions: extensions(&self.exclude_extensions),
+            max_depth: self.max_depth_enabled.then_some(self.max_depth),
+            skip_hidden: self.skip_hidden,
+        }
+    }
+
+    /// " in 2m 13s" since the current worker was spawned
+    fn elapsed_text(&self) -> String {
+        format!(" in {}", format_elapsed(self.run_started.elapsed()))
+    }
+
+    /// " · 1.2 GB/s · ETA 00:45" from the rolling byte samples, empty until there's enough data
+    fn throughput_text(&self) -> String {
+        let (Some(&(t0, b0)), Some(&(t1, b1))) = (self.rate_samples.front(), self.rate_samples.back()) else {
+            return String::new();
+        };
+        let secs = (t1 - t0).as_secs_f64();
+        if secs < 0.5 || b1 <= b0 {
+            return String::new();
+        }
+        let rate = (b1 - b0) as f64 / secs;
+        let (done, total) = self.bytes_progress;
+        let eta = (total.saturating_sub(done) as f64 / rate) as u64;
+        format!(" · {}/s · ETA {}", format_size(rate as u64, DECIMAL), format_duration(eta))
+    }
+
+    /// Share of the current phase done by bytes, or by `current`/`total` items when it has none
+    fn byte_fraction(&self, current: usize, total: usize) -> f32 {
+        match self.bytes_progress {
+            (done, bytes) if bytes > 0 => (done as f64 / bytes as f64) as f32,
+            _ => current as f32 / total as f32,
+        }
+    }
+
+    fn sync_options(&self, dry_run: bool) -> SyncOptions {
+        SyncOptions {
+            direction: self.sync_direction,
+            delete_extra: self.delete_extra,
+            only_overwrite_older: self.only_overwrite_older,
+            permanent_delete: self.permanent_delete,
+            preserve_timestamps: self.preserve_timestamps,
+            verify: self.verify_copies,
+            retries: self.sync_retries,
+            bandwidth_limit: (self.bandwidth_limit_mb * 1_000_000.0) as u64,
+            dry_run,
+            stage: self.stage_copies,
+            backup: self.backup_overwritten,
+            fix_permissions: self.fix_permissions,
+            fix_owner: self.fix_permissions && self.compare_owner,
+            apply_permissions: self.apply_permissions,
+            apply_dir_permissions: self.apply_permissions && self.apply_dir_permissions,
+        }
+    }
+
+    /// The comparison restricted to the entries ticked in the result tables
+    fn selected_results(&self, results: &CompareResult) -> CompareResult {
+        let keep = |f: &FileEntry| self.selected.contains(&f.rel_path);
+        CompareResult {
+            missing_in_dest: results.missing_in_dest.iter().filter(|f| keep(f)).cloned().collect(),
+            missing_in_source: results.missing_in_source.iter().filter(|f| keep(f)).cloned().collect(),
+            different_content: results.different_content.iter().filter(|(src, _)| keep(src)).cloned().collect(),
+            timestamp_only: results.timestamp_only.iter().filter(|(src, _)| keep(src)).cloned().collect(),
+            renamed: results.renamed.iter().filter(|(src, _)| keep(src)).cloned().collect(),
+            permissions_differ: results.permissions_differ.iter().filter(|(src, _)| keep(src)).cloned().collect(),
+            ..results.clone()
+        }
+    }
+
+    fn start_sync(&mut self, dry_run: bool) {
+        let results = match &self.results {
+            Some(r) => self.selected_results(r),
+            None => return,
+        };
+        let options = self.sync_options(dry_run);
+        self.spawn_sync(results, options);
+    }
+
+    /// One-off sync of a single row, never deleting anything
+    fn copy_now(&mut self, results: CompareResult, direction: SyncDirection) {
+        let mut options = self.sync_options(false);
+        options.direction = direction;
+        options.delete_extra = false;
+        self.spawn_sync(results, options);
+    }
+
+    fn spawn_sync(&mut self, results: CompareResult, options: SyncOptions) {
+        let source = PathBuf::from(&self.source);
+        let dest = PathBuf::from(&self.dest);
+        let dry_run = options.dry_run;
+        // The fields may have been edited since the comparison ran
+        if self.read_only_side() {
+            self.status_msg = "Error: Archives and manifests can only be compared, not synced".to_owned();
+            return;
+        }
+        if let Err(e) = scanner::check_distinct_roots(&source, &dest) {
+            self.status_msg = format!("Error: {}", e);
+            return;
+        }
+
+        self.is_syncing = true;
+        self.sync_dry_run = dry_run;
+        self.progress = 0.0;
+        self.rate_samples.clear();
+        self.sync_ops = (0, 0);
+        self.bytes_progress = (0, 0);
+        self.sync_errors.clear();
+        self.sync_verified = None;
+        self.sync_staged.clear();
+        self.sync_backed_up = None;
+        if dry_run {
+            self.preview_ops.clear();
+            self.status_msg = "📋 Planning Sync...".to_owned();
+        } else {
+            self.status_msg = "♻️ Starting Sync...".to_owned();
+        }
+
+        let (tx, rx) = unbounded();
+        self.rx = Some(rx);
+        self.run_started = Instant::now();
+        self.file_progress = None;
+
+        self.cancel_flag = Arc::new(AtomicBool::new(false));
+        let cancel = self.cancel_flag.clone();
+        self.pause_flag = Arc::new(AtomicBool::new(false));
+        let paused = self.pause_flag.clone();
+
+        thread::spawn(move || {
+            let _ = scanner::run_sync(source, dest, &results, &options, &cancel, &paused, tx);
+        });
+    }
+
+    fn export_results(&mut self) {
+        let results = match &self.results {
+            Some(r) => r,
+            None => return,
+        };
+
+        let format = self.export_format;
+        let path = rfd::FileDialog::new()
+            .add_filter(format.label(), &[format.extension()])
+            .set_file_name(format!("omnidiff_results.{}", format.extension()))
+            .save_file();
+
+        if let Some(path) = path {
+            self.status_msg = match export::export_results(results, format, &path, &self.source, &self.dest) {
+                Ok(()) => format!("💾 Exported results to {}", path.display()),
+                Err(e) => format!("❌ Export failed: {}", e),
+            };
+        }
+    }
+
+    fn toggle_pause(&mut self) {
+        let paused = !self.pause_flag.load(Ordering::Relaxed);
+        self.pause_flag.store(paused, Ordering::Relaxed);
+        if !paused {
+            // The paused stretch would otherwise drag the rate and ETA down
+            self.rate_samples.clear();
+        }
+    }
+
+    fn cancel_operation(&mut self) {
+        self.cancel_flag.store(true, Ordering::Relaxed);
+        self.status_msg = "⏹ Cancelling...".to_owned();
+    }
+    
+    fn format_time(&self, ts: u64) -> String {
+        // Convert timestamp to readable date
+        if let Some(dt) = DateTime::from_timestamp(ts as i64, 0) {
+            dt.format("%Y-%m-%d %H:%M").to_string()
+        } else {
+            "-".into()
+        }
+    }
+
+
+    fn show_sync_errors(&mut self, ui: &mut egui::Ui) {
+        let mut dismiss = false;
+        egui::Frame::group(ui.style())
+            .stroke(egui::Stroke::new(1.0, egui::Color32::from_rgb(231, 76, 60)))
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.colored_label(egui::Color32::from_rgb(231, 76, 60),
+                        egui::RichText::new(format!("❌ {} operations failed", self.sync_errors.len())).strong());
+                    if ui.button("✕ Dismiss").clicked() {
+                        dismiss = true;
+                    }
+                });
+                egui::ScrollArea::vertical().id_source("sync_errors").max_height(120.0).show(ui, |ui| {
+                    for (path, msg) in &self.sync_errors {
+                        ui.label(format!("{} — {}", path.display(), msg));
+                    }
+                });
+            });
+        if dismiss {
+            self.sync_errors.clear();
+        }
+    }
+
+    fn sort_files(&self, files: &mut [FileEntry]) {
+        match self.sort_column {
+            SortColumn::Path => files.sort_by(|a, b| a.rel_path.cmp(&b.rel_path)),
+            SortColumn::Size | SortColumn::DestSize => files.sort_by_key(|f| f.size),
+            SortColumn::Modified => files.sort_by_key(|f| f.modified),
+        }
+        if !self.sort_ascending {
+            files.reverse();
+        }
+    }
+
+    fn sort_pairs(&self, files: &mut [(FileEntry, FileEntry)]) {
+        match self.sort_column {
+            SortColumn::Path => files.sort_by(|a, b| a.0.rel_path.cmp(&b.0.rel_path)),
+            SortColumn::Size => files.sort_by_key(|(src, _)| src.size),
+            SortColumn::DestSize => files.sort_by_key(|(_, dest)| dest.size),
+            SortColumn::Modified => files.sort_by_key(|(src, _)| src.modified),
+        }
+        if !self.sort_ascending {
+            files.reverse();
+        }
+    }
+
+    /// Clickable column header; clicking the active column flips the direction
+    fn sort_header(&mut self, ui: &mut egui::Ui, label: &str, column: SortColumn) {
+        let text = if self.sort_column == column {
+            format!("{} {}", label, if self.sort_ascending { "⏶" } else { "⏷" })
+        } else {
+            label.to_owned()
+        };
+        let response = ui.add(egui::Label::new(egui::RichText::new(text).strong()).sense(egui::Sense::click()));
+        if response.clicked() {
+            if self.sort_column == column {
+                self.sort_ascending = !self.sort_ascending;
+            } else {
+                self.sort_column = column;
+                self.sort_ascending = true;
+            }
+        }
+    }
+
+    fn selection_checkbox(&mut self, ui: &mut egui::Ui, rel_path: &str) {
+        let mut checked = self.selected.contains(rel_path);
+        if ui.checkbox(&mut checked, "").changed() {
+            if checked {
+                self.selected.insert(rel_path.to_owned());
+            } else {
+                self.selected.remove(rel_path);
+            }
+        }
+    }
+
+    fn show_file_list(&mut self, ui: &mut egui::Ui, files: &[FileEntry], tab: Tab) {
+        use egui_extras::{TableBuilder, Column};
+        let mut exclude = None;
+        let mut preview = None;
+        let side = if tab == Tab::MissingInSource { "Destination" } else { "Source" };
+        
+        // Only the visible rows are built, so the table scrolls itself and fills the panel
+        TableBuilder::new(ui)
+            .striped(true)
+            .max_scroll_height(f32::INFINITY)
+            .auto_shrink([false; 2])
+            .column(Column::exact(24.0)) // Selected
+            .column(Column::exact(20.0)) // Type icon
+            .column(Column::initial(400.0).resizable(true)) // Path
+            .column(Column::exact(100.0)) // Size
+            .column(Column::initial(130.0)) // Date
+            .column(Column::remainder()) // Actions
+            .header(20.0, |mut header| {
+                header.col(|_| {});
+                header.col(|_| {});
+                header.col(|ui| { self.sort_header(ui, "Path", SortColumn::Path); });
+                header.col(|ui| { self.sort_header(ui, "Size", SortColumn::Size); });
+                header.col(|ui| { self.sort_header(ui, "Modified", SortColumn::Modified); });
+                header.col(|ui| { ui.strong("Actions"); });
+            })
+            .body(|body| {
+                body.rows(18.0, files.len(), |index, mut row| {
+                    let file = &files[index];
+                    // Identical files have nothing to sync
+                    let syncable = tab != Tab::Identical;
+                    row.col(|ui| {
+                        if syncable {
+                            self.selection_checkbox(ui, &file.rel_path);
+                        }
+                    });
+                    row.col(|ui| { ui.label(diff::file_icon(&file.rel_path)); });
+                    row.col(|ui| {
+                        let text = match &file.link_target {
+                            Some(target) => format!("{}  🔗 {}", file.rel_path, target.display()),
+                            None => file.rel_path.clone(),
+                        };
+                        let response = ui.add(egui::Label::new(text).sense(egui::Sense::click()))
+                            .context_menu(|ui| {
+                                if let Err(e) = file_actions(ui, &file.path) {
+                                    self.status_msg = format!("❌ {}", e);
+                                }
+                                if let Some(dir) = exclude_folder_button(ui, &file.rel_path) {
+                                    exclude = Some(dir);
+                                }
+                            });
+                        if response.clicked() {
+                            preview = Some(vec![(side, file.path.clone())]);
+                        }
+                    });
+                    row.col(|ui| { ui.label(format_size(file.size, DECIMAL)); });
+                    row.col(|ui| { ui.label(self.format_time(file.modified)); });
+                    row.col(|ui| {
+                        if syncable && ui.add_enabled(!self.is_syncing, egui::Button::new("Copy now")).clicked() {
+                            // Files only exist on one side, so they always copy towards the other
+                            let (result, direction) = if tab == Tab::MissingInSource {
+                                (CompareResult { missing_in_source: vec![file.clone()], ..Default::default() }, SyncDirection::DestToSource)
+                            } else {
+                                (CompareResult { missing_in_dest: vec![file.clone()], ..Default::default() }, SyncDirection::SourceToDest)
+                            };
+                            self.copy_now(result, direction);
+                        }
+                    });
+                });
+            });
+        if let Some(paths) = preview {
+            self.open_preview(ui.ctx(), paths);
+        }
+        if let Some(dir) = exclude {
+            self.exclude_and_rescan(dir);
+        }
+    }
+    
+    /// Matrix of every path across base / mine / theirs with its classification
+    fn show_three_way(&mut self, ui: &mut egui::Ui) {
+        use egui_extras::{TableBuilder, Column};
+        let Some(three_way) = &self.three_way else { return };
+
+        ui.horizontal(|ui| {
+            let conflicts = three_way.count(ThreeWayStatus::Conflict);
+            let identical = three_way.count(ThreeWayStatus::Identical);
+            ui.label(egui::RichText::new(format!("⚔ {} conflicts", conflicts)).strong()
+                .color(if conflicts > 0 { egui::Color32::from_rgb(231, 76, 60) } else { egui::Color32::GRAY }));
+            ui.separator();
+            ui.label(egui::RichText::new(format!("✏ {} changed on one side", three_way.entries.iter()
+                .filter(|e| matches!(e.status, ThreeWayStatus::ChangedIn(_))).count())).strong());
+            ui.separator();
+            ui.label(egui::RichText::new(format!("✅ {} identical in all three", identical)).strong());
+            ui.separator();
+            let unreadable = three_way.entries.iter().filter(|e| matches!(e.status, ThreeWayStatus::Unreadable(_))).count();
+            if unreadable > 0 {
+                ui.colored_label(egui::Color32::from_rgb(231, 76, 60), format!("⚠ {} could not be read", unreadable));
+                ui.separator();
+            }
+            ui.checkbox(&mut self.three_way_hide_identical, "Hide identical");
+        });
+        if !three_way.scan_errors.is_empty() {
+            scan_errors_header(ui, &three_way.scan_errors);
+        }
+        ui.horizontal(|ui| {
+            ui.label("🔎 Filter:");
+            ui.add(egui::TextEdit::singleline(&mut self.filter_text)
+                .desired_width(300.0)
+                .hint_text("path or extension"));
+        });
+
+        let filter = self.filter_text.to_lowercase();
+        let rows: Vec<_> = three_way.entries.iter()
+            .filter(|e| !(self.three_way_hide_identical && e.status == ThreeWayStatus::Identical))
+            .filter(|e| filter.is_empty() || e.rel_path.to_lowercase().contains(&filter))
+            .collect();
+
+        let status_text = |status: ThreeWayStatus| match status {
+            ThreeWayStatus::Identical => ("Identical".to_owned(), egui::Color32::GRAY),
+            ThreeWayStatus::Unique(side) => (format!("Only in {}", side.label()), egui::Color32::from_rgb(52, 152, 219)),
+            ThreeWayStatus::MissingIn(side) => (format!("Missing in {}", side.label()), egui::Color32::from_rgb(241, 196, 15)),
+            ThreeWayStatus::ChangedIn(side) => (format!("Changed in {}", side.label()), egui::Color32::from_rgb(46, 204, 113)),
+            ThreeWayStatus::Conflict => ("Conflict".to_owned(), egui::Color32::from_rgb(231, 76, 60)),
+            ThreeWayStatus::Unreadable(side) => (format!("⚠ Unreadable in {}", side.label()), egui::Color32::from_rgb(230, 126, 34)),
+        };
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            TableBuilder::new(ui)
+                .striped(true)
+                .column(Column::initial(360.0).resizable(true)) // Path
+                .columns(Column::exact(100.0), 3) // Base / Mine / Theirs
+                .column(Column::remainder()) // Status
+                .header(20.0, |mut header| {
+                    header.col(|ui| { ui.strong("Path"); });
+                    header.col(|ui| { ui.strong("Base"); });
+                    header.col(|ui| { ui.strong("Mine (Source)"); });
+                    header.col(|ui| { ui.strong("Theirs (Dest)"); });
+                    header.col(|ui| { ui.strong("Status"); });
+                })
+                .body(|mut body| {
+                    for entry in rows {
+                        body.row(18.0, |mut row| {
+                            row.col(|ui| { ui.label(&entry.rel_path); });
+                            for (i, side) in Side::ALL.iter().enumerate() {
+                                row.col(|ui| {
+                                    let odd_one_out = matches!(entry.status,
+                                        ThreeWayStatus::ChangedIn(s) | ThreeWayStatus::MissingIn(s) | ThreeWayStatus::Unreadable(s) if s == *side);
+                                    let text = match &entry.copies[i] {
+                                        Some(file) => format_size(file.size, DECIMAL),
+                                        None => "—".to_owned(),
+                                    };
+                                    if odd_one_out {
+                                        ui.label(egui::RichText::new(text).strong().color(egui::Color32::from_rgb(241, 196, 15)));
+                                    } else {
+                                        ui.label(text);
+                                    }
+                                });
+                            }
+                            row.col(|ui| {
+                                let (text, color) = status_text(entry.status);
+                                let response = ui.colored_label(color, text);
+                                if let Some(error) = &entry.error {
+                                    response.on_hover_text(error);
+                                }
+                            });
+                        });
+                    }
+                });
+        });
+    }
+
+    fn show_tree(&mut self, ui: &mut egui::Ui) {
+        let Some(results) = &self.results else { return };
+        let root = self.tree.get_or_insert_with(|| tree::build(results, Path::new(&self.source), Path::new(&self.dest)));
+
+        ui.horizontal(|ui| {
+            for status in NodeStatus::ALL {
+                if root.count(status) > 0 {
+                    ui.colored_label(tree_color(status), format!("■ {} {}", format_count(root.count(status)), status.label()));
+                    ui.separator();
+                }
+            }
+            ui.checkbox(&mut self.tree_hide_identical, "Hide identical");
+        });
+        ui.add_space(5.0);
+
+        let hide_identical = self.tree_hide_identical;
+        egui::ScrollArea::vertical().show(ui, |ui| tree_dir_ui(ui, root, "", hide_identical));
+    }
+
+    fn show_diff_list(&mut self, ui: &mut egui::Ui, files: &[(FileEntry, FileEntry)]) {
+        use egui_extras::{TableBuilder, Column};
+        let mut exclude = None;
+        let mut preview = None;
+        
+        // Only the visible rows are built, so the table scrolls itself and fills the panel
+        TableBuilder::new(ui)
+            .striped(true)
+            .max_scroll_height(f32::INFINITY)
+            .auto_shrink([false; 2])
+            .column(Column::exact(24.0)) // Selected
+            .column(Column::exact(20.0)) // Type icon
+            .column(Column::initial(300.0).resizable(true)) // Path
+            .column(Column::exact(80.0)) // Src Size
+            .column(Column::exact(80.0)) // Dest Size
+            .column(Column::exact(150.0)) // Hashes
+            .column(Column::remainder()) // Actions
+            .header(20.0, |mut header| {
+                header.col(|_| {});
+                header.col(|_| {});
+                header.col(|ui| { self.sort_header(ui, "Path", SortColumn::Path); });
+                header.col(|ui| { self.sort_header(ui, "Src Size", SortColumn::Size); });
+                header.col(|ui| { self.sort_header(ui, "Dest Size", SortColumn::DestSize); });
+                header.col(|ui| { ui.strong("Blake3"); });
+                header.col(|ui| { ui.strong("Actions"); });
+            })
+            .body(|body| {
+                body.rows(18.0, files.len(), |index, mut row| {
+                    let (src, dest) = &files[index];
+                    row.col(|ui| { self.selection_checkbox(ui, &src.rel_path); });
+                    row.col(|ui| { ui.label(diff::file_icon(&src.rel_path)); });
+                    row.col(|ui| {
+                        let text = if self.active_tab == Tab::Permissions {
+                            format!("{}  🔒 {} → {}", src.rel_path, scanner::permissions_text(src), scanner::permissions_text(dest))
+                        } else {
+                            src.rel_path.clone()
+                        };
+                        let response = ui.add(egui::Label::new(text).sense(egui::Sense::click()))
+                            .context_menu(|ui| {
+                                let mut result = Ok(());
+                                ui.menu_button("Source", |ui| result = file_actions(ui, &src.path));
+                                ui.menu_button("Destination", |ui| result = file_actions(ui, &dest.path));
+                                if let Err(e) = result {
+                                    self.status_msg = format!("❌ {}", e);
+                                }
+                                if let Some(dir) = exclude_folder_button(ui, &src.rel_path) {
+                                    exclude = Some(dir);
+                                }
+                            });
+                        if response.clicked() {
+                            preview = Some(vec![("Source", src.path.clone()), ("Destination", dest.path.clone())]);
+                        }
+                    });
+                    row.col(|ui| { ui.label(format_size(src.size, DECIMAL)); });
+                    row.col(|ui| { ui.label(format_size(dest.size, DECIMAL)); });
+                    row.col(|ui| {
+                        let hashes = match (&src.hash, &dest.hash) {
+                            (Some(a), Some(b)) => Some(Some(Ok((a.clone(), b.clone())))),
+                            _ => self.pair_hashes.get(&src.rel_path).cloned(),
+                        };
+                        match hashes {
+                            Some(Some(Ok((a, b)))) => {
+                                // Timestamp-only pairs can hash equal
+                                let op = if a == b { "=" } else { "≠" };
+                                ui.monospace(format!("{} {} {}", &a[..8], op, &b[..8]))
+                                    .on_hover_text(format!("Source: {}\nDestination: {}", a, b));
+                            }
+                            Some(Some(Err(e))) => { ui.label("⚠").on_hover_text(e); }
+                            Some(None) => { ui.spinner(); }
+                            None => {
+                                if ui.small_button("Compute").on_hover_text("Hash both copies with Blake3").clicked() {
+                                    self.request_pair_hash(ui.ctx(), src, dest);
+                                }
+                            }
+                        }
+                    });
+                    row.col(|ui| { 
+                        if ui.button("View Diff").clicked() {
+                            self.open_diff_viewer(ui.ctx(), &src.path, &dest.path, &src.rel_path);
+                        }
+                        if ui.add_enabled(!self.is_scanning && !self.is_syncing, egui::Button::new("Copy now")).clicked() {
+                            let result = CompareResult { different_content: vec![(src.clone(), dest.clone())], ..Default::default() };
+                            self.copy_now(result, self.sync_direction);
+                        }
+                    });
+                });
+            });
+        if let Some(paths) = preview {
+            self.open_preview(ui.ctx(), paths);
+        }
+        if let Some(dir) = exclude {
+            self.exclude_and_rescan(dir);
+        }
+    }
+
+    fn exclude_and_rescan(&mut self, dir: String) {
+        if self.is_scanning || self.is_syncing {
+            return;
+        }
+        self.session_excludes.insert(dir);
+        self.start_comparison();
+    }
+
+    /// Hash a pair in the background; the result lands in `pair_hashes`
+    fn request_pair_hash(&mut self, ctx: &egui::Context, src: &FileEntry, dest: &FileEntry) {
+        self.pair_hashes.insert(src.rel_path.clone(), None);
+        let (rel_path, src_path, dest_path) = (src.rel_path.clone(), src.path.clone(), dest.path.clone());
+        let tx = self.hash_tx.clone();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let hashes = scanner::calculate_hash(&src_path).map_err(|e| format!("Source: {}", e))
+                .and_then(|a| Ok((a, scanner::calculate_hash(&dest_path).map_err(|e| format!("Destination: {}", e))?)));
+            tx.send((rel_path, hashes)).ok();
+            ctx.request_repaint();
+        });
+    }
+
+    /// Mirror Mode deletions go through the confirmation modal first
+    fn request_sync(&mut self) {
+        // Staged syncs never delete, and a mirror with nothing extra has nothing to confirm
+        self.sync_deletions = match &self.results {
+            Some(r) if self.delete_extra && !self.stage_copies => {
+                let selected = self.selected_results(r);
+                match self.sync_direction {
+                    SyncDirection::SourceToDest => selected.missing_in_source.into_iter()
+                        .chain(selected.renamed.into_iter().map(|(_, dest)| dest))
+                        .collect(),
+                    SyncDirection::DestToSource => selected.missing_in_dest.into_iter()
+                        .chain(selected.renamed.into_iter().map(|(src, _)| src))
+                        .collect(),
+                    SyncDirection::Bidirectional => Vec::new(),
+                }
+            }
+            _ => Vec::new(),
+        };
+        if self.sync_deletions.is_empty() {
+            self.start_sync(false);
+        } else {
+            self.sync_understood = false;
+            self.confirm_sync_open = true;
+        }
+    }
+
+    fn handle_shortcuts(&mut self, ctx: &egui::Context) {
+        let idle = !self.is_scanning && !self.is_syncing;
+        if ctx.input_mut(|i| i.consume_shortcut(&SHORTCUT_COMPARE)) && idle {
+            self.start_comparison();
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&SHORTCUT_SYNC)) && idle && self.results.is_some() && !self.read_only_side() && !self.confirm_sync_open {
+            self.request_sync();
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&SHORTCUT_FILTER)) {
+            self.focus_filter = true;
+        }
+        // Esc closes the topmost dialog only
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Escape)) {
+            if let Some((_, reply)) = self.limit_prompt.take() {
+                reply.send(false).ok();
+            } else if self.confirm_sync_open {
+                self.confirm_sync_open = false;
+            } else if self.confirm_delete_extra.is_some() {
+                self.confirm_delete_extra = None;
+            } else if self.diff_open {
+                self.diff_open = false;
+            } else if self.preview_open {
+                self.preview_open = false;
+            }
+        }
+    }
+
+    /// First dropped folder (or archive, manifest, or one of two files) becomes the source, a second one the destination
+    fn handle_dropped_folders(&mut self, ctx: &egui::Context) {
+        let dropped: Vec<PathBuf> = ctx.input(|i| i.raw.dropped_files.iter().filter_map(|f| f.path.clone()).collect());
+        if !dropped.is_empty() {
+            let (mut sides, files): (Vec<_>, Vec<_>) = dropped.into_iter()
+                .partition(|p| p.is_dir() || archive::is_archive(p) || manifest::is_manifest(p));
+            // Exactly two plain files make a file-to-file comparison; otherwise files are ignored
+            let ignored = if sides.is_empty() && files.len() == 2 {
+                sides = files;
+                0
+            } else {
+                files.len()
+            };
+            let mut paths = sides.into_iter().map(|p| p.to_string_lossy().to_string());
+            if let Some(source) = paths.next() {
+                self.source = source;
+            }
+            if let Some(dest) = paths.next() {
+                self.dest = dest;
+            }
+            if ignored > 0 {
+                self.status_msg = format!("Ignored {} dropped file(s) - drop folders, archives or manifests, or exactly two files", ignored);
+            }
+        }
+
+        if ctx.input(|i| !i.raw.hovered_files.is_empty()) {
+            let painter = ctx.layer_painter(egui::LayerId::new(egui::Order::Foreground, egui::Id::new("drop_overlay")));
+            let screen = ctx.screen_rect();
+            painter.rect_filled(screen, 0.0, egui::Color32::from_black_alpha(180));
+            painter.text(
+                screen.center(),
+                egui::Align2::CENTER_CENTER,
+                "📂 Drop folders or files here",
+                egui::FontId::proportional(28.0),
+                egui::Color32::WHITE,
+            );
+        }
+    }
+
+    fn open_diff_viewer(&mut self, ctx: &egui::Context, src_path: &Path, dest_path: &Path, name: &str) {
+        self.diff_open = true;
+        self.diff_file_name = name.to_owned();
+        self.diff_paths = Some((src_path.to_path_buf(), dest_path.to_path_buf()));
+        self.diff_error = None;
+        // The diff below reads through links; their own targets are shown above it
+        self.diff_link_targets = [src_path, dest_path].map(|path| std::fs::read_link(path).ok());
+        self.diff_encoding.clear();
+        self.diff_text_note = None;
+        self.diff_match = 0;
+        self.diff_content.clear();
+        self.diff_rows.clear();
+        
+        self.clear_diff_images();
+        self.diff_image_note.clear();
+        self.diff_mode = DiffMode::Text;
+
+        // Reading and diffing can take seconds for big files; replacing the
+        // receiver makes a still-running load for another file go unheard
+        let (tx, rx) = unbounded();
+        self.diff_rx = Some(rx);
+        let (src_path, dest_path, name) = (src_path.to_path_buf(), dest_path.to_path_buf(), name.to_owned());
+        let options = self.diff_text_options;
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            tx.send(diff::load(&src_path, &dest_path, &name, options)).ok();
+            ctx.request_repaint();
+        });
+    }
+
+    /// Drop the previous file's textures and hex buffers. A texture's GPU
+    /// memory is freed once its last handle is gone, so nothing may keep a clone.
+    fn clear_diff_images(&mut self) {
+        self.diff_texture_src = None;
+        self.diff_texture_dest = None;
+        self.diff_texture_diff = None;
+        self.diff_bytes_src = Vec::new();
+        self.diff_bytes_dest = Vec::new();
+    }
+
+    /// Load thumbnails or snippets for a clicked row into the side panel
+    fn open_preview(&mut self, ctx: &egui::Context, paths: Vec<(&'static str, PathBuf)>) {
+        self.previews.clear();
+        let (tx, rx) = unbounded();
+        self.preview_rx = Some(rx);
+        let files: Vec<PathBuf> = paths.iter().map(|(_, path)| path.clone()).collect();
+        self.preview_paths = paths;
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            tx.send(files.iter().map(|path| diff::preview(path)).collect()).ok();
+            ctx.request_repaint();
+        });
+    }
+
+    fn show_quick_look(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.strong("Quick Look");
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui.small_button("✕").on_hover_text("Close the preview").clicked() {
+                    self.preview_paths.clear();
+                    self.previews.clear();
+                    self.preview_rx = None;
+                }
+            });
+        });
+        ui.separator();
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for (i, (side, path)) in self.preview_paths.iter().enumerate() {
+                ui.label(egui::RichText::new(*side).strong());
+                ui.label(egui::RichText::new(path.to_string_lossy()).small().weak());
+                match self.previews.get(i) {
+                    Some(QuickLook::Image(tex)) => { ui.image((tex.id(), tex.size_vec2())); }
+                    Some(QuickLook::Text(text)) => {
+                        ui.add(egui::Label::new(egui::RichText::new(text).monospace().small()).wrap(false));
+                    }
+                    Some(QuickLook::Unavailable(msg)) => { ui.label(msg); }
+                    None => { ui.spinner(); }
+                }
+                ui.add_space(10.0);
+            }
+        });
+    }
+
+    /// Move a finished load into the viewer state
+    fn apply_diff(&mut self, ctx: &egui::Context, loaded: diff::Loaded) {
+
+        match loaded {
+            diff::Loaded::Text { lines, rows, encoding, truncated } => {
+                if let Some((largest, line_count)) = truncated {
+                    self.diff_text_note = Some(format!(
+                        "File too large to diff ({}) — showing the first {} lines only",
+                        format_size(largest, DECIMAL),
+                        format_count(line_count)
+                    ));
+                }
+                self.diff_encoding = encoding;
+                self.diff_content = lines;
+                self.diff_rows = rows;
+            }
+            diff::Loaded::Image { src, dest, heatmap } => {
+                self.diff_mode = DiffMode::Image;
+                self.diff_texture_src = Some(load_texture(ctx, &src, "src_img"));
+                self.diff_texture_dest = Some(load_texture(ctx, &dest, "dest_img"));
+                match heatmap {
+                    Some((heatmap, changed)) => {
+                        let total = (src.width() as usize * src.height() as usize).max(1);
+                        self.diff_image_note = format!("{} of {} pixels differ ({:.2}%)", changed, total, changed as f64 * 100.0 / total as f64);
+                        self.diff_texture_diff = Some(load_texture(ctx, &heatmap, "diff_img"));
+                    }
+                    None => {
+                        self.diff_image_note = format!(
+                            "Dimensions differ: {}×{} vs {}×{} - pixel comparison unavailable",
+                            src.width(), src.height(), dest.width(), dest.height()
+                        );
+                    }
+                }
+            }
+            diff::Loaded::Binary { src, dest } => {
+                self.diff_mode = DiffMode::Binary;
+                self.diff_bytes_src = src;
+                self.diff_bytes_dest = dest;
+            }
+            diff::Loaded::Error(e) => self.diff_error = Some(e),
+        }
+    }
+}
+
+/// Texture memory held by egui (images, thumbnails, fonts), with a button
+/// dropping the images egui's own loaders keep cached
+fn texture_memory(ui: &mut egui::Ui) {
+    let (count, bytes) = ui.ctx().tex_manager().read().allocated()
+        .fold((0, 0), |(count, bytes), (_, meta)| (count + 1, bytes + meta.bytes_used()));
+    ui.label(egui::RichText::new(format!("🖼 {} textures, {}", count, format_size(bytes, DECIMAL))).small().weak())
+        .on_hover_text("Textures currently allocated by the app. Closing the viewer frees this file's images");
+    if ui.small_button("🧹 Free cached images").on_hover_text("Forget images egui's loaders cached; they reload when shown again").clicked() {
+        ui.ctx().forget_all_images();
+    }
+}
+
+fn load_texture(ctx: &egui::Context, img: &image::RgbaImage, label: &str) -> egui::TextureHandle {
+    let size = [img.width() as _, img.height() as _];
+    let pixels = img.as_flat_samples();
+    let color_image = egui::ColorImage::from_rgba_unmultiplied(size, pixels.as_slice());
+    ctx.load_texture(label, color_image, Default::default())
+}
+
+fn tree_color(status: NodeStatus) -> egui::Color32 {
+    match status {
+        NodeStatus::OnlyInSource => egui::Color32::from_rgb(46, 204, 113),
+        NodeStatus::OnlyInDest => egui::Color32::from_rgb(52, 152, 219),
+        NodeStatus::Different => egui::Color32::from_rgb(231, 76, 60),
+        NodeStatus::TimestampOnly => egui::Color32::from_rgb(230, 126, 34),
+        NodeStatus::Permissions => egui::Color32::from_rgb(155, 89, 182),
+        NodeStatus::Unreadable => egui::Color32::from_rgb(211, 84, 0),
+        NodeStatus::Identical => egui::Color32::GRAY,
+    }
+}
+
+/// Subfolders as collapsible headers with rollup counts, then files colored by status
+fn tree_dir_ui(ui: &mut egui::Ui, dir: &TreeDir, path: &str, hide_identical: bool) {
+    for (name, child) in &dir.dirs {
+        if hide_identical && child.differences() == 0 {
+            continue;
+        }
+        let child_path = if path.is_empty() { name.clone() } else { format!("{}/{}", path, name) };
+        let present: Vec<NodeStatus> = NodeStatus::ALL.into_iter().filter(|&s| child.count(s) > 0).collect();
+        let summary: Vec<String> = present.iter()
+            .map(|&s| format!("{} {}", format_count(child.count(s)), s.label()))
+            .collect();
+        // A folder takes its files' color only when they all agree
+        let differing: Vec<&NodeStatus> = present.iter().filter(|&&s| s != NodeStatus::Identical).collect();
+        let color = match differing.as_slice() {
+            [] => tree_color(NodeStatus::Identical),
+            [only] => tree_color(**only),
+            _ => ui.visuals().strong_text_color(),
+        };
+        egui::CollapsingHeader::new(egui::RichText::new(format!("📁 {}  ({})", name, summary.join(" · "))).color(color))
+            .id_source(&child_path)
+            .show(ui, |ui| tree_dir_ui(ui, child, &child_path, hide_identical));
+    }
+    for (name, &status) in &dir.files {
+        if hide_identical && status == NodeStatus::Identical {
+            continue;
+        }
+        ui.colored_label(tree_color(status), format!("📄 {}", name)).on_hover_text(status.label());
+    }
+}
+
+/// Collapsible list of the files and folders a scan could not list or read
+fn scan_errors_header(ui: &mut egui::Ui, errors: &[(PathBuf, String)]) {
+    egui::CollapsingHeader::new(egui::RichText::new(
+        format!("⚠️ {} files could not be read and are missing from the comparison — see details", errors.len())
+    ).color(egui::Color32::from_rgb(231, 76, 60)))
+        .id_source("scan_errors")
+        .show(ui, |ui| {
+            for (path, msg) in errors {
+                ui.label(format!("{}: {}", path.display(), msg));
+            }
+        });
+}
+
+/// Integer with thousands separators, e.g. 24,311
+fn format_count(n: usize) -> String {
+    let digits = n.to_string();
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Offer to exclude the folder holding `rel_path`; files in the root have none
+fn exclude_folder_button(ui: &mut egui::Ui, rel_path: &str) -> Option<String> {
+    let (dir, _) = rel_path.rsplit_once('/')?;
+    ui.separator();
+    if ui.button(format!("🚫 Exclude {}/ and rescan", dir)).clicked() {
+        ui.close_menu();
+        return Some(dir.to_owned());
+    }
+    None
+}
+
+/// Backslash-escape the characters gitignore patterns treat specially
+fn glob_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '*' | '?' | '[' | ']' | '!' | '#') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Archives and checksum manifests a comparison can be pointed at in place of a folder
+fn pick_archive() -> Option<PathBuf> {
+    rfd::FileDialog::new()
+        .add_filter("Archives", &["zip", "tar", "gz", "tgz"])
+        .add_filter("Checksum manifests", &manifest::EXTENSIONS)
+        .pick_file()
+}
+
+/// Right-click actions for one file; returns the error if launching failed
+fn file_actions(ui: &mut egui::Ui, path: &Path) -> Result<(), String> {
+    let mut result = Ok(());
+    if ui.button("Open file").clicked() {
+        result = open::that(path).map_err(|e| format!("Could not open {}: {}", path.display(), e));
+        ui.close_menu();
+    }
+    if ui.button("Open containing folder").clicked() {
+        result = reveal_in_file_manager(path).map_err(|e| format!("Could not reveal {}: {}", path.display(), e));
+        ui.close_menu();
+    }
+    if ui.button("Copy full path").clicked() {
+        ui.output_mut(|o| o.copied_text = path.display().to_string());
+        ui.close_menu();
+    }
+    result
+}
+
+/// Show `path` selected in Finder/Explorer, or open its folder elsewhere
+fn reveal_in_file_manager(path: &Path) -> std::io::Result<()> {
+    #[cfg(target_os = "macos")]
+    {
+        std::process::Command::new("open").arg("-R").arg(path).spawn().map(|_| ())
+    }
+    #[cfg(target_os = "windows")]
+    {
+        let mut select = std::ffi::OsString::from("/select,");
+        select.push(path);
+        std::process::Command::new("explorer").arg(select).spawn().map(|_| ())
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    {
+        open::that(path.parent().unwrap_or(path))
+    }
+}
+
+/// Modern Premium Styling, with the same rounding and accent in both themes
+fn themed_visuals(dark: bool) -> egui::Visuals {
+    let mut visuals = if dark { egui::Visuals::dark() } else { egui::Visuals::light() };
+    visuals.window_rounding = egui::Rounding::same(12.0);
+    visuals.widgets.noninteractive.rounding = egui::Rounding::same(8.0);
+    visuals.widgets.active.rounding = egui::Rounding::same(8.0);
+    visuals.widgets.inactive.rounding = egui::Rounding::same(8.0);
+    visuals.selection.bg_fill = egui::Color32::from_rgb(52, 152, 219); // Premium Blue
+    visuals
+}
+
+/// Logical cores available to the scan pool
+fn max_threads() -> usize {
+    thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
+/// Sidecar file for the hash cache, next to eframe's own app state
+fn hash_cache_path() -> Option<PathBuf> {
+    eframe::storage_dir(APP_ID).map(|dir| dir.join("hash_cache.json"))
+}
+
+fn log_path() -> Option<PathBuf> {
+    eframe::storage_dir(APP_ID).map(|dir| dir.join("omnidiff.log"))
+}
+
+fn profiles_path() -> Option<PathBuf> {
+    eframe::storage_dir(APP_ID).map(|dir| dir.join("profiles.json"))
+}
+
+/// Saved profiles; a missing or unreadable file means there are none
+fn load_profiles() -> BTreeMap<String, Settings> {
+    profiles_path()
+        .and_then(|path| std::fs::read(path).ok())
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default()
+}
+
+fn save_profiles(profiles: &BTreeMap<String, Settings>) -> Result<(), String> {
+    let path = profiles_path().ok_or("No folder for app data on this system")?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let data = serde_json::to_vec_pretty(profiles).map_err(|e| e.to_string())?;
+    std::fs::write(path, data).map_err(|e| e.to_string())
+}
+
+/// Seconds as mm:ss, or h:mm:ss past an hour
+fn format_duration(secs: u64) -> String {
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{:02}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+/// A run's wall time as "850ms", "42.3s", "2m 13s" or "1h 05m"
+fn format_elapsed(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    if secs >= 3600 {
+        format!("{}h {:02}m", secs / 3600, secs / 60 % 60)
+    } else if secs >= 60 {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    } else if secs >= 1 {
+        format!("{:.1}s", elapsed.as_secs_f64())
+    } else {
+        format!("{}ms", elapsed.as_millis())
+    }
+}
+
+/// The folder a sync writes into (and deletes from in Mirror Mode)
+fn receiving_side(direction: SyncDirection) -> &'static str {
+    match direction {
+        SyncDirection::SourceToDest => "destination",
+        SyncDirection::DestToSource => "source",
+        SyncDirection::Bidirectional => "both folders",
+    }
+}
+
+/// A diff line with search hits highlighted. When the line has word-level
+/// `emphasis`, only those words keep the line color; the rest is neutral.
+fn search_job(prefix: &str, text: &str, color: egui::Color32, emphasis: &[Range<usize>], query: &str, current: bool) -> egui::text::LayoutJob {
+    let font = egui::FontId::monospace(12.0);
+    let plain = if emphasis.is_empty() { color } else { egui::Color32::LIGHT_GRAY };
+    let normal = egui::TextFormat::simple(font.clone(), plain);
+    let changed = egui::TextFormat {
+        background: color.gamma_multiply(0.3),
+        ..egui::TextFormat::simple(font.clone(), color)
+    };
+    let hit = egui::TextFormat {
+        background: if current { egui::Color32::from_rgb(230, 126, 34) } else { egui::Color32::from_rgb(120, 100, 20) },
+        ..egui::TextFormat::simple(font.clone(), egui::Color32::WHITE)
+    };
+
+    let hits = diff::find_ignore_case(text, query);
+    let mut cuts: Vec<usize> = hits.iter().chain(emphasis)
+        .flat_map(|r| [r.start, r.end])
+        .chain([0, text.len()])
+        .collect();
+    cuts.sort_unstable();
+    cuts.dedup();
+
+    let mut job = egui::text::LayoutJob::default();
+    job.append(prefix, 0.0, egui::TextFormat::simple(font, color));
+    for span in cuts.windows(2) {
+        let (start, end) = (span[0], span[1]);
+        let inside = |ranges: &[Range<usize>]| ranges.iter().any(|r| r.start <= start && end <= r.end);
+        let format = if inside(&hits) {
+            hit.clone()
+        } else if inside(emphasis) {
+            changed.clone()
+        } else {
+            normal.clone()
+        };
+        job.append(&text[start..end], 0.0, format);
+    }
+    job
+}
+
+/// One hex dump row: offset, hex bytes and ASCII, with bytes that differ from `other` highlighted
+fn hex_row_job(bytes: &[u8], other: &[u8], row_start: usize) -> egui::text::LayoutJob {
+    let font = egui::FontId::monospace(12.0);
+    let normal = egui::TextFormat::simple(font.clone(), egui::Color32::GRAY);
+    let highlight = egui::TextFormat {
+        background: egui::Color32::from_rgb(120, 40, 40),
+        ..egui::TextFormat::simple(font, egui::Color32::WHITE)
+    };
+
+    let mut job = egui::text::LayoutJob::default();
+    job.append(&format!("{:08x}  ", row_start), 0.0, normal.clone());
+
+    let row = bytes.get(row_start..).unwrap_or(&[]);
+    let row = &row[..row.len().min(diff::HEX_ROW_BYTES)];
+    let format = |i: usize| if bytes.get(i) != other.get(i) { highlight.clone() } else { normal.clone() };
+
+    for (i, b) in row.iter().enumerate() {
+        job.append(&format!("{:02x} ", b), 0.0, format(row_start + i));
+    }
+    // Pad a short final row so the ASCII column lines up
+    job.append(&"   ".repeat(diff::HEX_ROW_BYTES - row.len()), 0.0, normal.clone());
+    job.append(" ", 0.0, normal.clone());
+    for (i, &b) in row.iter().enumerate() {
+        let c = if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' };
+        job.append(&c.to_string(), 0.0, format(row_start + i));
+    }
+    job
+}
+
+impl eframe::App for FolderCompareApp {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, SETTINGS_KEY, &self.settings());
+    }
+
+    // Keeps scroll positions, collapsing headers and window layout across launches
+    fn persist_egui_memory(&self) -> bool {
+        true
+    }
+
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        // eframe swaps in stock visuals when the OS theme changes, so re-apply ours
+        let visuals = themed_visuals(self.theme.is_dark(frame.info().system_theme));
+        if ctx.style().visuals != visuals {
+            ctx.set_visuals(visuals);
+        }
+
+        // Poll for updates
+        let mut cancelled = false;
+        if let Some(rx) = &self.rx {
+            while let Ok(status) = rx.try_recv() {
+                match status {
+                    ScanStatus::ScanningBoth => {
+                        self.status_msg = "📂 Scanning Both Folders...".into();
+                        self.progress = 0.15;
+                        self.bytes_progress = (0, 0);
+                    },
+                    ScanStatus::Scanned(count) => {
+                        self.status_msg = format!("📂 Scanned {} files...", format_count(count));
+                    },
+                    ScanStatus::Hashing(current, total) => {
+                        self.status_msg = format!("⚡ Verifying Content (Blake3) - {}/{}{}", current, total, self.throughput_text());
+                        // Nothing to hash means the verification phase is already done
+                        self.progress = if total == 0 { 1.0 } else { 0.4 + 0.6 * self.byte_fraction(current, total) };
+                    },
+                    ScanStatus::Syncing(current, total, rel_dir) => {
+                        let folder = if rel_dir.is_empty() { String::new() } else { format!(" {}/", rel_dir) };
+                        self.status_msg = format!("♻️ Syncing{} - {}/{} operations{}", folder, current, total, self.throughput_text());
+                        // Deletes carry no bytes, so the operation count still bounds the bar
+                        self.progress = if total == 0 { 1.0 } else { self.byte_fraction(current, total).min(current as f32 / total as f32) };
+                        self.sync_ops = (current, total);
+                    },
+                    ScanStatus::Bytes(done, total) => {
+                        self.bytes_progress = (done, total);
+                        let now = Instant::now();
+                        self.rate_samples.push_back((now, done));
+                        // Rolling window: keep only the last few seconds of samples
+                        while self.rate_samples.len() > 2 && now - self.rate_samples[0].0 > RATE_WINDOW {
+                            self.rate_samples.pop_front();
+                        }
+                    },
+                    ScanStatus::FileProgress(rel_path, done, size) => {
+                        self.file_progress = (done < size).then_some((rel_path, done, size));
+                    },
+                    ScanStatus::Verified(count) => {
+                        self.sync_verified = Some(count);
+                    },
+                    ScanStatus::LimitReached(count, reply) => {
+                        self.limit_prompt = Some((count, reply));
+                    },
+                    ScanStatus::Staged(dir) => {
+                        self.sync_staged.push(dir);
+                    },
+                    ScanStatus::BackedUp(count) => {
+                        self.sync_backed_up = Some(count);
+                    },
+                    ScanStatus::SyncError(path, msg) => {
+                        self.sync_errors.push((path, msg));
+                    },
+                    ScanStatus::Planned(action, direction, rel_path) => {
+                        self.preview_ops.push((action, direction, rel_path));
+                    },
+                    ScanStatus::Complete => { 
+                        if self.is_syncing && self.sync_dry_run {
+                            self.status_msg = format!("📋 Sync preview ready - {} planned operations", self.preview_ops.len());
+                            self.preview_open = true;
+                            self.is_syncing = false;
+                        } else if self.is_syncing {
+                            let mut verified = self.sync_verified
+                                .map(|n| format!(" · {} files verified", n))
+                                .unwrap_or_default();
+                            if let Some(n) = self.sync_backed_up {
+                                verified.push_str(&format!(" · {} backups created", n));
+                            }
+                            for dir in &self.sync_staged {
+                                verified.push_str(&format!(" · staged in {}", dir.display()));
+                            }
+                            self.status_msg = if self.sync_errors.is_empty() {
+                                format!("✅ Sync Complete{}{}", self.elapsed_text(), verified)
+                            } else {
+                                format!("⚠️ Sync finished with {} failed operations{}{}", self.sync_errors.len(), self.elapsed_text(), verified)
+                            };
                             self.is_syncing = false;
                         }
                         self.progress = 1.0; 
                     },
-                    ScanStatus::Error(e) => { self.status_msg = format!("❌ Error: {}", e); },
+                    ScanStatus::Cancelled => {
+                        if self.is_syncing {
+                            let (done, total) = self.sync_ops;
+                            self.status_msg = format!("⏹ Sync cancelled - {}/{} operations were already applied", done, total);
+                        } else {
+                            self.status_msg = "⏹ Comparison cancelled".into();
+                        }
+                        cancelled = true;
+                        break;
+                    },
                 }
             }
         }
+
+        if cancelled {
+            // Drop the channels so late events from the winding-down worker are ignored
+            self.is_scanning = false;
+            self.is_syncing = false;
+            self.progress = 0.0;
+            self.rx = None;
+            self.result_rx = None;
+            self.three_way_rx = None;
+            self.duplicates_rx = None;
+            self.content_rx = None;
+            self.manifest_rx = None;
+            self.found_rx = None;
+            self.live_different.clear();
+        }
+
+        if let Some(rx) = &self.found_rx {
+            self.live_different.extend(rx.try_iter());
+        }
+        while let Ok((rel_path, hashes)) = self.hash_rx.try_recv() {
+            // Ignore hashes requested before a new comparison cleared the map
+            if let Some(slot @ None) = self.pair_hashes.get_mut(&rel_path) {
+                *slot = Some(hashes);
+            }
+        }
+
+        if let Some(rx) = &self.preview_rx {
+            if let Ok(previews) = rx.try_recv() {
+                self.preview_rx = None;
+                self.previews = previews.into_iter().enumerate().map(|(i, preview)| match preview {
+                    diff::Preview::Image(img) => QuickLook::Image(load_texture(ctx, &img, &format!("preview_{}", i))),
+                    diff::Preview::Text(text) => QuickLook::Text(text),
+                    diff::Preview::Unavailable(msg) => QuickLook::Unavailable(msg),
+                }).collect();
+            }
+        }
+
+        if !self.diff_open {
+            self.diff_rx = None;
+            self.clear_diff_images();
+        }
+        if let Some(rx) = &self.diff_rx {
+            if let Ok(loaded) = rx.try_recv() {
+                self.diff_rx = None;
+                self.apply_diff(ctx, loaded);
+            }
+        }
+
+        if let Some(rx) = &self.duplicates_rx {
+            if let Ok(res) = rx.try_recv() {
+                match res {
+                    Ok(found) => {
+                        let unreadable = if found.errors.is_empty() {
+                            String::new()
+                        } else {
+                            format!(", {} unreadable", format_count(found.errors.len()))
+                        };
+                        self.status_msg = format!("✅ Duplicate Search Complete{} - {} groups{}", self.elapsed_text(), format_count(found.groups.len()), unreadable);
+                        self.duplicates = Some(found);
+                    },
+                    Err(e) => {
+                        self.status_msg = format!("❌ Failed: {}", e);
+                    }
+                }
+                self.is_scanning = false;
+                self.rx = None;
+                self.duplicates_rx = None;
+            }
+        }
+
+        if let Some(rx) = &self.content_rx {
+            if let Ok(res) = rx.try_recv() {
+                match res {
+                    Ok(data) => {
+                        self.status_msg = format!("✅ Content Match Complete{} - {} moved or renamed", self.elapsed_text(), format_count(data.moved.len()));
+                        self.content_matches = Some(data);
+                    },
+                    Err(e) => {
+                        self.status_msg = format!("❌ Failed: {}", e);
+                    }
+                }
+                self.is_scanning = false;
+                self.rx = None;
+                self.content_rx = None;
+            }
+        }
+
+        if let Some(rx) = &self.manifest_rx {
+            if let Ok(res) = rx.try_recv() {
+                self.status_msg = match res {
+                    Ok(report) => {
+                        let skipped = if report.errors.is_empty() { String::new() } else { format!(", {} unreadable left out", report.errors.len()) };
+                        format!("📜 Manifest written{} - {} files ({}){} to {}",
+                            self.elapsed_text(), format_count(report.files), format_size(report.bytes, DECIMAL), skipped, report.path.display())
+                    }
+                    Err(e) => format!("❌ Manifest export failed: {}", e),
+                };
+                self.is_scanning = false;
+                self.rx = None;
+                self.manifest_rx = None;
+            }
+        }
+
+        if let Some(rx) = &self.three_way_rx {
+            if let Ok(res) = rx.try_recv() {
+                match res {
+                    Ok(data) => {
+                        self.status_msg = format!(
+                            "✅ Three-way Comparison Complete{} - {} conflicts",
+                            self.elapsed_text(),
+                            format_count(data.count(ThreeWayStatus::Conflict))
+                        );
+                        self.three_way = Some(data);
+                    },
+                    Err(e) => {
+                        self.status_msg = format!("❌ Failed: {}", e);
+                    }
+                }
+                self.is_scanning = false;
+                self.rx = None;
+                self.three_way_rx = None;
+            }
+        }
         
         if let Some(rx) = &self.result_rx {
              if let Ok(res) = rx.try_recv() {
                  match res {
                      Ok(data) => {
+                         self.summary = Some(ResultSummary::from_result(&data));
+                         // Everything starts selected so a plain sync behaves as before
+                         self.selected = data.missing_in_dest.iter()
+                             .chain(data.missing_in_source.iter())
+                             .chain(data.different_content.iter().chain(&data.timestamp_only).chain(&data.renamed).chain(&data.permissions_differ).map(|(src, _)| src))
+                             .map(|f| f.rel_path.clone())
+                             .collect();
+                         let unreadable = data.scan_errors.len() + data.hash_errors.len();
+                         // Say how much a clean result is worth
+                         let done = if data.metadata_only { "Metadata comparison (no content read) complete" } else { "Comparison Complete" };
+                         let done = format!("{}{}", done, self.elapsed_text());
+                         self.status_msg = if unreadable > 0 {
+                             format!("⚠️ {} - {} files could not be read", done, format_count(unreadable))
+                         } else if data.filtered_count > 0 {
+                             format!("✅ {} - {} files skipped by filters", done, format_count(data.filtered_count))
+                         } else {
+                             format!("✅ {}", done)
+                         };
                          self.results = Some(data);
-                         self.status_msg = "✅ Comparison Complete".into();
+                         self.tree = None;
                      },
                      Err(e) => {
                          self.status_msg = format!("❌ Failed: {}", e);
@@ -331,11 +2586,33 @@ impl eframe::App for FolderCompareApp {
                  self.is_scanning = false;
                  self.rx = None;
                  self.result_rx = None;
+                 self.found_rx = None;
+                 self.live_different.clear();
              }
         }
 
+        self.handle_dropped_folders(ctx);
+        self.handle_shortcuts(ctx);
+
+        if !self.preview_paths.is_empty() {
+            egui::SidePanel::right("quick_look")
+                .resizable(true)
+                .default_width(300.0)
+                .show(ctx, |ui| self.show_quick_look(ui));
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
             // 1. Header
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Min), |ui| {
+                let hint = match self.theme {
+                    ThemePreference::Dark => "Dark theme (click for light)",
+                    ThemePreference::Light => "Light theme (click to follow the system)",
+                    ThemePreference::System => "Following the system theme (click for dark)",
+                };
+                if ui.button(self.theme.icon()).on_hover_text(hint).clicked() {
+                    self.theme = self.theme.next();
+                }
+            });
             ui.vertical_centered(|ui| {
                 ui.heading("🚀 OmniDiff Pro");
                 ui.label(egui::RichText::new("Ultra-parallel Sync Engine (Blake3)").color(egui::Color32::GRAY));
@@ -346,7 +2623,11 @@ impl eframe::App for FolderCompareApp {
             egui::Frame::group(ui.style())
                 .inner_margin(egui::Margin::same(15.0))
                 .show(ui, |ui| {
-                    ui.label(egui::RichText::new("Configuration").strong());
+                    ui.horizontal(|ui| {
+                        ui.label(egui::RichText::new("Configuration").strong());
+                        ui.add_space(20.0);
+                        self.profile_bar(ui);
+                    });
                     ui.add_space(5.0);
                     
                     egui::Grid::new("inputs_grid").spacing([10.0, 10.0]).striped(false).show(ui, |ui| {
@@ -359,6 +2640,17 @@ impl eframe::App for FolderCompareApp {
                                     self.source = path.to_string_lossy().to_string();
                                 }
                             }
+                            if ui.button("📄").on_hover_text("Pick a single file to diff against another file").clicked() {
+                                if let Some(path) = rfd::FileDialog::new().pick_file() {
+                                    self.source = path.to_string_lossy().to_string();
+                                }
+                            }
+                            if ui.button("📦").on_hover_text("Pick a .zip or .tar archive, or a .sha256 / .b3sum manifest, instead").clicked() {
+                                if let Some(path) = pick_archive() {
+                                    self.source = path.to_string_lossy().to_string();
+                                }
+                            }
+                            self.recent_menu(ui, "recent_source");
                         });
                         ui.end_row();
 
@@ -371,16 +2663,193 @@ impl eframe::App for FolderCompareApp {
                                     self.dest = path.to_string_lossy().to_string();
                                 }
                             }
+                            if ui.button("📄").on_hover_text("Pick a single file to diff against another file").clicked() {
+                                if let Some(path) = rfd::FileDialog::new().pick_file() {
+                                    self.dest = path.to_string_lossy().to_string();
+                                }
+                            }
+                            if ui.button("📦").on_hover_text("Pick a .zip or .tar archive, or a .sha256 / .b3sum manifest, instead").clicked() {
+                                if let Some(path) = pick_archive() {
+                                    self.dest = path.to_string_lossy().to_string();
+                                }
+                            }
+                            self.recent_menu(ui, "recent_dest");
+                        });
+                        ui.end_row();
+
+                        ui.label("Base Folder:");
+                        ui.horizontal(|ui| {
+                            ui.add(egui::TextEdit::singleline(&mut self.base)
+                                .desired_width(400.0)
+                                .hint_text("Optional: common ancestor for a three-way comparison"));
+                            if ui.button("📂 Browse").clicked() {
+                                if let Some(path) = rfd::FileDialog::new().pick_folder() {
+                                    self.base = path.to_string_lossy().to_string();
+                                }
+                            }
+                        });
+                        ui.end_row();
+
+                        // Exclusions
+                        ui.label("Exclude Patterns:");
+                        ui.add(egui::TextEdit::multiline(&mut self.exclude_patterns)
+                            .desired_width(400.0)
+                            .desired_rows(3)
+                            .hint_text("node_modules/\ntarget/\n*.log\n!keep.log"));
+                        ui.end_row();
+
+                        // Filters
+                        ui.label("Size Filter:");
+                        ui.horizontal(|ui| {
+                            ui.checkbox(&mut self.min_size_enabled, "At least");
+                            ui.add_enabled(self.min_size_enabled, egui::DragValue::new(&mut self.min_size_mb)
+                                .clamp_range(0.0..=f64::MAX).speed(1.0).suffix(" MB"));
+                            ui.checkbox(&mut self.max_size_enabled, "At most");
+                            ui.add_enabled(self.max_size_enabled, egui::DragValue::new(&mut self.max_size_mb)
+                                .clamp_range(0.0..=f64::MAX).speed(1.0).suffix(" MB"));
+                        });
+                        ui.end_row();
+
+                        ui.label("Max Depth:");
+                        ui.horizontal(|ui| {
+                            ui.checkbox(&mut self.max_depth_enabled, "");
+                            ui.add_enabled(self.max_depth_enabled, egui::DragValue::new(&mut self.max_depth)
+                                .clamp_range(1..=64).suffix(" levels"))
+                                .on_hover_text("1 = only files directly in the chosen folders. Unticked = unlimited");
+                        });
+                        ui.end_row();
+
+                        ui.label("Modified After:");
+                        ui.horizontal(|ui| {
+                            ui.checkbox(&mut self.modified_after_enabled, "");
+                            ui.add_enabled_ui(self.modified_after_enabled, |ui| {
+                                ui.add(egui_extras::DatePickerButton::new(&mut self.modified_after));
+                            });
+                        });
+                        ui.end_row();
+
+                        ui.label("Extensions:");
+                        ui.horizontal(|ui| {
+                            ui.add(egui::TextEdit::singleline(&mut self.include_extensions)
+                                .desired_width(190.0)
+                                .hint_text("Only: jpg, raw, mp4"));
+                            ui.add(egui::TextEdit::singleline(&mut self.exclude_extensions)
+                                .desired_width(190.0)
+                                .hint_text("Never: tmp, log"));
                         });
                         ui.end_row();
                     });
                     
                     ui.add_space(10.0);
                     ui.horizontal(|ui| {
-                        ui.checkbox(&mut self.delete_extra, "🗑 Delete extra files in destination (Mirror Mode)");
+                        ui.label("Sync direction:");
+                        ui.radio_value(&mut self.sync_direction, SyncDirection::SourceToDest, "Source → Destination");
+                        ui.radio_value(&mut self.sync_direction, SyncDirection::DestToSource, "Destination → Source");
+                        ui.radio_value(&mut self.sync_direction, SyncDirection::Bidirectional, "⇄ Both ways (newer wins)");
+                    });
+                    ui.horizontal(|ui| {
+                        let mirror_label = format!("🗑 Delete extra files in {} (Mirror Mode)", receiving_side(self.sync_direction));
+                        ui.add_enabled(self.sync_direction != SyncDirection::Bidirectional,
+                            egui::Checkbox::new(&mut self.delete_extra, mirror_label));
+                        if self.delete_extra {
+                            ui.checkbox(&mut self.permanent_delete, "Delete permanently (skip trash)");
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.only_overwrite_older, "🕒 Only overwrite older files")
+                            .on_hover_text("Different files are only replaced when the incoming copy has a strictly newer modification time");
+                        ui.checkbox(&mut self.preserve_timestamps, "📅 Preserve timestamps on copied files");
+                        ui.checkbox(&mut self.stage_copies, "📥 Stage instead of overwrite")
+                            .on_hover_text("Copy new and changed files into a timestamped __omnidiff_staging_ folder of the receiving side, keeping their paths, so they can be reviewed before replacing anything. Nothing is overwritten or deleted");
+                        ui.add_enabled(!self.stage_copies, egui::Checkbox::new(&mut self.backup_overwritten, "🗄 Backup before overwrite"))
+                            .on_hover_text("Move each file about to be replaced into a timestamped __omnidiff_backup_ folder of its side, keeping its path");
+                        if cfg!(unix) {
+                            ui.add_enabled(!self.stage_copies, egui::Checkbox::new(&mut self.fix_permissions, "🔒 Fix permissions"))
+                                .on_hover_text("Files listed under Permissions get the sending side's mode (and owner, when compared); their content is left alone");
+                            ui.checkbox(&mut self.apply_permissions, "🔏 Apply source permissions")
+                                .on_hover_text("Explicitly give every copied file its source's mode, reporting any that can't be set");
+                            if self.apply_permissions {
+                                ui.checkbox(&mut self.apply_dir_permissions, "Folders too")
+                                    .on_hover_text("Also give the folders the copies land in the mode of their source folder");
+                            }
+                        }
+                        ui.checkbox(&mut self.verify_copies, "🔐 Verify after copy")
+                            .on_hover_text("Re-hash every copied file and compare it with the source; mismatches are retried once, then reported");
+                        ui.label("Retries:");
+                        ui.add(egui::DragValue::new(&mut self.sync_retries).clamp_range(0..=10))
+                            .on_hover_text("Extra attempts, with growing pauses, for copies that fail with a transient IO error (e.g. a flaky network share)");
+                        ui.label("Limit to");
+                        ui.add(egui::DragValue::new(&mut self.bandwidth_limit_mb).speed(0.5).clamp_range(0.0..=10_000.0).suffix(" MB/s"))
+                            .on_hover_text("Cap the combined copy rate of a sync, 0 = unlimited");
+                    });
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.case_insensitive, "🔠 Case-insensitive path matching");
+                        ui.checkbox(&mut self.follow_symlinks, "🔗 Follow symlinks")
+                            .on_hover_text("Off: links are listed as entries of their own. On: linked files and folders are scanned, skipping link loops");
+                        ui.add_enabled(!self.follow_symlinks, egui::Checkbox::new(&mut self.compare_link_targets, "🎯 Compare link targets"))
+                            .on_hover_text("Links that point somewhere else count as different, even when what they point to is identical");
+                        if cfg!(unix) {
+                            ui.checkbox(&mut self.compare_permissions, "🔒 Compare permissions")
+                                .on_hover_text("Files with identical content but a different Unix mode (e.g. a lost executable bit) are listed under Permissions");
+                            if self.compare_permissions {
+                                ui.checkbox(&mut self.compare_owner, "Owner too")
+                                    .on_hover_text("Also compare the owning user and group");
+                            }
+                        }
+                        ui.checkbox(&mut self.track_dirs, "📁 Track folders")
+                            .on_hover_text("Also compare folders, so syncing to the destination recreates empty ones");
+                        ui.checkbox(&mut self.skip_hidden, "🙈 Skip hidden files")
+                            .on_hover_text("Ignore dotfiles and hidden/system files, and don't descend into hidden folders such as .git. Untick to include them");
+                        ui.checkbox(&mut self.use_hash_cache, "💾 Cache hashes between runs")
+                            .on_hover_text("Skip re-hashing files whose size and modification time are unchanged since the last comparison");
+                        let log_hint = log_path().map_or("No config folder available".to_owned(), |p| format!("Writes to {}", p.display()));
+                        if ui.checkbox(&mut self.verbose_logging, "📝 Verbose logging")
+                            .on_hover_text(format!("Log every classification, skipped file and sync operation, e.g. to send along with a bug report. {}", log_hint))
+                            .changed()
+                        {
+                            self.apply_verbose_logging();
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.radio_value(&mut self.check_content, false, "⚡ Metadata only (fast)")
+                            .on_hover_text("Compare sizes and modification times without opening a single file: a quick smoke check for slow network mounts, but changed bytes at an unchanged size and time go unnoticed");
+                        ui.add_enabled_ui(!self.check_content, |ui| {
+                            ui.label("Timestamp tolerance:");
+                            ui.add(egui::DragValue::new(&mut self.mtime_tolerance).clamp_range(0..=3600).suffix(" s"))
+                                .on_hover_text("Modification times this close count as equal (FAT stores them in 2 second steps)");
+                        });
+                    });
+                    ui.horizontal(|ui| {
+                        ui.radio_value(&mut self.check_content, true, "🔍 Verify content:");
+                        ui.add_enabled_ui(self.check_content, |ui| {
+                            ui.radio_value(&mut self.hash_mode, HashMode::Smart, "Smart (size+short-circuit)");
+                            ui.add_enabled(self.hash_mode == HashMode::Smart, egui::DragValue::new(&mut self.partial_chunk_kb)
+                                .clamp_range(4..=65536).prefix("sample ").suffix(" KB"))
+                                .on_hover_text("Read from each end of a file before deciding on a full hash. Larger samples catch more differences in big files (VM images, video) without a full read, but cost more per file");
+                            ui.radio_value(&mut self.hash_mode, HashMode::AlwaysFull, "Always full hash")
+                                .on_hover_text("Hash every common file completely, even when sizes differ");
+                            ui.checkbox(&mut self.detect_renames, "🔀 Detect renames")
+                                .on_hover_text("Hash files missing on one side against extra files of the same size, and list identical ones as renamed or moved instead");
+                            ui.checkbox(&mut self.ignore_line_endings, "↵ Ignore line endings")
+                                .on_hover_text("Re-read text files whose contents differ and count them as identical when only CRLF vs LF line endings changed");
+                        });
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Worker threads:");
+                        ui.add(egui::Slider::new(&mut self.threads, 1..=max_threads()))
+                            .on_hover_text("Fewer threads keep the machine responsive; spinning disks often hash faster with only a few");
+                        ui.separator();
+                        ui.label("Ask after");
+                        ui.add(egui::DragValue::new(&mut self.scan_limit).speed(10_000.0).clamp_range(0..=usize::MAX).suffix(" files"))
+                            .on_hover_text("Pause and ask before scanning past this many files, in case the wrong folder (say, a whole drive) was picked. 0 = never ask");
                     });
                     ui.add_space(5.0);
-                    ui.label(egui::RichText::new("ℹ️ Deep Content Verification (Blake3 mmap) enabled").small().italics());
+                    let mode_note = if self.check_content {
+                        "ℹ️ Deep Content Verification (Blake3 mmap) enabled"
+                    } else {
+                        "ℹ️ Metadata comparison (no content read): size and modification time only"
+                    };
+                    ui.label(egui::RichText::new(mode_note).small().italics());
                 });
             
             ui.add_space(15.0);
@@ -391,38 +2860,153 @@ impl eframe::App for FolderCompareApp {
                     .min_size(egui::vec2(200.0, 40.0))
                     .fill(if self.is_scanning { egui::Color32::from_rgb(60, 60, 60) } else { egui::Color32::from_rgb(52, 152, 219) });
                 
-                if ui.add_enabled(!self.is_scanning, btn).clicked() {
+                if ui.add_enabled(!self.is_scanning, btn).on_hover_text(ctx.format_shortcut(&SHORTCUT_COMPARE)).clicked() {
                     self.start_comparison();
                 }
+                if ui.add_enabled(self.can_refresh(), egui::Button::new("🔄 Refresh Changed Folders"))
+                    .on_hover_text("Re-scan only folders whose modification time changed since the last comparison. \
+                        Rewriting a file in place doesn't touch its folder, so run a full comparison to catch such edits")
+                    .clicked()
+                {
+                    self.start_refresh();
+                }
+                if ui.add_enabled(!self.is_scanning && !self.is_syncing, egui::Button::new("🧬 Find Duplicates in Source"))
+                    .on_hover_text("Scan only the source folder for byte-identical files")
+                    .clicked()
+                {
+                    self.start_duplicate_scan();
+                }
+                if ui.add_enabled(!self.is_scanning && !self.is_syncing, egui::Button::new("🧭 Match by Content"))
+                    .on_hover_text("Pair files across both folders by their Blake3 hash, ignoring names and paths, to find moved and renamed files")
+                    .clicked()
+                {
+                    self.start_content_match();
+                }
+                if ui.add_enabled(!self.is_scanning && !self.is_syncing, egui::Button::new("📜 Export Manifest"))
+                    .on_hover_text("Hash every file in the source folder and save a b3sum-compatible manifest, \
+                        which can later be compared against in place of a folder")
+                    .clicked()
+                {
+                    self.start_manifest_export();
+                }
                 
                 ui.add_space(10.0);
                 
                 if self.is_scanning || self.is_syncing {
-                     ui.add(egui::ProgressBar::new(self.progress).show_percentage().animate(true));
-                     ui.label(egui::RichText::new(&self.status_msg).strong());
+                     let paused = self.is_syncing && self.pause_flag.load(Ordering::Relaxed);
+                     ui.add(egui::ProgressBar::new(self.progress).show_percentage().animate(!paused));
+                     // A single huge file can hold the main bar still for minutes
+                     if let Some((rel_path, done, size)) = self.file_progress.as_ref().filter(|_| self.is_scanning) {
+                         ui.add(egui::ProgressBar::new(*done as f32 / *size as f32).text(format!("{} — {} of {}", rel_path, format_size(*done, DECIMAL), format_size(*size, DECIMAL))));
+                     }
+                     if paused {
+                         let (done, total) = self.sync_ops;
+                         ui.label(egui::RichText::new(format!("⏸ Paused — {}/{} operations", done, total)).strong());
+                     } else {
+                         ui.label(egui::RichText::new(&self.status_msg).strong());
+                     }
+
+                     let cancelling = self.cancel_flag.load(Ordering::Relaxed);
+                     ui.horizontal(|ui| {
+                         if self.is_syncing {
+                             let label = if paused { "▶ Resume" } else { "⏸ Pause" };
+                             if ui.add_enabled(!cancelling, egui::Button::new(label)).clicked() {
+                                 self.toggle_pause();
+                             }
+                         }
+                         if ui.add_enabled(!cancelling, egui::Button::new("⏹ Cancel")).clicked() {
+                             self.cancel_operation();
+                         }
+                     });
                 } else {
                      ui.label(&self.status_msg);
 
-                     if self.results.is_some() {
+                     if self.results.is_some() && !self.read_only_side() {
                          ui.add_space(10.0);
-                         let sync_btn = egui::Button::new(egui::RichText::new("⚡ SYNC TO DESTINATION").size(14.0).strong())
+                         let sync_label = match self.sync_direction {
+                             SyncDirection::SourceToDest => "⚡ SYNC TO DESTINATION",
+                             SyncDirection::DestToSource => "⚡ SYNC TO SOURCE",
+                             SyncDirection::Bidirectional => "⚡ SYNC BOTH WAYS",
+                         };
+                         let sync_btn = egui::Button::new(egui::RichText::new(sync_label).size(14.0).strong())
                              .min_size(egui::vec2(250.0, 35.0))
                              .fill(egui::Color32::from_rgb(46, 204, 113)); // Premium Green
                          
-                         if ui.add(sync_btn).clicked() {
-                             if self.delete_extra {
-                                 self.confirm_sync_open = true;
-                             } else {
-                                 self.start_sync();
+                         ui.horizontal(|ui| {
+                             if ui.add(sync_btn).on_hover_text(ctx.format_shortcut(&SHORTCUT_SYNC)).clicked() {
+                                 self.request_sync();
                              }
-                         }
+                             let preview_btn = egui::Button::new(egui::RichText::new("📋 Preview Sync").size(14.0))
+                                 .min_size(egui::vec2(150.0, 35.0));
+                             if ui.add(preview_btn).clicked() {
+                                 self.start_sync(true);
+                             }
+                         });
                      }
                 }
+
+                if !self.sync_errors.is_empty() {
+                    self.show_sync_errors(ui);
+                }
             });
             
             ui.separator();
             
+            if let Some(summary) = &self.summary {
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new(format!("📥 {} to copy", format_size(summary.copy_bytes, DECIMAL))).strong());
+                    ui.separator();
+                    let delete_text = egui::RichText::new(format!("🗑 {} extra in destination", format_size(summary.delete_bytes, DECIMAL))).strong();
+                    if self.delete_extra && summary.delete_bytes > 0 {
+                        ui.colored_label(egui::Color32::from_rgb(231, 76, 60), delete_text);
+                    } else {
+                        ui.label(delete_text);
+                    }
+                    ui.separator();
+                    // Only a content check can vouch for the bytes
+                    let verified = if self.results.as_ref().is_some_and(|r| !r.metadata_only) { "verified identical" } else { "identical" };
+                    ui.label(egui::RichText::new(format!("✅ {} files {}", format_count(summary.identical), verified)).strong());
+                });
+                if !summary.by_extension.is_empty() {
+                    show_extension_breakdown(ui, &summary.by_extension);
+                }
+            }
+
+            if self.three_way.is_some() {
+                self.show_three_way(ui);
+            }
+            if self.duplicates.is_some() {
+                self.show_duplicates(ui);
+            }
+            if self.content_matches.is_some() {
+                self.show_content_matches(ui);
+            }
+
+            // Triage can start on differences found so far; the full tabs replace this on completion
+            if self.results.is_none() && !self.live_different.is_empty() {
+                ui.label(egui::RichText::new(format!("Different so far ({})", self.live_different.len())).strong());
+                let mut data = self.live_different.clone();
+                self.sort_pairs(&mut data);
+                self.show_diff_list(ui, &data);
+            }
+
             // 4. Results Tabs
+            if self.results.is_some() {
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_source("export_format")
+                        .selected_text(self.export_format.label())
+                        .width(70.0)
+                        .show_ui(ui, |ui| {
+                            for format in [ExportFormat::Csv, ExportFormat::Json, ExportFormat::Html] {
+                                ui.selectable_value(&mut self.export_format, format, format.label());
+                            }
+                        });
+                    if ui.button("💾 Export Results").clicked() {
+                        self.export_results();
+                    }
+                });
+            }
+
             if let Some(results) = &self.results {
                 ui.horizontal(|ui| {
                     ui.style_mut().spacing.item_spacing.x = 0.0; // Connect tabs
@@ -442,45 +3026,223 @@ impl eframe::App for FolderCompareApp {
                         }
                     };
 
-                    if let Some(t) = tab_btn(ui, &format!("Missing in Dest ({})", results.missing_in_dest.len()), Tab::MissingInDest, self.active_tab) {
-                        self.active_tab = t;
+                    // Picking a list tab leaves the tree view
+                    let mut picked = None;
+                    picked = picked.or(tab_btn(ui, &format!("Missing in Dest ({})", results.missing_in_dest.len()), Tab::MissingInDest, self.active_tab));
+                    picked = picked.or(tab_btn(ui, &format!("Extra in Dest ({})", results.missing_in_source.len()), Tab::MissingInSource, self.active_tab));
+                    picked = picked.or(tab_btn(ui, &format!("Different ({})", results.different_content.len()), Tab::Different, self.active_tab));
+                    // Only shallow comparisons produce this category
+                    if !results.timestamp_only.is_empty() || self.active_tab == Tab::TimestampOnly {
+                        picked = picked.or(tab_btn(ui, &format!("Timestamp Only ({})", results.timestamp_only.len()), Tab::TimestampOnly, self.active_tab));
                     }
-                    if let Some(t) = tab_btn(ui, &format!("Extra in Dest ({})", results.missing_in_source.len()), Tab::MissingInSource, self.active_tab) {
-                        self.active_tab = t;
+                    if !results.permissions_differ.is_empty() || self.active_tab == Tab::Permissions {
+                        picked = picked.or(tab_btn(ui, &format!("Permissions ({})", results.permissions_differ.len()), Tab::Permissions, self.active_tab));
                     }
-                    if let Some(t) = tab_btn(ui, &format!("Different ({})", results.different_content.len()), Tab::Different, self.active_tab) {
+                    picked = picked.or(tab_btn(ui, &format!("Identical ({})", results.identical_count), Tab::Identical, self.active_tab));
+                    if let Some(t) = picked {
                         self.active_tab = t;
+                        self.tree_view = false;
                     }
+                    ui.add_space(10.0);
+                    ui.toggle_value(&mut self.tree_view, "🌳 Tree view");
                 });
                 
+                if !results.case_collisions.is_empty() {
+                    ui.add_space(5.0);
+                    egui::CollapsingHeader::new(egui::RichText::new(
+                        format!("⚠️ {} names differ only by case and were compared case-sensitively", results.case_collisions.len())
+                    ).color(egui::Color32::from_rgb(241, 196, 15)))
+                        .id_source("case_collisions")
+                        .show(ui, |ui| {
+                            for collision in &results.case_collisions {
+                                ui.label(collision);
+                            }
+                        });
+                }
+
+                if !results.scan_errors.is_empty() {
+                    ui.add_space(5.0);
+                    scan_errors_header(ui, &results.scan_errors);
+                }
+
+                if !results.hash_errors.is_empty() {
+                    ui.add_space(5.0);
+                    egui::CollapsingHeader::new(egui::RichText::new(
+                        format!("⚠️ {} common files could not be hashed, so their content is unverified", results.hash_errors.len())
+                    ).color(egui::Color32::from_rgb(231, 76, 60)))
+                        .id_source("hash_errors")
+                        .show(ui, |ui| {
+                            for (src, _, msg) in &results.hash_errors {
+                                ui.label(format!("{}: {}", src.rel_path, msg));
+                            }
+                        });
+                }
+
+                if !results.renamed.is_empty() {
+                    ui.add_space(5.0);
+                    egui::CollapsingHeader::new(format!("🔀 {} files renamed or moved (synced as a copy plus an extra file)", results.renamed.len()))
+                        .id_source("renamed")
+                        .show(ui, |ui| {
+                            for (src, dest) in &results.renamed {
+                                ui.label(format!("{} → {}", src.rel_path, dest.rel_path));
+                            }
+                        });
+                }
+
+                if !results.dirs_missing_in_dest.is_empty() {
+                    ui.add_space(5.0);
+                    egui::CollapsingHeader::new(format!("📁 {} folders missing in destination", results.dirs_missing_in_dest.len()))
+                        .id_source("dirs_missing_in_dest")
+                        .show(ui, |ui| {
+                            for dir in &results.dirs_missing_in_dest {
+                                ui.label(format!("{}/", dir));
+                            }
+                        });
+                }
+
                 ui.add_space(10.0);
+
+                if self.tree_view {
+                    self.show_tree(ui);
+                    return;
+                }
                 
                 let active_tab = self.active_tab; // Copy enum
                 
                 // Clone the data needed for the current view to release the borrow on self.results
-                let missing_in_dest = if active_tab == Tab::MissingInDest { Some(results.missing_in_dest.clone()) } else { None };
-                let missing_in_source = if active_tab == Tab::MissingInSource { Some(results.missing_in_source.clone()) } else { None };
-                let different_content = if active_tab == Tab::Different { Some(results.different_content.clone()) } else { None };
+                let mut missing_in_dest = if active_tab == Tab::MissingInDest { Some(results.missing_in_dest.clone()) } else { None };
+                let mut missing_in_source = if active_tab == Tab::MissingInSource { Some(results.missing_in_source.clone()) } else { None };
+                let mut identical = if active_tab == Tab::Identical { Some(results.identical.clone()) } else { None };
+                let mut different_content = match active_tab {
+                    Tab::Different => Some(results.different_content.clone()),
+                    Tab::TimestampOnly => Some(results.timestamp_only.clone()),
+                    Tab::Permissions => Some(results.permissions_differ.clone()),
+                    _ => None,
+                };
 
-                egui::ScrollArea::vertical().show(ui, |ui| {
-                    match active_tab {
-                         Tab::MissingInDest => {
-                            if let Some(data) = missing_in_dest {
-                                self.show_file_list(ui, &data);
-                            }
-                        },
-                        Tab::MissingInSource => {
-                            if let Some(data) = missing_in_source {
-                                self.show_file_list(ui, &data);
+                let total_rows = match active_tab {
+                    Tab::MissingInDest => results.missing_in_dest.len(),
+                    Tab::MissingInSource => results.missing_in_source.len(),
+                    Tab::Different => results.different_content.len(),
+                    Tab::TimestampOnly => results.timestamp_only.len(),
+                    Tab::Permissions => results.permissions_differ.len(),
+                    Tab::Identical => results.identical.len(),
+                };
+
+                let filter = self.filter_text.to_lowercase();
+                if !filter.is_empty() {
+                    let matches = |rel_path: &str| rel_path.to_lowercase().contains(&filter);
+                    if let Some(data) = missing_in_dest.as_mut() { data.retain(|f| matches(&f.rel_path)); }
+                    if let Some(data) = missing_in_source.as_mut() { data.retain(|f| matches(&f.rel_path)); }
+                    if let Some(data) = identical.as_mut() { data.retain(|f| matches(&f.rel_path)); }
+                    if let Some(data) = different_content.as_mut() { data.retain(|(src, _)| matches(&src.rel_path)); }
+                }
+
+                if let Some(data) = missing_in_dest.as_mut() { self.sort_files(data); }
+                if let Some(data) = missing_in_source.as_mut() { self.sort_files(data); }
+                if let Some(data) = identical.as_mut() { self.sort_files(data); }
+                if let Some(data) = different_content.as_mut() { self.sort_pairs(data); }
+
+                let (mut copy_all, mut delete_all) = (false, false);
+                let mut include_again = None;
+                let shown_rows = missing_in_dest.as_ref().map(|d| d.len())
+                    .or(missing_in_source.as_ref().map(|d| d.len()))
+                    .or(different_content.as_ref().map(|d| d.len()))
+                    .or(identical.as_ref().map(|d| d.len()))
+                    .unwrap_or(0);
+
+                ui.horizontal(|ui| {
+                    ui.label("🔎 Filter:");
+                    let filter = ui.add(egui::TextEdit::singleline(&mut self.filter_text)
+                        .desired_width(300.0)
+                        .hint_text("path or extension"))
+                        .on_hover_text(ctx.format_shortcut(&SHORTCUT_FILTER));
+                    if std::mem::take(&mut self.focus_filter) {
+                        filter.request_focus();
+                    }
+                    if !self.filter_text.is_empty() {
+                        if ui.button("✕").on_hover_text("Clear filter").clicked() {
+                            self.filter_text.clear();
+                        }
+                        ui.label(egui::RichText::new(format!("showing {} of {}", shown_rows, total_rows)).italics());
+                    }
+                    if !self.session_excludes.is_empty() {
+                        ui.separator();
+                        ui.label("Excluded:");
+                        for dir in &self.session_excludes {
+                            if ui.small_button(format!("{}/ ✕", dir)).on_hover_text("Include this folder again and rescan").clicked() {
+                                include_again = Some(dir.clone());
                             }
-                        },
-                        Tab::Different => {
-                             if let Some(data) = different_content {
-                                self.show_diff_list(ui, &data);
+                        }
+                    }
+
+                    ui.separator();
+                    // Selection buttons act on the rows currently shown
+                    let visible: Vec<String> = missing_in_dest.iter().chain(missing_in_source.iter()).flatten()
+                        .chain(different_content.iter().flatten().map(|(src, _)| src))
+                        .map(|f| f.rel_path.clone())
+                        .collect();
+                    if active_tab != Tab::Identical {
+                        if ui.button("Select All").clicked() {
+                            self.selected.extend(visible.iter().cloned());
+                        }
+                        if ui.button("Select None").clicked() {
+                            for rel_path in &visible {
+                                self.selected.remove(rel_path);
                             }
                         }
                     }
+                    ui.label(format!("{} selected for sync", self.selected.len()));
+
+                    // Whole-category actions ignore the selection and the path filter
+                    let idle = !self.is_syncing && total_rows > 0;
+                    match active_tab {
+                        Tab::MissingInDest => {
+                            copy_all = ui.add_enabled(idle, egui::Button::new("📥 Copy all missing to destination")).clicked();
+                        }
+                        Tab::MissingInSource => {
+                            delete_all = ui.add_enabled(idle, egui::Button::new("🗑 Delete all extra")).clicked();
+                        }
+                        _ => {}
+                    }
                 });
+
+                match active_tab {
+                    Tab::MissingInDest => {
+                        if let Some(data) = missing_in_dest {
+                            self.show_file_list(ui, &data, Tab::MissingInDest);
+                        }
+                    },
+                    Tab::MissingInSource => {
+                        if let Some(data) = missing_in_source {
+                            self.show_file_list(ui, &data, Tab::MissingInSource);
+                        }
+                    },
+                    Tab::Different | Tab::TimestampOnly | Tab::Permissions => {
+                        if let Some(data) = different_content {
+                            self.show_diff_list(ui, &data);
+                        }
+                    }
+                    Tab::Identical => {
+                        if let Some(data) = identical {
+                            self.show_file_list(ui, &data, Tab::Identical);
+                        }
+                    }
+                }
+
+                if copy_all {
+                    let missing = self.results.as_ref().map(|r| r.missing_in_dest.clone()).unwrap_or_default();
+                    self.copy_now(CompareResult { missing_in_dest: missing, ..Default::default() }, SyncDirection::SourceToDest);
+                }
+                if delete_all {
+                    self.confirm_delete_extra = self.results.as_ref().map(|r| r.missing_in_source.clone());
+                }
+                if let Some(dir) = include_again {
+                    self.session_excludes.remove(&dir);
+                    if !self.is_syncing {
+                        self.start_comparison();
+                    }
+                }
             }
         });
         
@@ -496,10 +3258,30 @@ impl eframe::App for FolderCompareApp {
                 .resizable(false)
                 .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
                 .show(ctx, |ui| {
-                    ui.label("Mirror Mode is enabled. This will PERMANENTLY DELETE files in the destination that do not exist in the source.");
+                    let (target, origin) = match self.sync_direction {
+                        SyncDirection::DestToSource => ("source", "destination"),
+                        _ => ("destination", "source"),
+                    };
+                    let bytes: u64 = self.sync_deletions.iter().map(|f| f.size).sum();
+                    let what = format!("{} files ({}) in the {} that do not exist in the {}",
+                        format_count(self.sync_deletions.len()), format_size(bytes, DECIMAL), target, origin);
+                    if self.permanent_delete {
+                        ui.label(format!("Mirror Mode is enabled. This will PERMANENTLY DELETE {}:", what));
+                    } else {
+                        ui.label(format!("Mirror Mode is enabled. This will move {} to the trash:", what));
+                    }
+                    ui.add_space(5.0);
+                    egui::ScrollArea::vertical().max_height(200.0).show_rows(
+                        ui, 16.0, self.sync_deletions.len(), |ui, range| {
+                            for file in &self.sync_deletions[range] {
+                                ui.label(format!("{}  ({})", file.rel_path, format_size(file.size, DECIMAL)));
+                            }
+                        });
+                    ui.add_space(5.0);
+                    ui.checkbox(&mut self.sync_understood, "I understand these files will be deleted");
                     ui.add_space(10.0);
                     ui.horizontal(|ui| {
-                        if ui.button("PROCEED").clicked() {
+                        if ui.add_enabled(self.sync_understood, egui::Button::new("PROCEED")).clicked() {
                             do_sync = true;
                         }
                         if ui.button("CANCEL").clicked() {
@@ -511,61 +3293,440 @@ impl eframe::App for FolderCompareApp {
         
         if do_sync {
             self.confirm_sync_open = false;
-            self.start_sync();
+            self.sync_deletions.clear();
+            self.start_sync(false);
+        }
+
+        // Dropping the reply sender, e.g. when the run ends first, counts as an abort
+        let mut answer = None;
+        if let Some((count, _)) = &self.limit_prompt {
+            egui::Window::new("⚠️ Very Large Scan")
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+                .show(ctx, |ui| {
+                    ui.label(format!("Over {} files found so far. Scanning is paused.", format_count(*count)));
+                    ui.label("Was this the folder you meant? The limit can be changed in the options.");
+                    ui.add_space(10.0);
+                    ui.horizontal(|ui| {
+                        if ui.button("CONTINUE").clicked() {
+                            answer = Some(true);
+                        }
+                        if ui.button("ABORT").clicked() {
+                            answer = Some(false);
+                        }
+                    });
+                });
+        }
+        if !self.is_scanning {
+            self.limit_prompt = None;
+        }
+        if let Some(go_on) = answer {
+            if let Some((_, reply)) = self.limit_prompt.take() {
+                reply.send(go_on).ok();
+            }
+        }
+
+        let (mut do_delete, mut cancel_delete) = (false, false);
+        if let Some(extra) = &self.confirm_delete_extra {
+            egui::Window::new("⚠️ Delete All Extra Files")
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+                .show(ctx, |ui| {
+                    let bytes: u64 = extra.iter().map(|f| f.size).sum();
+                    let what = format!("{} files ({}) that only exist in the destination", format_count(extra.len()), format_size(bytes, DECIMAL));
+                    if self.permanent_delete {
+                        ui.label(format!("This will PERMANENTLY DELETE {}.", what));
+                    } else {
+                        ui.label(format!("This will move {} to the trash.", what));
+                    }
+                    ui.add_space(10.0);
+                    ui.horizontal(|ui| {
+                        do_delete = ui.button("DELETE").clicked();
+                        cancel_delete = ui.button("CANCEL").clicked();
+                    });
+                });
+        }
+        if cancel_delete {
+            self.confirm_delete_extra = None;
+        }
+        if do_delete {
+            if let Some(extra) = self.confirm_delete_extra.take() {
+                let mut options = self.sync_options(false);
+                options.direction = SyncDirection::SourceToDest;
+                options.delete_extra = true;
+                // Staged syncs never delete, which is all this one does
+                options.stage = false;
+                self.spawn_sync(CompareResult { missing_in_source: extra, ..Default::default() }, options);
+            }
+        }
+
+        // Sync Preview (dry run)
+        if self.preview_open {
+            let count = |action: SyncAction| self.preview_ops.iter().filter(|(a, _, _)| *a == action).count();
+            let (copies, updates, deletes, skips) = (count(SyncAction::Copy), count(SyncAction::Update), count(SyncAction::Delete), count(SyncAction::Skip));
+            let chmods = count(SyncAction::Permissions);
+
+            egui::Window::new("📋 Sync Preview")
+                .open(&mut self.preview_open)
+                .default_size([600.0, 500.0])
+                .show(ctx, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label(egui::RichText::new(format!("{} to copy", copies)).strong());
+                        ui.label(egui::RichText::new(format!("{} to update", updates)).strong());
+                        if skips > 0 {
+                            ui.label(egui::RichText::new(format!("{} skipped", skips)).strong());
+                        }
+                        if chmods > 0 {
+                            ui.label(egui::RichText::new(format!("{} permission fixes", chmods)).strong());
+                        }
+                        let delete_text = egui::RichText::new(format!("{} to delete", deletes)).strong();
+                        if deletes > 0 {
+                            ui.colored_label(egui::Color32::from_rgb(231, 76, 60), delete_text);
+                        } else {
+                            ui.label(delete_text);
+                        }
+                    });
+                    ui.label(egui::RichText::new("Dry run - nothing has been written to disk.").small().italics());
+                    ui.separator();
+
+                    egui::ScrollArea::vertical().show(ui, |ui| {
+                        for (action, direction, rel_path) in &self.preview_ops {
+                            let arrow = if *direction == SyncDirection::DestToSource { "←" } else { "→" };
+                            match action {
+                                SyncAction::Copy => { ui.label(format!("{} COPY    {}", arrow, rel_path)); },
+                                SyncAction::Update => { ui.label(format!("{} UPDATE  {}", arrow, rel_path)); },
+                                SyncAction::Permissions => { ui.label(format!("{} CHMOD   {}", arrow, rel_path)); },
+                                SyncAction::Skip => {
+                                    ui.colored_label(egui::Color32::GRAY, format!("  SKIP    {} (receiving copy is not older)", rel_path));
+                                },
+                                SyncAction::Delete => {
+                                    ui.colored_label(egui::Color32::from_rgb(231, 76, 60),
+                                        egui::RichText::new(format!("⚠ DELETE  {} (in {})", rel_path, receiving_side(*direction))).strong());
+                                },
+                            }
+                        }
+                    });
+                });
         }
 
         // Diff Window Modal
+        let mut rediff = false;
         if self.diff_open {
             egui::Window::new(format!("OmniDiff Viewer: {}", self.diff_file_name))
                 .open(&mut self.diff_open)
                 .default_size([800.0, 600.0])
                 .show(ctx, |ui| {
-                     if let Some(err) = &self.diff_error {
+                     if self.diff_link_targets.iter().any(Option::is_some) {
+                         let describe = |target: &Option<PathBuf>| target.as_ref()
+                             .map_or("not a link".to_owned(), |t| t.display().to_string());
+                         ui.label(egui::RichText::new(format!(
+                             "🔗 Source → {}   ·   Destination → {}",
+                             describe(&self.diff_link_targets[0]), describe(&self.diff_link_targets[1])
+                         )).strong());
+                         ui.separator();
+                     }
+                     if self.diff_rx.is_some() {
+                         ui.horizontal(|ui| {
+                             ui.spinner();
+                             ui.label("Loading diff…");
+                         });
+                     } else if let Some(err) = &self.diff_error {
                          ui.colored_label(egui::Color32::RED, err);
                      } else {
 
                          // Check Mode
-                         if self.diff_mode == DiffMode::Image {
+                         if self.diff_mode == DiffMode::Binary {
+                             let diffs: Vec<usize> = diff::byte_differences(&self.diff_bytes_src, &self.diff_bytes_dest).collect();
+                             match diffs.first() {
+                                 Some(first) => ui.label(egui::RichText::new(format!(
+                                     "Binary file · first difference at 0x{:08x} · {} differing bytes in the first {}",
+                                     first, diffs.len(), format_size(diff::HEX_PREVIEW_BYTES as u64, DECIMAL)
+                                 )).strong()),
+                                 None => ui.label(egui::RichText::new(format!(
+                                     "Binary file · the first {} are identical",
+                                     format_size(diff::HEX_PREVIEW_BYTES as u64, DECIMAL)
+                                 )).strong()),
+                             };
+                             ui.separator();
+
+                             let rows = self.diff_bytes_src.len().max(self.diff_bytes_dest.len()).div_ceil(diff::HEX_ROW_BYTES);
+                             let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+                             egui::ScrollArea::both().show_rows(ui, row_height, rows, |ui, range| {
+                                 for row in range {
+                                     let start = row * diff::HEX_ROW_BYTES;
+                                     ui.horizontal(|ui| {
+                                         ui.label(hex_row_job(&self.diff_bytes_src, &self.diff_bytes_dest, start));
+                                         ui.separator();
+                                         ui.label(hex_row_job(&self.diff_bytes_dest, &self.diff_bytes_src, start));
+                                     });
+                                 }
+                             });
+                         } else if self.diff_mode == DiffMode::Image {
                              // Image Compare View
-                             ui.columns(2, |columns| {
-                                 columns[0].vertical_centered(|ui| {
-                                     ui.label(egui::RichText::new("Source").strong());
-                                     if let Some(tex) = &self.diff_texture_src {
-                                         ui.image((tex.id(), tex.size_vec2()));
-                                     } else {
-                                         ui.label("Error loading source image");
-                                     }
-                                 });
-                                 columns[1].vertical_centered(|ui| {
-                                     ui.label(egui::RichText::new("Destination").strong());
-                                     if let Some(tex) = &self.diff_texture_dest {
-                                         ui.image((tex.id(), tex.size_vec2()));
-                                     } else {
-                                         ui.label("Error loading dest image");
-                                     }
+                             ui.horizontal(|ui| {
+                                 ui.radio_value(&mut self.image_view, ImageView::SideBySide, "Side by side");
+                                 ui.add_enabled_ui(self.diff_texture_diff.is_some(), |ui| {
+                                     ui.radio_value(&mut self.image_view, ImageView::Difference, "Difference");
+                                     ui.radio_value(&mut self.image_view, ImageView::Overlay, "Overlay");
                                  });
+                                 ui.separator();
+                                 texture_memory(ui);
                              });
+                             if self.diff_texture_diff.is_some() {
+                                 ui.label(&self.diff_image_note);
+                             } else {
+                                 ui.colored_label(egui::Color32::from_rgb(241, 196, 15), &self.diff_image_note);
+                             }
+                             ui.separator();
+
+                             // Views that need matching dimensions fall back to side by side
+                             let view = if self.diff_texture_diff.is_some() { self.image_view } else { ImageView::SideBySide };
+                             match view {
+                                 ImageView::SideBySide => {
+                                     ui.columns(2, |columns| {
+                                         columns[0].vertical_centered(|ui| {
+                                             ui.label(egui::RichText::new("Source").strong());
+                                             if let Some(tex) = &self.diff_texture_src {
+                                                 ui.image((tex.id(), tex.size_vec2()));
+                                             } else {
+                                                 ui.label("Error loading source image");
+                                             }
+                                         });
+                                         columns[1].vertical_centered(|ui| {
+                                             ui.label(egui::RichText::new("Destination").strong());
+                                             if let Some(tex) = &self.diff_texture_dest {
+                                                 ui.image((tex.id(), tex.size_vec2()));
+                                             } else {
+                                                 ui.label("Error loading dest image");
+                                             }
+                                         });
+                                     });
+                                 },
+                                 ImageView::Difference => {
+                                     if let Some(tex) = &self.diff_texture_diff {
+                                         egui::ScrollArea::both().show(ui, |ui| {
+                                             ui.image((tex.id(), tex.size_vec2()));
+                                         });
+                                     }
+                                 },
+                                 ImageView::Overlay => {
+                                     ui.add(egui::Slider::new(&mut self.image_blend, 0.0..=1.0).text("Source opacity"));
+                                     if let (Some(src), Some(dest)) = (&self.diff_texture_src, &self.diff_texture_dest) {
+                                         egui::ScrollArea::both().show(ui, |ui| {
+                                             let (rect, _) = ui.allocate_exact_size(dest.size_vec2(), egui::Sense::hover());
+                                             egui::Image::new((dest.id(), dest.size_vec2())).paint_at(ui, rect);
+                                             egui::Image::new((src.id(), src.size_vec2()))
+                                                 .tint(egui::Color32::from_white_alpha((self.image_blend * 255.0) as u8))
+                                                 .paint_at(ui, rect);
+                                         });
+                                     }
+                                 },
+                             }
                          } else {
                              // Text Diff View
-                             egui::ScrollArea::vertical().show(ui, |ui| {
-                                 for (line, tag) in &self.diff_content {
-                                     let color = match tag {
-                                         ChangeTag::Delete => egui::Color32::RED,
-                                         ChangeTag::Insert => egui::Color32::GREEN,
-                                         ChangeTag::Equal => egui::Color32::GRAY,
-                                     };
-                                     let prefix = match tag {
-                                         ChangeTag::Delete => "- ",
-                                         ChangeTag::Insert => "+ ",
-                                         ChangeTag::Equal => "  ",
+                             ui.horizontal(|ui| {
+                                 ui.radio_value(&mut self.diff_side_by_side, false, "Unified");
+                                 ui.radio_value(&mut self.diff_side_by_side, true, "Side-by-side");
+                                 ui.separator();
+                                 ui.checkbox(&mut self.diff_collapse, "Collapse unchanged");
+                                 ui.add_enabled(self.diff_collapse, egui::DragValue::new(&mut self.diff_context)
+                                     .clamp_range(0..=50)
+                                     .suffix(" lines context"));
+                                 ui.separator();
+                                 let options = self.diff_text_options;
+                                 ui.checkbox(&mut self.diff_text_options.ignore_trailing_whitespace, "Ignore trailing whitespace");
+                                 ui.checkbox(&mut self.diff_text_options.ignore_blank_lines, "Ignore blank lines");
+                                 if self.diff_text_options != options {
+                                     rediff = true;
+                                 }
+                                 ui.separator();
+                                 if ui.button("📋 Copy").on_hover_text("Copy the whole diff in unified format").clicked() {
+                                     let text = diff::unified_text(&self.diff_content, &self.diff_file_name, false);
+                                     ui.output_mut(|o| o.copied_text = text);
+                                 }
+                                 if ui.button("📋 Copy changes").on_hover_text("Copy only added and removed lines").clicked() {
+                                     let text = diff::unified_text(&self.diff_content, &self.diff_file_name, true);
+                                     ui.output_mut(|o| o.copied_text = text);
+                                 }
+                                 ui.separator();
+                                 ui.label(egui::RichText::new(&self.diff_encoding).small().weak());
+                             });
+                             if let Some(note) = &self.diff_text_note {
+                                 ui.colored_label(egui::Color32::from_rgb(241, 196, 15), note);
+                             }
+
+                             // Search hits are row indices into whichever view is showing
+                             let query = self.diff_search.clone();
+                             let is_hit = |text: &str| !diff::find_ignore_case(text, &query).is_empty();
+                             let hits: Vec<usize> = if self.diff_side_by_side {
+                                 self.diff_rows.iter().enumerate()
+                                     .filter(|(_, r)| [&r.left, &r.right].iter().any(|side| side.as_ref().is_some_and(|(_, t)| is_hit(t))))
+                                     .map(|(i, _)| i)
+                                     .collect()
+                             } else {
+                                 self.diff_content.iter().enumerate()
+                                     .filter(|(_, l)| is_hit(&l.text))
+                                     .map(|(i, _)| i)
+                                     .collect()
+                             };
+                             ui.horizontal(|ui| {
+                                 let search = ui.add(egui::TextEdit::singleline(&mut self.diff_search)
+                                     .hint_text("🔍 Search (Enter / Shift+Enter)")
+                                     .desired_width(250.0));
+                                 if search.changed() {
+                                     self.diff_match = 0;
+                                     self.diff_scroll_to_match = true;
+                                 }
+                                 let mut step = None; // Some(forward)
+                                 if search.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                                     step = Some(!ui.input(|i| i.modifiers.shift));
+                                     search.request_focus();
+                                 }
+                                 if ui.add_enabled(!hits.is_empty(), egui::Button::new("⬆")).clicked() {
+                                     step = Some(false);
+                                 }
+                                 if ui.add_enabled(!hits.is_empty(), egui::Button::new("⬇")).clicked() {
+                                     step = Some(true);
+                                 }
+                                 if let (Some(forward), false) = (step, hits.is_empty()) {
+                                     self.diff_match = if forward {
+                                         (self.diff_match + 1) % hits.len()
+                                     } else {
+                                         (self.diff_match + hits.len() - 1) % hits.len()
                                      };
-                                     ui.colored_label(color, format!("{}{}", prefix, line));
+                                     self.diff_scroll_to_match = true;
+                                 }
+                                 if !query.is_empty() {
+                                     if hits.is_empty() {
+                                         ui.label("No matches");
+                                     } else {
+                                         ui.label(format!("{}/{}", self.diff_match.min(hits.len() - 1) + 1, hits.len()));
+                                     }
                                  }
                              });
+                             ui.separator();
+
+                             let current_hit = hits.get(self.diff_match).copied();
+                             let mut scroll_to_hit = std::mem::take(&mut self.diff_scroll_to_match);
+
+                             // Rows with hits stay visible even when unchanged lines are collapsed
+                             let mut changed: Vec<bool> = if self.diff_side_by_side {
+                                 self.diff_rows.iter().map(|r| r.changed).collect()
+                             } else {
+                                 self.diff_content.iter().map(|l| l.tag != ChangeTag::Equal).collect()
+                             };
+                             for &i in &hits {
+                                 changed[i] = true;
+                             }
+                             let segments = if self.diff_collapse {
+                                 diff::collapse_unchanged(&changed, self.diff_context)
+                             } else {
+                                 vec![Segment::Rows(0..changed.len())]
+                             };
+                             let gutter = |no: Option<usize>| no.map(|n| format!("{:>5}", n)).unwrap_or_else(|| "     ".into());
+                             let collapsed_label = |ui: &mut egui::Ui, count: usize| {
+                                 ui.label(egui::RichText::new(format!("… {} unchanged lines …", count)).italics().color(egui::Color32::DARK_GRAY));
+                             };
+
+                             if self.diff_side_by_side {
+                                 let half = (ui.available_width() / 2.0 - 10.0).max(100.0);
+                                 egui::ScrollArea::both().show(ui, |ui| {
+                                     // One grid keeps both columns aligned and scrolling together
+                                     egui::Grid::new("side_by_side_diff")
+                                         .num_columns(2)
+                                         .min_col_width(half)
+                                         .max_col_width(half)
+                                         .spacing([10.0, 2.0])
+                                         .show(ui, |ui| {
+                                             ui.label(egui::RichText::new("Source").strong());
+                                             ui.label(egui::RichText::new("Destination").strong());
+                                             ui.end_row();
+
+                                             for segment in &segments {
+                                                 let range = match segment {
+                                                     Segment::Rows(range) => range.clone(),
+                                                     Segment::Collapsed(count) => {
+                                                         collapsed_label(ui, *count);
+                                                         collapsed_label(ui, *count);
+                                                         ui.end_row();
+                                                         continue;
+                                                     }
+                                                 };
+                                                 for (i, row) in range.clone().zip(&self.diff_rows[range]) {
+                                                     let (left_color, right_color) = if row.changed {
+                                                         (egui::Color32::RED, egui::Color32::GREEN)
+                                                     } else {
+                                                         (egui::Color32::GRAY, egui::Color32::GRAY)
+                                                     };
+                                                     let current = current_hit == Some(i);
+                                                     let sides = [(&row.left, left_color, &row.left_emphasis), (&row.right, right_color, &row.right_emphasis)];
+                                                     for (side, color, emphasis) in sides {
+                                                         let response = match side {
+                                                             Some((no, line)) => {
+                                                                 let prefix = format!("{} ", gutter(Some(*no)));
+                                                                 ui.label(search_job(&prefix, line, color, emphasis, &query, current))
+                                                             },
+                                                             None => ui.label(""),
+                                                         };
+                                                         if current && scroll_to_hit {
+                                                             response.scroll_to_me(Some(egui::Align::Center));
+                                                             scroll_to_hit = false;
+                                                         }
+                                                     }
+                                                     ui.end_row();
+                                                 }
+                                             }
+                                         });
+                                 });
+                             } else {
+                                 egui::ScrollArea::vertical().show(ui, |ui| {
+                                     for segment in &segments {
+                                         let range = match segment {
+                                             Segment::Rows(range) => range.clone(),
+                                             Segment::Collapsed(count) => {
+                                                 collapsed_label(ui, *count);
+                                                 continue;
+                                             }
+                                         };
+                                         for (i, line) in range.clone().zip(&self.diff_content[range]) {
+                                             let color = match line.tag {
+                                                 ChangeTag::Delete => egui::Color32::RED,
+                                                 ChangeTag::Insert => egui::Color32::GREEN,
+                                                 ChangeTag::Equal => egui::Color32::GRAY,
+                                             };
+                                             let prefix = match line.tag {
+                                                 ChangeTag::Delete => "- ",
+                                                 ChangeTag::Insert => "+ ",
+                                                 ChangeTag::Equal => "  ",
+                                             };
+                                             let current = current_hit == Some(i);
+                                             let gutters = format!("{} {} {}", gutter(line.old_no), gutter(line.new_no), prefix);
+                                             let response = ui.label(search_job(&gutters, &line.text, color, &line.emphasis, &query, current));
+                                             if current && scroll_to_hit {
+                                                 response.scroll_to_me(Some(egui::Align::Center));
+                                                 scroll_to_hit = false;
+                                             }
+                                         }
+                                     }
+                                 });
+                             }
                          }
                      }
                 });
         }
+        // The text options change what gets diffed, so the pair is read again
+        if rediff {
+            if let Some((src, dest)) = self.diff_paths.clone() {
+                let name = self.diff_file_name.clone();
+                self.open_diff_viewer(ctx, &src, &dest, &name);
+            }
+        }
+        if let Some((src, dest)) = self.file_pair.take() {
+            let name = src.file_name().unwrap_or_default().to_string_lossy().to_string();
+            self.open_diff_viewer(ctx, &src, &dest, &name);
+        }
     }
 
 }
@@ -0,0 +1,663 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use chrono::{Local, TimeZone};
+use crossbeam_channel::Sender;
+use flate2::read::{DeflateDecoder, MultiGzDecoder};
+use ignore::gitignore::Gitignore;
+
+use crate::scanner::{FileEntry, FileFilter, ScanCounters, ScanStatus};
+
+/// Whether `path` is a file this module can list: .zip, .tar, .tar.gz or .tgz
+pub fn is_archive(path: &Path) -> bool {
+    kind(path).is_some() && path.is_file()
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+fn kind(path: &Path) -> Option<Kind> {
+    let name = path.file_name()?.to_string_lossy().to_lowercase();
+    if name.ends_with(".zip") {
+        Some(Kind::Zip)
+    } else if name.ends_with(".tar") {
+        Some(Kind::Tar)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Some(Kind::TarGz)
+    } else {
+        None
+    }
+}
+
+/// One file inside an archive, before filtering
+struct Member {
+    rel_path: String,
+    size: u64,
+    modified: u64,
+    content: Content,
+}
+
+enum Content {
+    File,
+    Symlink(PathBuf),
+    HardLink(String), // Cleaned name of an earlier member holding the data
+    Unsupported(String), // Why the member can't be listed, reported as a scan error
+}
+
+/// List the files of an archive into the same map `scan_folder` builds.
+/// Entry paths are `archive/rel_path`, which can't be opened, so with `hash`
+/// every entry is hashed from its decompressed stream while it's read.
+pub fn scan_archive(
+    root: &Path,
+    excludes: &Gitignore,
+    filter: &FileFilter,
+    hash: bool,
+    cancel: &AtomicBool,
+    counters: &ScanCounters,
+    tx: &Sender<ScanStatus>
+) -> HashMap<String, FileEntry> {
+    let mut files: HashMap<String, FileEntry> = HashMap::new();
+    // Sizes of every member seen, listed or not, so hard links to filtered ones resolve
+    let mut sizes: HashMap<String, u64> = HashMap::new();
+    let mut visit = |member: Member, data: &mut dyn Read| -> io::Result<bool> {
+        // Members count against the scan limit like walked files; a "no" also cancels
+        if !counters.within_limit(cancel, tx) {
+            return Ok(false);
+        }
+        let c = counters.scanned.fetch_add(1, Ordering::Relaxed) + 1;
+        if c.is_multiple_of(1000) {
+            tx.send(ScanStatus::Scanned(c)).ok();
+        }
+
+        let path = root.join(&member.rel_path);
+        // A hard link is another name for its target's data, which came earlier
+        let (size, link_target, known_hash) = match member.content {
+            Content::File => (member.size, None, None),
+            Content::Symlink(target) => (member.size, Some(target), None),
+            Content::HardLink(target) => match (files.get(&target), sizes.get(&target)) {
+                (Some(entry), _) => (entry.size, entry.link_target.clone(), entry.hash.clone()),
+                (None, Some(&size)) if !hash => (size, None, None),
+                _ => {
+                    counters.error(path, format!("Hard link to {}, whose content was not read; left out of the comparison", target));
+                    return Ok(true);
+                }
+            },
+            Content::Unsupported(reason) => {
+                counters.error(path, format!("{}; left out of the comparison", reason));
+                return Ok(true);
+            }
+        };
+        sizes.insert(member.rel_path.clone(), size);
+
+        if !filter.allows_path(&member.rel_path, &path, excludes) {
+            return Ok(true);
+        }
+        if !filter.accepts_extension(&path) || !filter.accepts(size, member.modified) {
+            counters.filtered.fetch_add(1, Ordering::Relaxed);
+            return Ok(true);
+        }
+        let hash = if known_hash.is_some() || !hash || link_target.is_some() {
+            known_hash
+        } else {
+            let mut hasher = blake3::Hasher::new();
+            io::copy(data, &mut hasher)?;
+            Some(hasher.finalize().to_hex().to_string())
+        };
+        files.insert(member.rel_path.clone(), FileEntry {
+            path,
+            rel_path: member.rel_path,
+            size,
+            modified: member.modified,
+            hash,
+            link_target,
+            mode: None,
+            owner: None,
+        });
+        Ok(true)
+    };
+
+    let res = match kind(root) {
+        Some(Kind::Zip) => read_zip(root, &mut visit),
+        Some(Kind::Tar) => File::open(root).and_then(|f| read_tar(BufReader::new(f), &mut visit)),
+        Some(Kind::TarGz) => File::open(root).and_then(|f| read_tar(MultiGzDecoder::new(BufReader::new(f)), &mut visit)),
+        None => Err(io::Error::new(io::ErrorKind::InvalidInput, "Not an archive")),
+    };
+    if let Err(e) = res {
+        counters.error(root.to_path_buf(), format!("Archive only partly read: {}", e));
+    }
+    files
+}
+
+/// Archive names may use backslashes or a leading "./"; both are dropped from the key
+fn clean_name(name: &str) -> String {
+    let name = name.replace('\\', "/");
+    name.split('/').filter(|p| !p.is_empty() && *p != ".").collect::<Vec<_>>().join("/")
+}
+
+type Visit<'a> = dyn FnMut(Member, &mut dyn Read) -> io::Result<bool> + 'a;
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_owned())
+}
+
+fn u16_at(buf: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([buf[at], buf[at + 1]])
+}
+
+fn u32_at(buf: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([buf[at], buf[at + 1], buf[at + 2], buf[at + 3]])
+}
+
+fn u64_at(buf: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(buf[at..at + 8].try_into().unwrap())
+}
+
+/// Where the central directory is and how many entries it holds, from the
+/// end record or, when that has its fields saturated, the ZIP64 one
+fn zip_directory(file: &mut File, len: u64) -> io::Result<(u64, u64, u64)> {
+    // The end record sits in the last 22 bytes plus an optional comment of up to 64KB
+    let tail_len = len.min(22 + 65535);
+    let tail_start = len - tail_len;
+    file.seek(SeekFrom::Start(tail_start))?;
+    let mut tail = vec![0; tail_len as usize];
+    file.read_exact(&mut tail)?;
+    let eocd = (0..tail.len().saturating_sub(21)).rev()
+        .find(|&i| u32_at(&tail, i) == 0x0605_4b50)
+        .ok_or_else(|| invalid("No zip end record"))?;
+    let count = u16_at(&tail, eocd + 10) as u64;
+    let cd_size = u32_at(&tail, eocd + 12) as u64;
+    let cd_offset = u32_at(&tail, eocd + 16) as u64;
+    if count != 0xFFFF && cd_size != 0xFFFF_FFFF && cd_offset != 0xFFFF_FFFF {
+        return bounded(count, cd_size, cd_offset, tail_start + eocd as u64);
+    }
+
+    // The ZIP64 locator comes right before the end record and points at the ZIP64 end record
+    let locator = eocd.checked_sub(20)
+        .filter(|&at| u32_at(&tail, at) == 0x0706_4b50)
+        .ok_or_else(|| invalid("ZIP64 end record locator missing"))?;
+    let record_offset = u64_at(&tail, locator + 8);
+    if record_offset.checked_add(56).is_none_or(|record_end| record_end > tail_start + locator as u64) {
+        return Err(invalid("ZIP64 end record out of bounds"));
+    }
+    let mut record = [0; 56];
+    file.seek(SeekFrom::Start(record_offset))?;
+    file.read_exact(&mut record)?;
+    if u32_at(&record, 0) != 0x0606_4b50 {
+        return Err(invalid("Corrupt ZIP64 end record"));
+    }
+    bounded(u64_at(&record, 32), u64_at(&record, 40), u64_at(&record, 48), record_offset)
+}
+
+/// The central directory has to end before the record describing it, which
+/// also keeps a corrupt size from allocating more than the file holds
+fn bounded(count: u64, cd_size: u64, cd_offset: u64, end: u64) -> io::Result<(u64, u64, u64)> {
+    if cd_offset.checked_add(cd_size).is_none_or(|cd_end| cd_end > end) {
+        return Err(invalid("Zip central directory out of bounds"));
+    }
+    Ok((count, cd_size, cd_offset))
+}
+
+/// Walk the central directory, then stream each member from its local header
+fn read_zip(root: &Path, visit: &mut Visit) -> io::Result<()> {
+    let mut file = File::open(root)?;
+    let len = file.metadata()?.len();
+    let (count, cd_size, cd_offset) = zip_directory(&mut file, len)?;
+
+    let mut cd = vec![0; cd_size as usize];
+    file.seek(SeekFrom::Start(cd_offset))?;
+    file.read_exact(&mut cd)?;
+
+    let mut at = 0;
+    for _ in 0..count {
+        if at + 46 > cd.len() || u32_at(&cd, at) != 0x0201_4b50 {
+            return Err(invalid("Corrupt zip central directory"));
+        }
+        let method = u16_at(&cd, at + 10);
+        let (time, date) = (u16_at(&cd, at + 12), u16_at(&cd, at + 14));
+        let mut compressed = u32_at(&cd, at + 20) as u64;
+        let mut size = u32_at(&cd, at + 24) as u64;
+        let name_len = u16_at(&cd, at + 28) as usize;
+        let extra_len = u16_at(&cd, at + 30) as usize;
+        let comment_len = u16_at(&cd, at + 32) as usize;
+        let unix_mode = u32_at(&cd, at + 38) >> 16;
+        let mut header_offset = u32_at(&cd, at + 42) as u64;
+        let name_end = at + 46 + name_len;
+        if name_end + extra_len > cd.len() {
+            return Err(invalid("Corrupt zip central directory"));
+        }
+        let name = String::from_utf8_lossy(&cd[at + 46..name_end]).to_string();
+        let extra = &cd[name_end..name_end + extra_len];
+        at = name_end + extra_len + comment_len;
+        zip64_extra(extra, &mut size, &mut compressed, &mut header_offset)
+            .ok_or_else(|| invalid(&format!("{}: ZIP64 extra field missing", name)))?;
+
+        if name.ends_with('/') {
+            continue;
+        }
+        let modified = unix_mtime(extra).unwrap_or_else(|| dos_mtime(date, time));
+
+        // The local header repeats the name but may carry a different extra field
+        let mut local = [0; 30];
+        file.seek(SeekFrom::Start(header_offset))?;
+        file.read_exact(&mut local)?;
+        if u32_at(&local, 0) != 0x0403_4b50 {
+            return Err(invalid("Corrupt zip local header"));
+        }
+        let skip = u16_at(&local, 26) as i64 + u16_at(&local, 28) as i64;
+        file.seek(SeekFrom::Current(skip))?;
+        let raw = (&mut file).take(compressed);
+
+        let is_link = unix_mode & 0o170000 == 0o120000;
+        let mut data: Box<dyn Read + '_> = match method {
+            0 => Box::new(raw),
+            8 => Box::new(DeflateDecoder::new(raw)),
+            _ => return Err(invalid(&format!("{}: unsupported compression method {}", name, method))),
+        };
+        let content = if is_link {
+            let mut target = String::new();
+            data.read_to_string(&mut target)?;
+            Content::Symlink(PathBuf::from(target))
+        } else {
+            Content::File
+        };
+        let member = Member { rel_path: clean_name(&name), size, modified, content };
+        if !visit(member, &mut data)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Replace the saturated 32-bit fields with the 64-bit values of the ZIP64
+/// extra field, which lists only those, in this order
+fn zip64_extra(mut extra: &[u8], size: &mut u64, compressed: &mut u64, offset: &mut u64) -> Option<()> {
+    let fields = [size, compressed, offset];
+    if fields.iter().all(|f| **f != 0xFFFF_FFFF) {
+        return Some(());
+    }
+    while extra.len() >= 4 {
+        let (id, len) = (u16_at(extra, 0), u16_at(extra, 2) as usize);
+        let mut data = extra.get(4..4 + len)?;
+        if id == 0x0001 {
+            for field in fields.into_iter().filter(|f| **f == 0xFFFF_FFFF) {
+                *field = u64_at(data.get(..8)?, 0);
+                data = &data[8..];
+            }
+            return Some(());
+        }
+        extra = &extra[4 + len..];
+    }
+    None
+}
+
+/// Seconds from the "extended timestamp" extra field, which is UTC
+fn unix_mtime(mut extra: &[u8]) -> Option<u64> {
+    while extra.len() >= 4 {
+        let (id, len) = (u16_at(extra, 0), u16_at(extra, 2) as usize);
+        let data = extra.get(4..4 + len)?;
+        if id == 0x5455 && data.len() >= 5 && data[0] & 1 != 0 {
+            return u64::try_from(i32::from_le_bytes([data[1], data[2], data[3], data[4]])).ok();
+        }
+        extra = &extra[4 + len..];
+    }
+    None
+}
+
+/// MS-DOS timestamps have two-second resolution and no zone; they're local time
+fn dos_mtime(date: u16, time: u16) -> u64 {
+    let year = (date >> 9) as i32 + 1980;
+    let (month, day) = ((date >> 5) as u32 & 0xF, date as u32 & 0x1F);
+    let (hour, min, sec) = ((time >> 11) as u32, (time >> 5) as u32 & 0x3F, (time as u32 & 0x1F) * 2);
+    Local.with_ymd_and_hms(year, month, day, hour, min, sec).earliest()
+        .and_then(|t| u64::try_from(t.timestamp()).ok())
+        .unwrap_or(0)
+}
+
+/// Octal (or GNU base-256) numeric field of a tar header
+fn tar_number(field: &[u8]) -> u64 {
+    if field[0] & 0x80 != 0 {
+        return field[1..].iter().fold(0, |n, &b| (n << 8) | b as u64);
+    }
+    let text = String::from_utf8_lossy(field);
+    u64::from_str_radix(text.trim_matches(|c: char| c == '\0' || c == ' '), 8).unwrap_or(0)
+}
+
+fn tar_string(field: &[u8]) -> String {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).to_string()
+}
+
+/// Read one member's data padded to whole 512-byte blocks
+fn read_padded(reader: &mut impl Read, size: u64) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    reader.take(size.next_multiple_of(512)).read_to_end(&mut data)?;
+    data.truncate(size as usize);
+    Ok(data)
+}
+
+/// Stream a ustar/GNU/pax tar, handing regular files, links and members that
+/// can't be read (sparse files, unknown types) to `visit`
+fn read_tar(mut reader: impl Read, visit: &mut Visit) -> io::Result<()> {
+    let mut header = [0; 512];
+    // Long names and pax overrides apply to the member that follows them
+    let mut long_name: Option<String> = None;
+    let mut long_link: Option<String> = None;
+    let mut pax: HashMap<String, String> = HashMap::new();
+    loop {
+        match reader.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        }
+        if header.iter().all(|&b| b == 0) {
+            return Ok(());
+        }
+
+        let mut size = tar_number(&header[124..136]);
+        let typeflag = header[156];
+        match typeflag {
+            b'L' => {
+                long_name = Some(tar_string(&read_padded(&mut reader, size)?));
+                continue;
+            }
+            b'K' => {
+                long_link = Some(tar_string(&read_padded(&mut reader, size)?));
+                continue;
+            }
+            b'x' => {
+                pax = parse_pax(&read_padded(&mut reader, size)?);
+                continue;
+            }
+            _ => {}
+        }
+
+        let mut name = tar_string(&header[0..100]);
+        if &header[257..262] == b"ustar" {
+            let prefix = tar_string(&header[345..500]);
+            if !prefix.is_empty() {
+                name = format!("{}/{}", prefix, name);
+            }
+        }
+        if let Some(long) = long_name.take() {
+            name = long;
+        }
+        let mut modified = tar_number(&header[136..148]);
+        let mut link = long_link.take().unwrap_or_else(|| tar_string(&header[157..257]));
+        let mut sparse = typeflag == b'S';
+        for (key, value) in std::mem::take(&mut pax) {
+            match key.as_str() {
+                "path" => name = value,
+                "linkpath" => link = value,
+                "size" => size = value.parse().unwrap_or(size),
+                // Fractional seconds are dropped like everywhere else
+                "mtime" => modified = value.split('.').next().and_then(|s| s.parse().ok()).unwrap_or(modified),
+                // pax-format GNU sparse files store only their data runs
+                key if key.starts_with("GNU.sparse.") => sparse = true,
+                _ => {}
+            }
+        }
+
+        let padded = size.next_multiple_of(512);
+        let mut data = (&mut reader).take(size);
+        let content = match typeflag {
+            _ if sparse => Some(Content::Unsupported("Sparse tar members are not supported".into())),
+            b'0' | b'\0' | b'7' => Some(Content::File),
+            b'1' => Some(Content::HardLink(clean_name(&link))),
+            b'2' => Some(Content::Symlink(link.clone().into())),
+            // Folders, devices, FIFOs, global headers and volume labels hold no file data
+            b'5' | b'3' | b'4' | b'6' | b'g' | b'V' => None,
+            other => Some(Content::Unsupported(format!("Tar member type '{}' is not supported", other.escape_ascii()))),
+        };
+        let keep_going = match content {
+            // A link's own size on disk is the length of its target
+            Some(content @ Content::Symlink(_)) => visit(Member { rel_path: clean_name(&name), size: link.len() as u64, modified, content }, &mut data)?,
+            Some(content) => visit(Member { rel_path: clean_name(&name), size, modified, content }, &mut data)?,
+            None => true,
+        };
+        if !keep_going {
+            return Ok(());
+        }
+        // Skip whatever the visitor left unread, then the padding
+        io::copy(&mut data, &mut io::sink())?;
+        io::copy(&mut (&mut reader).take(padded - size), &mut io::sink())?;
+    }
+}
+
+/// "<len> <key>=<value>\n" records of a pax extended header
+fn parse_pax(data: &[u8]) -> HashMap<String, String> {
+    let text = String::from_utf8_lossy(data);
+    let mut records = HashMap::new();
+    let mut rest = text.as_ref();
+    while let Some((len, _)) = rest.split_once(' ') {
+        let Ok(len) = len.parse::<usize>() else { break };
+        let Some(record) = rest.get(..len) else { break };
+        if let Some((key, value)) = record.split_once(' ').and_then(|(_, kv)| kv.trim_end_matches('\n').split_once('=')) {
+            records.insert(key.to_owned(), value.to_owned());
+        }
+        rest = &rest[len..];
+    }
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const HELLO: &[u8] = b"hello";
+    const BLOB: &[u8] = &[0, 1, 2, 3, 255];
+
+    /// A stored (uncompressed) zip; with `zip64` every size and offset goes
+    /// through the ZIP64 extra field and end records
+    fn zip(entries: &[(&str, &[u8])], zip64: bool) -> Vec<u8> {
+        let (time, date) = (0u16, (40u16 << 9) | (1 << 5) | 1);
+        let mut out = Vec::new();
+        let mut cd = Vec::new();
+        for (name, data) in entries {
+            let offset = out.len() as u32;
+            out.extend(0x0403_4b50u32.to_le_bytes());
+            out.extend([20, 0, 0, 0, 0, 0]);
+            out.extend(time.to_le_bytes());
+            out.extend(date.to_le_bytes());
+            out.extend(0u32.to_le_bytes());
+            out.extend((data.len() as u32).to_le_bytes());
+            out.extend((data.len() as u32).to_le_bytes());
+            out.extend((name.len() as u16).to_le_bytes());
+            out.extend(0u16.to_le_bytes());
+            out.extend(name.as_bytes());
+            out.extend(*data);
+
+            let mut extra = Vec::new();
+            if zip64 {
+                extra.extend([1, 0, 24, 0]);
+                extra.extend((data.len() as u64).to_le_bytes());
+                extra.extend((data.len() as u64).to_le_bytes());
+                extra.extend((offset as u64).to_le_bytes());
+            }
+            let pick = |real: u32| if zip64 { 0xFFFF_FFFF } else { real };
+            cd.extend(0x0201_4b50u32.to_le_bytes());
+            cd.extend([30, 3, 20, 0, 0, 0, 0, 0]);
+            cd.extend(time.to_le_bytes());
+            cd.extend(date.to_le_bytes());
+            cd.extend(0u32.to_le_bytes());
+            cd.extend(pick(data.len() as u32).to_le_bytes());
+            cd.extend(pick(data.len() as u32).to_le_bytes());
+            cd.extend((name.len() as u16).to_le_bytes());
+            cd.extend((extra.len() as u16).to_le_bytes());
+            cd.extend([0, 0, 0, 0, 0, 0]);
+            cd.extend((0o100644u32 << 16).to_le_bytes());
+            cd.extend(pick(offset).to_le_bytes());
+            cd.extend(name.as_bytes());
+            cd.extend(extra);
+        }
+        let cd_offset = out.len() as u64;
+        out.extend(&cd);
+        let count = entries.len() as u64;
+        if zip64 {
+            let record_offset = out.len() as u64;
+            out.extend(0x0606_4b50u32.to_le_bytes());
+            out.extend(44u64.to_le_bytes());
+            out.extend([45, 0, 45, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+            out.extend(count.to_le_bytes());
+            out.extend(count.to_le_bytes());
+            out.extend((cd.len() as u64).to_le_bytes());
+            out.extend(cd_offset.to_le_bytes());
+            out.extend(0x0706_4b50u32.to_le_bytes());
+            out.extend(0u32.to_le_bytes());
+            out.extend(record_offset.to_le_bytes());
+            out.extend(1u32.to_le_bytes());
+        }
+        out.extend(0x0605_4b50u32.to_le_bytes());
+        out.extend([0, 0, 0, 0]);
+        let (count, cd_size, cd_offset) = if zip64 {
+            (0xFFFF, 0xFFFF_FFFF, 0xFFFF_FFFF)
+        } else {
+            (count as u16, cd.len() as u32, cd_offset as u32)
+        };
+        out.extend(count.to_le_bytes());
+        out.extend(count.to_le_bytes());
+        out.extend(cd_size.to_le_bytes());
+        out.extend(cd_offset.to_le_bytes());
+        out.extend(0u16.to_le_bytes());
+        out
+    }
+
+    /// One ustar member: header block, then the data padded to 512 bytes
+    fn tar_member(out: &mut Vec<u8>, name: &str, typeflag: u8, data: &[u8], link: &str) {
+        let mut header = [0u8; 512];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[100..108].copy_from_slice(b"0000644\0");
+        header[124..136].copy_from_slice(format!("{:011o}\0", data.len()).as_bytes());
+        header[136..148].copy_from_slice(format!("{:011o}\0", 1_600_000_000).as_bytes());
+        header[148..156].copy_from_slice(b"        ");
+        header[156] = typeflag;
+        header[157..157 + link.len()].copy_from_slice(link.as_bytes());
+        header[257..265].copy_from_slice(b"ustar\x0000");
+        out.extend(header);
+        out.extend(data);
+        out.resize(out.len().next_multiple_of(512), 0);
+    }
+
+    fn tar() -> Vec<u8> {
+        let mut out = Vec::new();
+        tar_member(&mut out, "./a.txt", b'0', HELLO, "");
+        tar_member(&mut out, "dir/", b'5', &[], "");
+        tar_member(&mut out, "dir/b.bin", b'0', BLOB, "");
+        tar_member(&mut out, "dir/link", b'2', &[], "../a.txt");
+        out.extend([0; 1024]);
+        out
+    }
+
+    /// Write `bytes` to a temp file named `name` and list it with hashing on
+    fn scan(name: &str, bytes: &[u8]) -> (HashMap<String, FileEntry>, Vec<(PathBuf, String)>) {
+        let path = std::env::temp_dir().join(format!("omnidiff_test_{}_{}", std::process::id(), name));
+        std::fs::write(&path, bytes).unwrap();
+        let counters = ScanCounters::with_limit(0);
+        let (tx, _rx) = crossbeam_channel::unbounded();
+        let files = scan_archive(&path, &Gitignore::empty(), &FileFilter::default(), true, &AtomicBool::new(false), &counters, &tx);
+        std::fs::remove_file(&path).ok();
+        let errors = std::mem::take(&mut *counters.errors.lock().unwrap());
+        (files, errors)
+    }
+
+    fn hash(data: &[u8]) -> Option<String> {
+        Some(blake3::hash(data).to_hex().to_string())
+    }
+
+    fn assert_members(files: &HashMap<String, FileEntry>) {
+        assert_eq!(files["a.txt"].size, HELLO.len() as u64);
+        assert_eq!(files["a.txt"].hash, hash(HELLO));
+        assert_eq!(files["dir/b.bin"].size, BLOB.len() as u64);
+        assert_eq!(files["dir/b.bin"].hash, hash(BLOB));
+    }
+
+    #[test]
+    fn zip_round_trip() {
+        let (files, errors) = scan("plain.zip", &zip(&[("a.txt", HELLO), ("dir/", &[]), ("dir/b.bin", BLOB)], false));
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(files.len(), 2);
+        assert_members(&files);
+    }
+
+    #[test]
+    fn zip64_round_trip() {
+        let (files, errors) = scan("big.zip", &zip(&[("a.txt", HELLO), ("dir/b.bin", BLOB)], true));
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(files.len(), 2);
+        assert_members(&files);
+    }
+
+    #[test]
+    fn zip_directory_past_its_end_record_is_rejected() {
+        let mut bytes = zip(&[("a.txt", HELLO)], false);
+        let size_at = bytes.len() - 10;
+        bytes[size_at..size_at + 4].copy_from_slice(&0x7FFF_FFFFu32.to_le_bytes());
+        let (files, errors) = scan("bogus.zip", &bytes);
+        assert!(files.is_empty());
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].1.contains("out of bounds"), "{}", errors[0].1);
+    }
+
+    #[test]
+    fn members_count_against_the_scan_limit() {
+        let path = std::env::temp_dir().join(format!("omnidiff_test_{}_limit.tar", std::process::id()));
+        std::fs::write(&path, tar()).unwrap();
+        let counters = ScanCounters::with_limit(2);
+        // Nobody listening to answer the limit prompt counts as a no
+        let (tx, rx) = crossbeam_channel::unbounded();
+        drop(rx);
+        let cancel = AtomicBool::new(false);
+        let files = scan_archive(&path, &Gitignore::empty(), &FileFilter::default(), false, &cancel, &counters, &tx);
+        std::fs::remove_file(&path).ok();
+        assert_eq!(files.len(), 1);
+        assert!(cancel.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn tar_round_trip() {
+        let (files, errors) = scan("plain.tar", &tar());
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(files.len(), 3);
+        assert_members(&files);
+        assert_eq!(files["a.txt"].modified, 1_600_000_000);
+        assert_eq!(files["dir/link"].link_target, Some(PathBuf::from("../a.txt")));
+        assert_eq!(files["dir/link"].hash, None);
+    }
+
+    #[test]
+    fn tar_hard_links_long_links_and_sparse_members() {
+        let long_target = format!("{}/target", "deep".repeat(40));
+        let mut out = Vec::new();
+        tar_member(&mut out, "dir/b.bin", b'0', BLOB, "");
+        tar_member(&mut out, "hard.bin", b'1', &[], "dir/b.bin");
+        tar_member(&mut out, "././@LongLink", b'K', format!("{}\0", long_target).as_bytes(), "");
+        tar_member(&mut out, "long", b'2', &[], "");
+        tar_member(&mut out, "sparse.img", b'S', &[0; 16], "");
+        out.extend([0; 1024]);
+
+        let (files, errors) = scan("links.tar", &out);
+        assert_eq!(files["hard.bin"].size, BLOB.len() as u64);
+        assert_eq!(files["hard.bin"].hash, hash(BLOB));
+        assert_eq!(files["long"].link_target, Some(PathBuf::from(&long_target)));
+        assert!(!files.contains_key("sparse.img"));
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(errors[0].0.ends_with("sparse.img") && errors[0].1.contains("Sparse"), "{:?}", errors);
+    }
+
+    #[test]
+    fn tar_gz_round_trip() {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&tar()).unwrap();
+        let (files, errors) = scan("packed.tar.gz", &encoder.finish().unwrap());
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(files.len(), 3);
+        assert_members(&files);
+    }
+}
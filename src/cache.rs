@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::scanner::{calculate_hash, FileEntry};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedHash {
+    size: u64,
+    modified: u64,
+    hash: String,
+}
+
+/// Full content hashes remembered between runs, keyed by absolute path.
+/// An entry is only trusted while the file's size and mtime are unchanged.
+#[derive(Debug, Default)]
+pub struct HashCache {
+    entries: Mutex<HashMap<PathBuf, CachedHash>>,
+}
+
+impl HashCache {
+    /// Load the cache from `path`; a missing or unreadable file starts empty
+    pub fn load(path: &Path) -> Self {
+        let entries = fs::read(path).ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+        Self { entries: Mutex::new(entries) }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let entries = self.entries.lock().unwrap();
+        let data = serde_json::to_vec(&*entries).map_err(|e| e.to_string())?;
+        // Write to a temp file first so a crash never leaves a truncated cache
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, data).map_err(|e| e.to_string())?;
+        fs::rename(&tmp, path).map_err(|e| e.to_string())
+    }
+
+    /// Cached hash for `entry`, computing and storing it on a miss
+    pub fn hash(&self, entry: &FileEntry) -> std::io::Result<String> {
+        self.hash_with(entry, || calculate_hash(&entry.path))
+    }
+
+    /// Like `hash`, but a miss is computed by `compute`, e.g. from an already open file
+    pub fn hash_with(&self, entry: &FileEntry, compute: impl FnOnce() -> std::io::Result<String>) -> std::io::Result<String> {
+        if let Some(cached) = self.entries.lock().unwrap().get(&entry.path) {
+            if cached.size == entry.size && cached.modified == entry.modified {
+                return Ok(cached.hash.clone());
+            }
+        }
+
+        let hash = compute()?;
+        self.entries.lock().unwrap().insert(entry.path.clone(), CachedHash {
+            size: entry.size,
+            modified: entry.modified,
+            hash: hash.clone(),
+        });
+        Ok(hash)
+    }
+}
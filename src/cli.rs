@@ -0,0 +1,300 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+
+use crossbeam_channel::unbounded;
+
+use crate::scanner::{self, CompareOptions, CompareResult, FileFilter, ScanStatus, SyncAction, SyncOptions};
+
+const USAGE: &str = "\
+Usage: omnidiff --source <DIR> --dest <DIR> [options]
+
+Either side may also be a .zip, .tar, .tar.gz or .tgz archive (compare only), or
+a .sha256 / .b3sum checksum manifest to verify the other side's files against.
+
+Options:
+  --check-content           Verify file contents with Blake3 (default: metadata only, no file is read)
+  --ignore-case             Match paths case-insensitively
+  --mtime-tolerance <SECS>  Treat closer modification times as equal (default: 2)
+  --sample-kb <KB>          Bytes read from each end before a full hash, in KB (default: 16)
+  --exclude <PATTERN>       Gitignore-style pattern to skip (repeatable)
+  --detect-renames          With --check-content, pair missing and extra files with the same content
+  --ignore-eol              With --check-content, treat text files differing only in CRLF vs LF as identical
+  --track-dirs              Also compare folders; syncing then creates empty ones
+  --max-depth <N>           Only descend N levels (1 = files directly in the folders)
+  --include-hidden          Also compare dotfiles and hidden/system files (skipped by default)
+  --compare-links           Count symlinks pointing to different targets as different
+  --compare-perms           Report identical files whose Unix permission bits differ
+  --compare-owner           With --compare-perms, also compare the owning user and group
+  --sync                    Copy missing and different files from source to destination
+  --mirror                  Like --sync, and move files only in the destination to the trash
+  --dry-run                 With --sync/--mirror, only print the planned operations
+  --verify                  With --sync/--mirror, re-hash every copied file
+  --stage                   With --sync, copy into a new __omnidiff_staging_<time> folder of the destination
+  --backup                  With --sync, move overwritten files into a __omnidiff_backup_<time> folder first
+  --fix-perms               With --sync/--mirror, give files whose permissions differ the source's mode (and owner)
+  --apply-perms             With --sync/--mirror, set each copied file's mode to its source's (Unix)
+  --apply-dir-perms         With --apply-perms, do the same for the folders copies land in
+  --retries <N>             Extra attempts for copies hitting transient IO errors (default: 3)
+  --bwlimit <MB/S>          Cap the combined copy rate (default: unlimited)
+  --json                    Print the comparison as JSON
+  -v, --verbose             Log every classification and sync decision to stderr (RUST_LOG also works)
+  -h, --help                Show this help
+
+Exit status: 0 = folders match, 1 = differences found or files unreadable, 2 = error";
+
+#[derive(Default)]
+struct Args {
+    source: Option<PathBuf>,
+    dest: Option<PathBuf>,
+    check_content: bool,
+    case_insensitive: bool,
+    exclude_patterns: Vec<String>,
+    mtime_tolerance: Option<u64>,
+    sample_kb: usize,
+    track_dirs: bool,
+    detect_renames: bool,
+    ignore_eol: bool,
+    max_depth: Option<usize>,
+    include_hidden: bool,
+    compare_links: bool,
+    compare_perms: bool,
+    compare_owner: bool,
+    fix_perms: bool,
+    apply_perms: bool,
+    apply_dir_perms: bool,
+    sync: bool,
+    mirror: bool,
+    dry_run: bool,
+    verify: bool,
+    stage: bool,
+    backup: bool,
+    retries: Option<u32>,
+    bandwidth_limit_mb: f64,
+    json: bool,
+    verbose: bool,
+}
+
+fn parse_args(args: &[String]) -> Result<Args, String> {
+    let mut parsed = Args::default();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = |name: &str| iter.next().cloned().ok_or(format!("{} needs a value", name));
+        match arg.as_str() {
+            "--source" => parsed.source = Some(value(arg)?.into()),
+            "--dest" => parsed.dest = Some(value(arg)?.into()),
+            "--exclude" => parsed.exclude_patterns.push(value(arg)?),
+            "--mtime-tolerance" => {
+                let secs = value(arg)?;
+                parsed.mtime_tolerance = Some(secs.parse().map_err(|_| format!("Invalid --mtime-tolerance: {}", secs))?);
+            }
+            "--sample-kb" => {
+                let kb = value(arg)?;
+                parsed.sample_kb = kb.parse().map_err(|_| format!("Invalid --sample-kb: {}", kb))?;
+            }
+            "--retries" => {
+                let n = value(arg)?;
+                parsed.retries = Some(n.parse().map_err(|_| format!("Invalid --retries: {}", n))?);
+            }
+            "--bwlimit" => {
+                let mb = value(arg)?;
+                parsed.bandwidth_limit_mb = mb.parse().ok().filter(|v: &f64| *v >= 0.0)
+                    .ok_or(format!("Invalid --bwlimit: {}", mb))?;
+            }
+            "--max-depth" => {
+                let n = value(arg)?;
+                parsed.max_depth = Some(n.parse().map_err(|_| format!("Invalid --max-depth: {}", n))?);
+            }
+            "--check-content" => parsed.check_content = true,
+            "--ignore-case" => parsed.case_insensitive = true,
+            "--track-dirs" => parsed.track_dirs = true,
+            "--detect-renames" => parsed.detect_renames = true,
+            "--ignore-eol" => parsed.ignore_eol = true,
+            "--include-hidden" => parsed.include_hidden = true,
+            "--compare-links" => parsed.compare_links = true,
+            "--compare-perms" => parsed.compare_perms = true,
+            "--compare-owner" => parsed.compare_owner = true,
+            "--fix-perms" => parsed.fix_perms = true,
+            "--apply-perms" => parsed.apply_perms = true,
+            "--apply-dir-perms" => parsed.apply_dir_perms = true,
+            "--sync" => parsed.sync = true,
+            "--mirror" => parsed.mirror = true,
+            "--dry-run" => parsed.dry_run = true,
+            "--verify" => parsed.verify = true,
+            "--stage" => parsed.stage = true,
+            "--backup" => parsed.backup = true,
+            "--json" => parsed.json = true,
+            "-v" | "--verbose" => parsed.verbose = true,
+            other => return Err(format!("Unknown argument: {}", other)),
+        }
+    }
+    Ok(parsed)
+}
+
+/// Run a comparison (and optional sync) without the GUI, returning the exit code
+pub fn run(args: &[String]) -> i32 {
+    if args.iter().any(|a| a == "-h" || a == "--help") {
+        println!("{}", USAGE);
+        return 0;
+    }
+    let args = match parse_args(args) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return 2;
+        }
+    };
+    let (Some(source), Some(dest)) = (args.source.clone(), args.dest.clone()) else {
+        eprintln!("--source and --dest are required\n\n{}", USAGE);
+        return 2;
+    };
+    crate::logging::init(args.verbose);
+
+    let options = CompareOptions {
+        check_content: args.check_content,
+        exclude_patterns: args.exclude_patterns.clone(),
+        case_insensitive: args.case_insensitive,
+        mtime_tolerance: args.mtime_tolerance.unwrap_or(2),
+        track_dirs: args.track_dirs,
+        detect_renames: args.detect_renames,
+        ignore_line_endings: args.ignore_eol,
+        partial_chunk: args.sample_kb * 1024,
+        compare_link_targets: args.compare_links,
+        compare_permissions: args.compare_perms,
+        compare_owner: args.compare_owner,
+        filter: FileFilter { max_depth: args.max_depth, skip_hidden: !args.include_hidden, ..Default::default() },
+        ..Default::default()
+    };
+    let cancel = AtomicBool::new(false);
+    // Progress events are not shown; the receiver just has to outlive the run
+    let (tx, _rx) = unbounded();
+    let results = match scanner::run_comparison(source.clone(), dest.clone(), &options, &cancel, tx, None) {
+        Ok(results) => results,
+        Err(e) => {
+            eprintln!("Comparison failed: {}", e);
+            return 2;
+        }
+    };
+
+    if args.json {
+        match serde_json::to_string_pretty(&results) {
+            Ok(json) => println!("{}", json),
+            Err(e) => {
+                eprintln!("Could not serialize results: {}", e);
+                return 2;
+            }
+        }
+    } else {
+        print_summary(&results);
+    }
+
+    let differs = !results.missing_in_dest.is_empty()
+        || !results.missing_in_source.is_empty()
+        || !results.different_content.is_empty()
+        || !results.timestamp_only.is_empty()
+        || !results.dirs_missing_in_dest.is_empty()
+        || !results.renamed.is_empty()
+        || !results.permissions_differ.is_empty()
+        || !results.hash_errors.is_empty()
+        // An incomplete comparison must not pass as a match
+        || !results.scan_errors.is_empty();
+
+    if (args.sync || args.mirror) && !sync(&source, &dest, &results, &args) {
+        return 2;
+    }
+    if differs { 1 } else { 0 }
+}
+
+fn print_summary(results: &CompareResult) {
+    let section = |title: &str, paths: Vec<&str>| {
+        println!("{} ({}):", title, paths.len());
+        for path in paths {
+            println!("  {}", path);
+        }
+    };
+    section("Missing in destination", results.missing_in_dest.iter().map(|f| f.rel_path.as_str()).collect());
+    section("Extra in destination", results.missing_in_source.iter().map(|f| f.rel_path.as_str()).collect());
+    section("Different", results.different_content.iter().map(|(src, _)| src.rel_path.as_str()).collect());
+    if !results.timestamp_only.is_empty() {
+        section("Timestamp only", results.timestamp_only.iter().map(|(src, _)| src.rel_path.as_str()).collect());
+    }
+    if !results.renamed.is_empty() {
+        let pairs = results.renamed.iter().map(|(src, dest)| format!("{} -> {}", src.rel_path, dest.rel_path)).collect::<Vec<_>>();
+        section("Renamed or moved", pairs.iter().map(String::as_str).collect());
+    }
+    if !results.permissions_differ.is_empty() {
+        let lines = results.permissions_differ.iter()
+            .map(|(src, dest)| format!("{} ({} -> {})", src.rel_path, scanner::permissions_text(src), scanner::permissions_text(dest)))
+            .collect::<Vec<_>>();
+        section("Permissions differ", lines.iter().map(String::as_str).collect());
+    }
+    if !results.dirs_missing_in_dest.is_empty() {
+        section("Folders missing in destination", results.dirs_missing_in_dest.iter().map(String::as_str).collect());
+    }
+    println!("Identical: {}", results.identical_count);
+    if results.metadata_only {
+        println!("Metadata comparison (no content read); use --check-content to verify bytes");
+    }
+    for (path, msg) in &results.scan_errors {
+        eprintln!("Unreadable: {}: {}", path.display(), msg);
+    }
+    for (src, _, msg) in &results.hash_errors {
+        eprintln!("Could not hash: {}: {}", src.rel_path, msg);
+    }
+}
+
+/// Apply (or preview) a source → destination sync; false if anything failed
+fn sync(source: &Path, dest: &Path, results: &CompareResult, args: &Args) -> bool {
+    let options = SyncOptions {
+        delete_extra: args.mirror,
+        preserve_timestamps: true,
+        dry_run: args.dry_run,
+        verify: args.verify,
+        stage: args.stage,
+        backup: args.backup,
+        fix_permissions: args.fix_perms,
+        fix_owner: args.fix_perms && args.compare_owner,
+        apply_permissions: args.apply_perms,
+        apply_dir_permissions: args.apply_perms && args.apply_dir_perms,
+        retries: args.retries.unwrap_or(SyncOptions::default().retries),
+        bandwidth_limit: (args.bandwidth_limit_mb * 1_000_000.0) as u64,
+        ..Default::default()
+    };
+    let cancel = AtomicBool::new(false);
+    let paused = AtomicBool::new(false);
+    let (tx, rx) = unbounded();
+    let errors = match scanner::run_sync(source.to_path_buf(), dest.to_path_buf(), results, &options, &cancel, &paused, tx) {
+        Ok(errors) => errors,
+        Err(e) => {
+            eprintln!("Sync failed: {}", e);
+            return false;
+        }
+    };
+
+    for status in rx.try_iter() {
+        match status {
+            ScanStatus::Planned(action, _, rel_path) => {
+                let verb = match action {
+                    SyncAction::Copy => "COPY",
+                    SyncAction::Update => "UPDATE",
+                    SyncAction::Delete => "DELETE",
+                    SyncAction::Skip => "SKIP",
+                    SyncAction::Permissions => "CHMOD",
+                };
+                // Keep stdout parseable when it carries JSON
+                if args.json {
+                    eprintln!("{:<6} {}", verb, rel_path);
+                } else {
+                    println!("{:<6} {}", verb, rel_path);
+                }
+            }
+            ScanStatus::Staged(dir) => eprintln!("Staged into {}", dir.display()),
+            ScanStatus::BackedUp(count) => eprintln!("Backed up {} overwritten files", count),
+            ScanStatus::Verified(count) => eprintln!("Verified {} copies against their source", count),
+            _ => {}
+        }
+    }
+    for (path, msg) in &errors {
+        eprintln!("Failed: {}: {}", path.display(), msg);
+    }
+    errors.is_empty()
+}
@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+use crossbeam_channel::Sender;
+use rayon::prelude::*;
+use serde::Serialize;
+
+use crate::cache::HashCache;
+use crate::scanner::{self, CompareOptions, FileEntry, ProgressGate, ScanCounters, ScanStatus};
+
+/// Both trees indexed by content instead of path
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ContentResult {
+    pub only_in_source: Vec<FileEntry>, // No copy of this content in dest left to pair with
+    pub only_in_dest: Vec<FileEntry>,
+    pub moved: Vec<(FileEntry, FileEntry)>, // (Source, Dest): same content, different path
+    pub unchanged_count: usize, // Same content at the same path
+    pub scan_errors: Vec<(PathBuf, String)>, // Missing from the match because they could not be listed or read
+}
+
+/// Match files across `source` and `dest` by Blake3 hash, ignoring names and
+/// paths. Only sizes present on both sides get hashed. Symlinks are skipped.
+pub fn run_content_match(
+    source: PathBuf,
+    dest: PathBuf,
+    options: &CompareOptions,
+    cancel: &AtomicBool,
+    tx: Sender<ScanStatus>
+) -> Result<ContentResult, String> {
+    scanner::thread_pool(options.threads)?.install(|| {
+        let source_excludes = scanner::build_excludes(&source, &options.exclude_patterns)?;
+        let dest_excludes = scanner::build_excludes(&dest, &options.exclude_patterns)?;
+
+        tx.send(ScanStatus::ScanningBoth).ok();
+        let counters = ScanCounters::with_limit(options.scan_limit);
+        let (source_files, dest_files) = rayon::join(
+            || scanner::scan_folder(&source, &source_excludes, options.follow_symlinks, &options.filter, cancel, &counters, &tx),
+            || scanner::scan_folder(&dest, &dest_excludes, options.follow_symlinks, &options.filter, cancel, &counters, &tx)
+        );
+        tx.send(ScanStatus::Scanned(counters.scanned.load(Ordering::Relaxed))).ok();
+
+        if cancel.load(Ordering::Relaxed) {
+            tx.send(ScanStatus::Cancelled).ok();
+            return Err("Cancelled".into());
+        }
+
+        let source_files: Vec<FileEntry> = source_files.into_values().filter(|e| e.link_target.is_none()).collect();
+        let dest_files: Vec<FileEntry> = dest_files.into_values().filter(|e| e.link_target.is_none()).collect();
+
+        // A size found on one side only cannot have a match on the other
+        let mut sizes: HashMap<u64, (bool, bool)> = HashMap::new();
+        for entry in &source_files {
+            sizes.entry(entry.size).or_default().0 = true;
+        }
+        for entry in &dest_files {
+            sizes.entry(entry.size).or_default().1 = true;
+        }
+        let shared = |entry: &FileEntry| sizes[&entry.size] == (true, true);
+
+        let (source_candidates, mut only_in_source): (Vec<_>, Vec<_>) = source_files.into_iter().partition(shared);
+        let (dest_candidates, mut only_in_dest): (Vec<_>, Vec<_>) = dest_files.into_iter().partition(shared);
+        let candidates: Vec<(bool, FileEntry)> = source_candidates.into_iter().map(|e| (true, e))
+            .chain(dest_candidates.into_iter().map(|e| (false, e)))
+            .collect();
+
+        let cache = options.hash_cache.as_deref().map(HashCache::load);
+        let total = candidates.len();
+        let total_bytes: u64 = candidates.iter().map(|(_, e)| e.size).sum();
+        let counter = AtomicUsize::new(0);
+        let progress = ProgressGate::new();
+        let bytes_done = AtomicU64::new(0);
+        let hashed: Vec<(bool, FileEntry)> = candidates.into_par_iter()
+            .filter_map(|(is_source, mut entry)| {
+                if cancel.load(Ordering::Relaxed) {
+                    return None;
+                }
+                entry.hash = match &cache {
+                    Some(cache) => cache.hash(&entry),
+                    None => scanner::calculate_hash(&entry.path),
+                }.ok();
+                let c = counter.fetch_add(1, Ordering::Relaxed) + 1;
+                let done = bytes_done.fetch_add(entry.size, Ordering::Relaxed) + entry.size;
+                if progress.ready(c == total) {
+                    tx.send(ScanStatus::Bytes(done, total_bytes)).ok();
+                    tx.send(ScanStatus::Hashing(c, total)).ok();
+                }
+                Some((is_source, entry))
+            })
+            .collect();
+
+        if cancel.load(Ordering::Relaxed) {
+            tx.send(ScanStatus::Cancelled).ok();
+            return Err("Cancelled".into());
+        }
+        if let (Some(cache), Some(path)) = (&cache, &options.hash_cache) {
+            cache.save(path).ok();
+        }
+
+        let mut by_hash: HashMap<(u64, String), (Vec<FileEntry>, Vec<FileEntry>)> = HashMap::new();
+        for (is_source, entry) in hashed {
+            // Unreadable files cannot be matched; report them as unpaired
+            let Some(hash) = entry.hash.clone() else {
+                if is_source { only_in_source.push(entry) } else { only_in_dest.push(entry) }
+                continue;
+            };
+            let group = by_hash.entry((entry.size, hash)).or_default();
+            if is_source { group.0.push(entry) } else { group.1.push(entry) }
+        }
+
+        let mut scan_errors = std::mem::take(&mut *counters.errors.lock().unwrap());
+        scan_errors.sort();
+        let mut result = ContentResult { only_in_source, only_in_dest, scan_errors, ..Default::default() };
+        for (sources, dests) in by_hash.into_values() {
+            pair_copies(sources, dests, &mut result);
+        }
+        result.only_in_source.sort_by(|a, b| a.rel_path.cmp(&b.rel_path));
+        result.only_in_dest.sort_by(|a, b| a.rel_path.cmp(&b.rel_path));
+        result.moved.sort_by(|a, b| a.0.rel_path.cmp(&b.0.rel_path));
+
+        tx.send(ScanStatus::Complete).ok();
+        Ok(result)
+    })
+}
+
+/// Pair the copies of one content one-to-one: a copy at the same path is
+/// unchanged, the rest pair up by path order as moves, and whatever is left
+/// over on either side stays unpaired
+fn pair_copies(mut sources: Vec<FileEntry>, mut dests: Vec<FileEntry>, result: &mut ContentResult) {
+    sources.sort_by(|a, b| a.rel_path.cmp(&b.rel_path));
+    dests.sort_by(|a, b| a.rel_path.cmp(&b.rel_path));
+    let mut moved_from = Vec::new();
+    for src in sources {
+        match dests.binary_search_by(|d| d.rel_path.cmp(&src.rel_path)) {
+            Ok(i) => {
+                dests.remove(i);
+                result.unchanged_count += 1;
+            },
+            Err(_) => moved_from.push(src),
+        }
+    }
+    let mut dests = dests.into_iter();
+    for src in moved_from {
+        match dests.next() {
+            Some(dest) => result.moved.push((src, dest)),
+            None => result.only_in_source.push(src),
+        }
+    }
+    result.only_in_dest.extend(dests);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(rel_path: &str) -> FileEntry {
+        FileEntry {
+            path: PathBuf::from(rel_path),
+            rel_path: rel_path.to_owned(),
+            size: 4,
+            modified: 0,
+            hash: Some("h".into()),
+            link_target: None,
+            mode: None,
+            owner: None,
+        }
+    }
+
+    fn paths(files: &[FileEntry]) -> Vec<&str> {
+        files.iter().map(|f| f.rel_path.as_str()).collect()
+    }
+
+    #[test]
+    fn copies_pair_one_to_one() {
+        let mut result = ContentResult::default();
+        pair_copies(vec![entry("a"), entry("b")], vec![entry("x"), entry("y"), entry("z")], &mut result);
+        let moved: Vec<(&str, &str)> = result.moved.iter().map(|(s, d)| (s.rel_path.as_str(), d.rel_path.as_str())).collect();
+        assert_eq!(moved, [("a", "x"), ("b", "y")]);
+        assert_eq!(paths(&result.only_in_dest), ["z"]);
+        assert!(result.only_in_source.is_empty());
+    }
+
+    #[test]
+    fn same_path_copy_is_not_reused() {
+        let mut result = ContentResult::default();
+        pair_copies(vec![entry("a"), entry("b"), entry("c")], vec![entry("b"), entry("x")], &mut result);
+        assert_eq!(result.unchanged_count, 1);
+        let moved: Vec<(&str, &str)> = result.moved.iter().map(|(s, d)| (s.rel_path.as_str(), d.rel_path.as_str())).collect();
+        assert_eq!(moved, [("a", "x")]);
+        assert_eq!(paths(&result.only_in_source), ["c"]);
+        assert!(result.only_in_dest.is_empty());
+    }
+}
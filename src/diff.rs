@@ -0,0 +1,467 @@
+use std::fs::File;
+use std::io::Read;
+use std::ops::Range;
+use std::path::Path;
+
+use encoding_rs::{Encoding, UTF_8, WINDOWS_1252};
+use image::{Rgba, RgbaImage};
+use similar::{ChangeTag, DiffTag, TextDiff};
+
+/// One line of a unified diff with 1-based line numbers on each side
+#[derive(Debug, Clone)]
+pub struct DiffLine {
+    pub old_no: Option<usize>,
+    pub new_no: Option<usize>,
+    pub tag: ChangeTag,
+    pub text: String,
+    pub emphasis: Vec<Range<usize>>, // Changed words within a replaced line
+}
+
+/// One aligned row of a side-by-side diff. A missing side means the line
+/// only exists in the other file.
+#[derive(Debug, Clone, Default)]
+pub struct SideBySideRow {
+    pub left: Option<(usize, String)>,  // (line number, text)
+    pub right: Option<(usize, String)>,
+    pub changed: bool,
+    pub left_emphasis: Vec<Range<usize>>, // Changed words, byte ranges into the text
+    pub right_emphasis: Vec<Range<usize>>,
+}
+
+/// A run of rows to render, or a placeholder for hidden unchanged rows
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    Rows(Range<usize>),
+    Collapsed(usize),
+}
+
+/// Lines whose words overlap less than this are shown as a plain rewrite
+const WORD_DIFF_MIN_RATIO: f32 = 0.5;
+
+/// Byte ranges of the words that changed between two paired lines, for
+/// (old, new). Both are empty when the lines have too little in common.
+pub fn word_changes(old: &str, new: &str) -> (Vec<Range<usize>>, Vec<Range<usize>>) {
+    let diff = TextDiff::from_words(old, new);
+    if diff.ratio() < WORD_DIFF_MIN_RATIO {
+        return (Vec::new(), Vec::new());
+    }
+
+    let push = |ranges: &mut Vec<Range<usize>>, range: Range<usize>| match ranges.last_mut() {
+        Some(last) if last.end == range.start => last.end = range.end,
+        _ => ranges.push(range),
+    };
+    let (mut old_pos, mut new_pos) = (0, 0);
+    let (mut old_ranges, mut new_ranges) = (Vec::new(), Vec::new());
+    for change in diff.iter_all_changes() {
+        let len = change.value().len();
+        match change.tag() {
+            ChangeTag::Equal => {
+                old_pos += len;
+                new_pos += len;
+            }
+            ChangeTag::Delete => {
+                push(&mut old_ranges, old_pos..old_pos + len);
+                old_pos += len;
+            }
+            ChangeTag::Insert => {
+                push(&mut new_ranges, new_pos..new_pos + len);
+                new_pos += len;
+            }
+        }
+    }
+    (old_ranges, new_ranges)
+}
+
+/// Which differences inside lines a text diff should not count
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TextOptions {
+    pub ignore_trailing_whitespace: bool,
+    pub ignore_blank_lines: bool,
+}
+
+/// The lines a diff runs on, normalized per `options`, with each one's
+/// 1-based number in the file (blank lines may have been dropped)
+fn diff_lines(text: &str, options: TextOptions) -> (Vec<&str>, Vec<usize>) {
+    text.split_inclusive('\n')
+        .enumerate()
+        .filter(|(_, line)| !(options.ignore_blank_lines && line.trim().is_empty()))
+        .map(|(i, line)| (if options.ignore_trailing_whitespace { line.trim_end() } else { line }, i + 1))
+        .unzip()
+}
+
+/// A diffed line as displayed: without its line terminator
+fn display_line(line: &str) -> String {
+    line.trim_end_matches(['\n', '\r']).to_owned()
+}
+
+/// `old_numbers`/`new_numbers` map diff indexes back to file line numbers
+pub fn unified<'a>(diff: &TextDiff<'a, 'a, '_, str>, old_numbers: &[usize], new_numbers: &[usize]) -> Vec<DiffLine> {
+    let mut lines = Vec::new();
+    for op in diff.ops() {
+        let start = lines.len();
+        lines.extend(diff.iter_changes(op).map(|change| DiffLine {
+            old_no: change.old_index().map(|i| old_numbers[i]),
+            new_no: change.new_index().map(|i| new_numbers[i]),
+            tag: change.tag(),
+            text: display_line(change.value()),
+            emphasis: Vec::new(),
+        }));
+
+        // A replace yields its deletions, then its insertions; pair them up in order
+        if let (DiffTag::Replace, old_range, new_range) = op.as_tag_tuple() {
+            for i in 0..old_range.len().min(new_range.len()) {
+                let (old, new) = (start + i, start + old_range.len() + i);
+                let (old_emphasis, new_emphasis) = word_changes(&lines[old].text, &lines[new].text);
+                lines[old].emphasis = old_emphasis;
+                lines[new].emphasis = new_emphasis;
+            }
+        }
+    }
+    lines
+}
+
+/// Render lines as unified-diff text under a `---`/`+++` header for `name`.
+/// With `changed_only`, unchanged context lines are left out.
+pub fn unified_text(lines: &[DiffLine], name: &str, changed_only: bool) -> String {
+    let mut out = format!("--- a/{}\n+++ b/{}\n", name, name);
+    for line in lines {
+        let prefix = match line.tag {
+            ChangeTag::Delete => '-',
+            ChangeTag::Insert => '+',
+            ChangeTag::Equal if changed_only => continue,
+            ChangeTag::Equal => ' ',
+        };
+        out.push(prefix);
+        out.push_str(&line.text);
+        out.push('\n');
+    }
+    out
+}
+
+/// Split rows into visible runs, hiding unchanged runs that are further
+/// than `context` rows away from any change
+pub fn collapse_unchanged(changed: &[bool], context: usize) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+
+    while i < changed.len() {
+        if changed[i] {
+            i += 1;
+            continue;
+        }
+
+        let run_start = i;
+        while i < changed.len() && !changed[i] {
+            i += 1;
+        }
+
+        // Keep context after the previous change and before the next one
+        let keep_head = if run_start == 0 { 0 } else { context };
+        let keep_tail = if i == changed.len() { 0 } else { context };
+        if i - run_start > keep_head + keep_tail {
+            let hidden_start = run_start + keep_head;
+            let hidden_end = i - keep_tail;
+            if hidden_start > start {
+                segments.push(Segment::Rows(start..hidden_start));
+            }
+            segments.push(Segment::Collapsed(hidden_end - hidden_start));
+            start = hidden_end;
+        }
+    }
+
+    if start < changed.len() {
+        segments.push(Segment::Rows(start..changed.len()));
+    }
+    segments
+}
+
+/// Align old/new lines into rows using the grouped operations of the diff
+pub fn side_by_side(diff: &TextDiff<'_, '_, '_, str>, old_numbers: &[usize], new_numbers: &[usize]) -> Vec<SideBySideRow> {
+    let old = diff.old_slices();
+    let new = diff.new_slices();
+    let numbers = [old_numbers, new_numbers];
+    let line = |side: usize, i: usize, s: &str| (numbers[side][i], display_line(s));
+    let mut rows = Vec::new();
+
+    for op in diff.ops() {
+        let (tag, old_range, new_range) = op.as_tag_tuple();
+        match tag {
+            DiffTag::Equal => {
+                for (o, n) in old_range.zip(new_range) {
+                    rows.push(SideBySideRow { left: Some(line(0, o, old[o])), right: Some(line(1, n, new[n])), ..Default::default() });
+                }
+            }
+            DiffTag::Delete => {
+                for o in old_range {
+                    rows.push(SideBySideRow { left: Some(line(0, o, old[o])), changed: true, ..Default::default() });
+                }
+            }
+            DiffTag::Insert => {
+                for n in new_range {
+                    rows.push(SideBySideRow { right: Some(line(1, n, new[n])), changed: true, ..Default::default() });
+                }
+            }
+            DiffTag::Replace => {
+                // Pair replaced lines up, leftovers on the longer side stand alone
+                let count = old_range.len().max(new_range.len());
+                for i in 0..count {
+                    let left = old_range.clone().nth(i).map(|o| line(0, o, old[o]));
+                    let right = new_range.clone().nth(i).map(|n| line(1, n, new[n]));
+                    let (left_emphasis, right_emphasis) = match (&left, &right) {
+                        (Some((_, l)), Some((_, r))) => word_changes(l, r),
+                        _ => Default::default(),
+                    };
+                    rows.push(SideBySideRow { left, right, changed: true, left_emphasis, right_emphasis });
+                }
+            }
+        }
+    }
+
+    rows
+}
+
+/// Bytes of each file shown in the hex view
+pub const HEX_PREVIEW_BYTES: usize = 64 * 1024;
+/// Binaries larger than this keep the plain "binary file" message
+pub const HEX_DIFF_MAX_SIZE: u64 = 100 * 1024 * 1024;
+pub const HEX_ROW_BYTES: usize = 16;
+
+/// Byte ranges of `query` within `text`, ignoring ASCII case
+pub fn find_ignore_case(text: &str, query: &str) -> Vec<Range<usize>> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+    // ASCII lowercasing keeps byte offsets valid for the original text
+    let haystack = text.to_ascii_lowercase();
+    let needle = query.to_ascii_lowercase();
+    haystack.match_indices(&needle).map(|(i, m)| i..i + m.len()).collect()
+}
+
+/// Drop the (possibly partial) last line of a truncated text
+pub fn trim_partial_line(text: &mut String) {
+    let end = text.rfind('\n').map_or(0, |i| i + 1);
+    text.truncate(end);
+}
+
+pub fn read_head(path: &Path, limit: usize) -> std::io::Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    File::open(path)?.take(limit as u64).read_to_end(&mut buffer)?;
+    Ok(buffer)
+}
+
+/// Text files above this size are only diffed over their first TEXT_HEAD_BYTES
+pub const TEXT_DIFF_MAX_SIZE: u64 = 8 * 1024 * 1024;
+pub const TEXT_HEAD_BYTES: usize = 1024 * 1024;
+
+/// Decode file bytes as text, returning the text and the detected encoding name.
+/// A BOM wins; otherwise strict UTF-8, then Windows-1252 for NUL-free legacy
+/// text. Anything with NUL bytes and no BOM is treated as binary.
+/// `truncated` tolerates a character cut in half at the end of the buffer.
+pub fn decode_text(bytes: &[u8], truncated: bool) -> Option<(String, &'static str)> {
+    if let Some((encoding, bom_len)) = Encoding::for_bom(bytes) {
+        let (text, had_errors) = encoding.decode_without_bom_handling(&bytes[bom_len..]);
+        return (truncated || !had_errors).then(|| (text.into_owned(), encoding.name()));
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(text) => return Some((text.to_owned(), UTF_8.name())),
+        Err(e) if truncated && e.error_len().is_none() => {
+            let text = std::str::from_utf8(&bytes[..e.valid_up_to()]).ok()?;
+            return Some((text.to_owned(), UTF_8.name()));
+        }
+        Err(_) => {}
+    }
+    if bytes.contains(&0) {
+        return None;
+    }
+    let (text, _) = WINDOWS_1252.decode_without_bom_handling(bytes);
+    Some((text.into_owned(), WINDOWS_1252.name()))
+}
+
+/// Offsets where the two buffers differ, including bytes past the end of the shorter one
+pub fn byte_differences<'a>(a: &'a [u8], b: &'a [u8]) -> impl Iterator<Item = usize> + 'a {
+    (0..a.len().max(b.len())).filter(move |&i| a.get(i) != b.get(i))
+}
+
+/// Per-pixel heatmap of two same-sized images: unchanged pixels are a dimmed
+/// grayscale of `a`, changed ones run from red to yellow with the channel delta.
+/// Returns the heatmap and the number of differing pixels.
+pub fn image_heatmap(a: &RgbaImage, b: &RgbaImage) -> Option<(RgbaImage, usize)> {
+    if a.dimensions() != b.dimensions() {
+        return None;
+    }
+
+    let mut changed = 0;
+    let heatmap = RgbaImage::from_fn(a.width(), a.height(), |x, y| {
+        let (pa, pb) = (a.get_pixel(x, y), b.get_pixel(x, y));
+        let delta = pa.0.iter().zip(pb.0.iter()).map(|(ca, cb)| ca.abs_diff(*cb)).max().unwrap_or(0);
+        if delta == 0 {
+            let luma = ((pa[0] as u16 + pa[1] as u16 + pa[2] as u16) / 3 / 4) as u8;
+            Rgba([luma, luma, luma, 255])
+        } else {
+            changed += 1;
+            let green = delta.saturating_sub(128).saturating_mul(2);
+            Rgba([128u8.saturating_add(delta / 2), green, 0, 255])
+        }
+    });
+    Some((heatmap, changed))
+}
+
+/// Everything the diff viewer needs for one file pair, built off the UI thread
+pub enum Loaded {
+    Text {
+        lines: Vec<DiffLine>,
+        rows: Vec<SideBySideRow>,
+        encoding: String, // "UTF-8", or "UTF-16LE → UTF-8" when the sides differ
+        truncated: Option<(u64, usize)>, // (largest file size, lines diffed) when only the head was read
+    },
+    Image {
+        src: RgbaImage,
+        dest: RgbaImage,
+        heatmap: Option<(RgbaImage, usize)>, // None when the dimensions differ
+    },
+    Binary { src: Vec<u8>, dest: Vec<u8> }, // First HEX_PREVIEW_BYTES of each
+    Error(String),
+}
+
+// HEIC/HEIF is left out: no HEIF decoder is built in, so those photos get the hex view
+const IMAGE_EXTENSIONS: [&str; 9] = ["png", "jpg", "jpeg", "bmp", "gif", "webp", "ico", "tiff", "svg"];
+const VIDEO_EXTENSIONS: [&str; 8] = ["mp4", "mov", "mkv", "avi", "webm", "m4v", "wmv", "flv"];
+const ARCHIVE_EXTENSIONS: [&str; 9] = ["zip", "tar", "gz", "tgz", "bz2", "xz", "7z", "rar", "zst"];
+const BINARY_EXTENSIONS: [&str; 10] = ["exe", "dll", "so", "dylib", "bin", "o", "a", "class", "iso", "dmg"];
+
+/// Lowercased text after the last dot, or the whole name when there is none
+fn extension(name: &str) -> String {
+    name.rsplit('.').next().unwrap_or("").to_lowercase()
+}
+
+/// Decode any IMAGE_EXTENSIONS file, rasterizing SVGs at their own size
+fn decode_image(path: &Path) -> Result<RgbaImage, String> {
+    match extension(&path.to_string_lossy()).as_str() {
+        "svg" => {
+            let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+            let img = egui_extras::image::load_svg_bytes(&bytes)?;
+            let [w, h] = img.size;
+            let pixels = img.pixels.iter().flat_map(|p| p.to_srgba_unmultiplied()).collect();
+            RgbaImage::from_raw(w as u32, h as u32, pixels).ok_or_else(|| "Invalid SVG size".into())
+        }
+        _ => {
+            let reader = image::io::Reader::open(path).map_err(|e| e.to_string())?
+                .with_guessed_format().map_err(|e| e.to_string())?;
+            Ok(reader.decode().map_err(|e| e.to_string())?.to_rgba8())
+        }
+    }
+}
+
+/// Row icon for a file, by extension; anything unrecognised counts as text
+pub fn file_icon(name: &str) -> &'static str {
+    let ext = extension(name);
+    let is = |list: &[&str]| list.contains(&ext.as_str());
+    if is(&IMAGE_EXTENSIONS) {
+        "🖼"
+    } else if is(&VIDEO_EXTENSIONS) {
+        "🎞"
+    } else if is(&ARCHIVE_EXTENSIONS) {
+        "📦"
+    } else if is(&BINARY_EXTENSIONS) || name.ends_with(".DS_Store") || name.ends_with("Thumbs.db") {
+        "⚙"
+    } else {
+        "📄"
+    }
+}
+
+/// What the quick-look panel shows for one file
+pub enum Preview {
+    Image(RgbaImage), // Scaled to fit PREVIEW_THUMBNAIL
+    Text(String), // The first PREVIEW_LINES lines
+    Unavailable(String),
+}
+
+const PREVIEW_THUMBNAIL: u32 = 256;
+const PREVIEW_LINES: usize = 20;
+const PREVIEW_HEAD_BYTES: usize = 8 * 1024;
+
+/// A thumbnail or a text snippet of `path`, cheap enough to build on every click
+pub fn preview(path: &Path) -> Preview {
+    if IMAGE_EXTENSIONS.contains(&extension(&path.to_string_lossy()).as_str()) {
+        return match decode_image(path) {
+            Ok(img) => Preview::Image(image::DynamicImage::ImageRgba8(img).thumbnail(PREVIEW_THUMBNAIL, PREVIEW_THUMBNAIL).to_rgba8()),
+            Err(e) => Preview::Unavailable(format!("Failed to load the image: {}", e)),
+        };
+    }
+    match read_head(path, PREVIEW_HEAD_BYTES) {
+        Ok(bytes) => match decode_text(&bytes, true) {
+            Some((text, _)) => Preview::Text(text.lines().take(PREVIEW_LINES).collect::<Vec<_>>().join("\n")),
+            None => Preview::Unavailable("Binary file - no preview.".into()),
+        },
+        Err(e) => Preview::Unavailable(format!("Failed to read file: {}", e)),
+    }
+}
+
+/// Read and diff a file pair as an image, text or hex view, in that order of preference
+pub fn load(src_path: &Path, dest_path: &Path, name: &str, options: TextOptions) -> Loaded {
+    // Known binary system files go straight to the hex view
+    if name.ends_with(".DS_Store") || name.ends_with("Thumbs.db") {
+        return load_binary(src_path, dest_path);
+    }
+
+    if IMAGE_EXTENSIONS.contains(&extension(name).as_str()) {
+        return match (decode_image(src_path), decode_image(dest_path)) {
+            (Ok(src), Ok(dest)) => {
+                let heatmap = image_heatmap(&src, &dest);
+                Loaded::Image { src, dest, heatmap }
+            }
+            (Err(e), _) => Loaded::Error(format!("Failed to load the source image: {}", e)),
+            (_, Err(e)) => Loaded::Error(format!("Failed to load the destination image: {}", e)),
+        };
+    }
+
+    // 1. Try reading as text, decoding UTF-16 and legacy encodings.
+    // Huge files only get their head diffed so the viewer stays responsive.
+    let largest = [src_path, dest_path].iter()
+        .map(|p| std::fs::metadata(p).map(|m| m.len()).unwrap_or(0))
+        .max()
+        .unwrap_or(0);
+    let truncated = largest > TEXT_DIFF_MAX_SIZE;
+    let read_text = |path: &Path| {
+        let bytes = if truncated { read_head(path, TEXT_HEAD_BYTES) } else { std::fs::read(path) };
+        let (mut text, encoding) = decode_text(&bytes.ok()?, truncated)?;
+        if truncated {
+            trim_partial_line(&mut text);
+        }
+        Some((text, encoding))
+    };
+    let ((src_txt, src_enc), (dest_txt, dest_enc)) = match (read_text(src_path), read_text(dest_path)) {
+        (Some(src), Some(dest)) => (src, dest),
+        // 2. Fall back to a hex view
+        _ => return load_binary(src_path, dest_path),
+    };
+
+    let encoding = if src_enc == dest_enc {
+        src_enc.to_owned()
+    } else {
+        format!("{} → {}", src_enc, dest_enc)
+    };
+    let truncated = truncated.then(|| (largest, src_txt.lines().count().max(dest_txt.lines().count())));
+    let (old_lines, old_numbers) = diff_lines(&src_txt, options);
+    let (new_lines, new_numbers) = diff_lines(&dest_txt, options);
+    let diff = TextDiff::from_slices(&old_lines, &new_lines);
+    Loaded::Text {
+        lines: unified(&diff, &old_numbers, &new_numbers),
+        rows: side_by_side(&diff, &old_numbers, &new_numbers),
+        encoding,
+        truncated,
+    }
+}
+
+fn load_binary(src_path: &Path, dest_path: &Path) -> Loaded {
+    let size = |p: &Path| std::fs::metadata(p).map(|m| m.len()).unwrap_or(u64::MAX);
+    if size(src_path) > HEX_DIFF_MAX_SIZE || size(dest_path) > HEX_DIFF_MAX_SIZE {
+        return Loaded::Error("Binary file detected (or invalid encoding). Text comparison unavailable.".into());
+    }
+
+    match (read_head(src_path, HEX_PREVIEW_BYTES), read_head(dest_path, HEX_PREVIEW_BYTES)) {
+        (Ok(src), Ok(dest)) => Loaded::Binary { src, dest },
+        (Err(e), _) | (_, Err(e)) => Loaded::Error(format!("Failed to read file: {}", e)),
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crossbeam_channel::Sender;
+use log::warn;
+use rayon::prelude::*;
+
+use crate::scanner::{self, CompareOptions, FileEntry, ProgressGate, ScanCounters, ScanStatus};
+
+/// Byte-identical files within one tree
+#[derive(Debug, Clone)]
+pub struct DuplicateGroup {
+    pub size: u64,
+    pub hash: String,
+    pub files: Vec<FileEntry>, // Sorted by rel_path; the first one is kept when removing extras
+}
+
+/// Result of a duplicate search
+#[derive(Debug, Clone, Default)]
+pub struct DuplicateScan {
+    pub groups: Vec<DuplicateGroup>,
+    pub errors: Vec<(String, String)>, // rel_path, reason for files that could not be hashed
+    pub scan_errors: Vec<(PathBuf, String)>, // Missing from the search because they could not be listed or read
+}
+
+impl DuplicateGroup {
+    /// Space reclaimed by keeping a single copy
+    pub fn wasted(&self) -> u64 {
+        self.size * (self.files.len() as u64 - 1)
+    }
+}
+
+/// Group `root`'s files by size, then head/tail hash, then full hash.
+/// Empty files are skipped; they are trivially identical and free.
+/// Files that can't be read are left out of the groups and listed in `errors`.
+pub fn find_duplicates(
+    root: PathBuf,
+    options: &CompareOptions,
+    cancel: &AtomicBool,
+    tx: Sender<ScanStatus>
+) -> Result<DuplicateScan, String> {
+    scanner::thread_pool(options.threads)?.install(|| {
+        let excludes = scanner::build_excludes(&root, &options.exclude_patterns)?;
+        tx.send(ScanStatus::ScanningBoth).ok();
+        let counters = ScanCounters::with_limit(options.scan_limit);
+        let files = scanner::scan_folder(&root, &excludes, options.follow_symlinks, &options.filter, cancel, &counters, &tx);
+        tx.send(ScanStatus::Scanned(counters.scanned.load(Ordering::Relaxed))).ok();
+
+        let mut by_size: HashMap<u64, Vec<FileEntry>> = HashMap::new();
+        for entry in files.into_values().filter(|e| e.size > 0 && e.link_target.is_none()) {
+            by_size.entry(entry.size).or_default().push(entry);
+        }
+        let candidates: Vec<FileEntry> = by_size.into_values()
+            .filter(|group| group.len() > 1)
+            .flatten()
+            .collect();
+
+        let errors = Mutex::new(Vec::new());
+        let record = |entry: &FileEntry, e: std::io::Error| {
+            warn!("{}: could not hash: {}", entry.rel_path, e);
+            errors.lock().unwrap().push((entry.rel_path.clone(), e.to_string()));
+        };
+
+        let total = candidates.len();
+        let counter = AtomicUsize::new(0);
+        let progress = ProgressGate::new();
+        let hashed: Vec<(u64, [u8; 32], FileEntry)> = candidates.into_par_iter()
+            .filter_map(|entry| {
+                if cancel.load(Ordering::Relaxed) {
+                    return None;
+                }
+                let c = counter.fetch_add(1, Ordering::Relaxed) + 1;
+                if progress.ready(c == total) {
+                    tx.send(ScanStatus::Hashing(c, total)).ok();
+                }
+                match scanner::calculate_partial_hash(&entry.path, options.partial_chunk) {
+                    Ok(partial) => Some((entry.size, partial, entry)),
+                    Err(e) => {
+                        record(&entry, e);
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        // Only files sharing size and head/tail need the full read
+        let mut by_partial: HashMap<(u64, [u8; 32]), Vec<FileEntry>> = HashMap::new();
+        for (size, partial, entry) in hashed {
+            by_partial.entry((size, partial)).or_default().push(entry);
+        }
+        let to_hash: Vec<FileEntry> = by_partial.into_values()
+            .filter(|group| group.len() > 1)
+            .flatten()
+            .collect();
+        let total = to_hash.len();
+        let total_bytes: u64 = to_hash.iter().map(|e| e.size).sum();
+        let counter = AtomicUsize::new(0);
+        let bytes_done = AtomicU64::new(0);
+        let progress = ProgressGate::new();
+        let full: Vec<(u64, String, FileEntry)> = to_hash.into_par_iter()
+            .filter_map(|mut entry| {
+                if cancel.load(Ordering::Relaxed) {
+                    return None;
+                }
+                let res = scanner::calculate_hash(&entry.path);
+                let c = counter.fetch_add(1, Ordering::Relaxed) + 1;
+                let done = bytes_done.fetch_add(entry.size, Ordering::Relaxed) + entry.size;
+                if progress.ready(c == total) {
+                    tx.send(ScanStatus::Bytes(done, total_bytes)).ok();
+                    tx.send(ScanStatus::Hashing(c, total)).ok();
+                }
+                match res {
+                    Ok(hash) => {
+                        entry.hash = Some(hash.clone());
+                        Some((entry.size, hash, entry))
+                    },
+                    Err(e) => {
+                        record(&entry, e);
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        if cancel.load(Ordering::Relaxed) {
+            tx.send(ScanStatus::Cancelled).ok();
+            return Err("Cancelled".into());
+        }
+
+        let mut by_hash: HashMap<(u64, String), Vec<FileEntry>> = HashMap::new();
+        for (size, hash, entry) in full {
+            by_hash.entry((size, hash)).or_default().push(entry);
+        }
+        let mut groups: Vec<DuplicateGroup> = by_hash.into_iter()
+            .filter(|(_, files)| files.len() > 1)
+            .map(|((size, hash), mut files)| {
+                files.sort_by(|a, b| a.rel_path.cmp(&b.rel_path));
+                DuplicateGroup { size, hash, files }
+            })
+            .collect();
+        groups.sort_by(|a, b| b.wasted().cmp(&a.wasted()).then_with(|| a.files[0].rel_path.cmp(&b.files[0].rel_path)));
+
+        let mut errors = errors.into_inner().unwrap();
+        errors.sort();
+        let mut scan_errors = std::mem::take(&mut *counters.errors.lock().unwrap());
+        scan_errors.sort();
+        tx.send(ScanStatus::Complete).ok();
+        Ok(DuplicateScan { groups, errors, scan_errors })
+    })
+}
+
+/// Move every file but the first of each group to the trash (or delete it
+/// outright), returning the files that could not be removed
+pub fn remove_extras(groups: &[DuplicateGroup], permanent: bool) -> Vec<(PathBuf, String)> {
+    groups.iter()
+        .flat_map(|group| group.files.iter().skip(1))
+        .filter_map(|file| {
+            let res = if permanent {
+                std::fs::remove_file(&file.path).map_err(|e| format!("Delete failed: {}", e))
+            } else {
+                trash::delete(&file.path).map_err(|e| format!("Could not move to trash (file kept): {}", e))
+            };
+            res.err().map(|msg| (file.path.clone(), msg))
+        })
+        .collect()
+}
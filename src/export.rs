@@ -0,0 +1,201 @@
+use std::fs;
+use std::path::Path;
+
+use chrono::{DateTime, Local};
+use humansize::{format_size, DECIMAL};
+
+use crate::scanner::{self, CompareResult, FileEntry};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+    Html,
+}
+
+impl ExportFormat {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "CSV",
+            ExportFormat::Json => "JSON",
+            ExportFormat::Html => "HTML",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+            ExportFormat::Html => "html",
+        }
+    }
+}
+
+/// Write `results` to `path`; the compared folders only appear in the HTML report
+pub fn export_results(results: &CompareResult, format: ExportFormat, path: &Path, source: &str, dest: &str) -> Result<(), String> {
+    let contents = match format {
+        ExportFormat::Csv => to_csv(results),
+        ExportFormat::Json => serde_json::to_string_pretty(results).map_err(|e| e.to_string())?,
+        ExportFormat::Html => to_html(results, source, dest),
+    };
+    fs::write(path, contents).map_err(|e| e.to_string())
+}
+
+/// One row per file: category, rel_path, then source and destination metadata
+pub fn to_csv(results: &CompareResult) -> String {
+    let mut out = String::from("category,rel_path,source_size,dest_size,source_modified,dest_modified,source_hash,dest_hash\n");
+
+    for entry in &results.missing_in_dest {
+        push_row(&mut out, "missing_in_dest", &entry.rel_path, Some(entry), None);
+    }
+    for entry in &results.missing_in_source {
+        push_row(&mut out, "extra_in_dest", &entry.rel_path, None, Some(entry));
+    }
+    for (src, dest) in &results.different_content {
+        push_row(&mut out, "different", &src.rel_path, Some(src), Some(dest));
+    }
+    for (src, dest) in &results.timestamp_only {
+        push_row(&mut out, "timestamp_only", &src.rel_path, Some(src), Some(dest));
+    }
+    for (src, dest, _) in &results.hash_errors {
+        push_row(&mut out, "hash_error", &src.rel_path, Some(src), Some(dest));
+    }
+    for (src, dest) in &results.permissions_differ {
+        push_row(&mut out, "permissions_differ", &src.rel_path, Some(src), Some(dest));
+    }
+    for (src, dest) in &results.renamed {
+        push_row(&mut out, "renamed", &format!("{} -> {}", src.rel_path, dest.rel_path), Some(src), Some(dest));
+    }
+
+    out
+}
+
+fn push_row(out: &mut String, category: &str, rel_path: &str, src: Option<&FileEntry>, dest: Option<&FileEntry>) {
+    let size = |e: Option<&FileEntry>| e.map(|e| e.size.to_string()).unwrap_or_default();
+    let modified = |e: Option<&FileEntry>| e.map(|e| e.modified.to_string()).unwrap_or_default();
+    let hash = |e: Option<&FileEntry>| e.and_then(|e| e.hash.clone()).unwrap_or_default();
+
+    let fields = [
+        category.to_owned(),
+        csv_field(rel_path),
+        size(src),
+        size(dest),
+        modified(src),
+        modified(dest),
+        hash(src),
+        hash(dest),
+    ];
+    out.push_str(&fields.join(","));
+    out.push('\n');
+}
+
+/// Quote a field when it contains a delimiter, quote, or newline
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+const HTML_STYLE: &str = "\
+body { font-family: -apple-system, 'Segoe UI', Helvetica, Arial, sans-serif; margin: 2em; color: #222; }
+h1 { margin-bottom: 0.2em; }
+.meta { color: #666; margin-bottom: 1.5em; }
+.stats span { display: inline-block; margin: 0 0.5em 0.5em 0; padding: 0.4em 0.8em; border-radius: 4px; background: #eef2f5; }
+table { border-collapse: collapse; width: 100%; margin-bottom: 2em; font-size: 0.9em; }
+th, td { text-align: left; padding: 0.3em 0.6em; border-bottom: 1px solid #ddd; }
+th { background: #34495e; color: #fff; }
+tr:nth-child(even) td { background: #f7f9fa; }
+td.hash { font-family: monospace; font-size: 0.85em; word-break: break-all; }
+h2 .count { color: #888; font-weight: normal; }";
+
+/// Self-contained report: inline styles, no scripts or external assets
+pub fn to_html(results: &CompareResult, source: &str, dest: &str) -> String {
+    let size = |e: &FileEntry| format_size(e.size, DECIMAL);
+    let modified = |e: &FileEntry| DateTime::from_timestamp(e.modified as i64, 0)
+        .map(|dt| dt.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_default();
+    let hash = |e: &FileEntry| e.hash.clone().unwrap_or_else(|| "—".into());
+
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>OmniDiff Report</title><style>{}</style></head><body>\n",
+        HTML_STYLE
+    );
+    out.push_str("<h1>OmniDiff Comparison Report</h1>\n");
+    out.push_str(&format!(
+        "<div class=\"meta\">Source: <b>{}</b><br>Destination: <b>{}</b><br>Generated {}</div>\n",
+        html_escape(source), html_escape(dest), Local::now().format("%Y-%m-%d %H:%M:%S")
+    ));
+
+    let stats = [
+        ("Missing in destination", results.missing_in_dest.len()),
+        ("Extra in destination", results.missing_in_source.len()),
+        ("Different", results.different_content.len()),
+        ("Timestamp only", results.timestamp_only.len()),
+        ("Permissions differ", results.permissions_differ.len()),
+        ("Renamed or moved", results.renamed.len()),
+        ("Identical", results.identical_count),
+        ("Could not be hashed", results.hash_errors.len()),
+        ("Skipped by filters", results.filtered_count),
+    ];
+    out.push_str("<div class=\"stats\">");
+    for (label, count) in stats {
+        out.push_str(&format!("<span>{}: <b>{}</b></span>", label, count));
+    }
+    out.push_str("</div>\n");
+
+    let files = |e: &FileEntry| vec![html_escape(&e.rel_path), size(e), modified(e)];
+    push_table(&mut out, "Missing in destination", &["Path", "Size", "Modified"],
+        results.missing_in_dest.iter().map(files).collect());
+    push_table(&mut out, "Extra in destination", &["Path", "Size", "Modified"],
+        results.missing_in_source.iter().map(files).collect());
+    push_table(&mut out, "Different", &["Path", "Source size", "Destination size", "Source hash", "Destination hash"],
+        results.different_content.iter()
+            .map(|(src, dest)| vec![html_escape(&src.rel_path), size(src), size(dest), hash(src), hash(dest)])
+            .collect());
+    push_table(&mut out, "Timestamp only", &["Path", "Size", "Source modified", "Destination modified"],
+        results.timestamp_only.iter()
+            .map(|(src, dest)| vec![html_escape(&src.rel_path), size(src), modified(src), modified(dest)])
+            .collect());
+    push_table(&mut out, "Permissions differ", &["Path", "Source mode", "Destination mode"],
+        results.permissions_differ.iter()
+            .map(|(src, dest)| vec![html_escape(&src.rel_path), html_escape(&scanner::permissions_text(src)), html_escape(&scanner::permissions_text(dest))])
+            .collect());
+    push_table(&mut out, "Renamed or moved", &["Source path", "Destination path", "Size", "Hash"],
+        results.renamed.iter()
+            .map(|(src, dest)| vec![html_escape(&src.rel_path), html_escape(&dest.rel_path), size(src), hash(src)])
+            .collect());
+    push_table(&mut out, "Could not be hashed", &["Path", "Error"],
+        results.hash_errors.iter()
+            .map(|(src, _, msg)| vec![html_escape(&src.rel_path), html_escape(msg)])
+            .collect());
+
+    out.push_str("</body></html>\n");
+    out
+}
+
+/// A titled table; cells must already be escaped. Hash columns get the monospace style.
+fn push_table(out: &mut String, title: &str, headers: &[&str], rows: Vec<Vec<String>>) {
+    if rows.is_empty() {
+        return;
+    }
+    out.push_str(&format!("<h2>{} <span class=\"count\">({})</span></h2>\n<table><tr>", title, rows.len()));
+    for header in headers {
+        out.push_str(&format!("<th>{}</th>", header));
+    }
+    out.push_str("</tr>\n");
+    for row in rows {
+        out.push_str("<tr>");
+        for (cell, header) in row.iter().zip(headers) {
+            let class = if header.ends_with("hash") { " class=\"hash\"" } else { "" };
+            out.push_str(&format!("<td{}>{}</td>", class, cell));
+        }
+        out.push_str("</tr>\n");
+    }
+    out.push_str("</table>\n");
+}
+
+fn html_escape(value: &str) -> String {
+    value.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
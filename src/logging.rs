@@ -0,0 +1,102 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// `log` backend: stderr at the level named by RUST_LOG (off when unset),
+/// plus an optional file that records everything down to debug
+struct Logger {
+    stderr_level: LevelFilter,
+    file: Mutex<Option<File>>,
+}
+
+static LOGGER: std::sync::OnceLock<Logger> = std::sync::OnceLock::new();
+
+/// Install the logger; `verbose` forces debug output on stderr (the CLI's --verbose)
+pub fn init(verbose: bool) {
+    let from_env = std::env::var("RUST_LOG").ok()
+        .and_then(|level| level.parse().ok())
+        .unwrap_or(LevelFilter::Off);
+    let stderr_level = if verbose { from_env.max(LevelFilter::Debug) } else { from_env };
+    let logger = LOGGER.get_or_init(|| Logger { stderr_level, file: Mutex::new(None) });
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(stderr_level);
+    }
+}
+
+/// Start (Some) or stop (None) appending debug logs to `path`
+pub fn set_file(path: Option<&Path>) -> Result<(), String> {
+    let Some(logger) = LOGGER.get() else { return Ok(()) };
+    let file = match path {
+        Some(path) => {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+            Some(OpenOptions::new().create(true).append(true).open(path).map_err(|e| e.to_string())?)
+        }
+        None => None,
+    };
+    let level = if file.is_some() { logger.stderr_level.max(LevelFilter::Debug) } else { logger.stderr_level };
+    *logger.file.lock().unwrap() = file;
+    log::set_max_level(level);
+    Ok(())
+}
+
+/// Verbose logging captures debug and up; trace stays on stderr only. Below
+/// info only our own records are kept, not those of ignore, trash or eframe.
+fn for_file(metadata: &Metadata) -> bool {
+    metadata.level() <= Level::Info
+        || (metadata.level() <= Level::Debug && metadata.target().starts_with(env!("CARGO_CRATE_NAME")))
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format!(
+            "{} {:<5} {}: {}",
+            chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+            record.level(),
+            record.target(),
+            record.args()
+        );
+        if record.level() <= self.stderr_level {
+            eprintln!("{}", line);
+        }
+        if for_file(record.metadata()) {
+            if let Some(file) = self.file.lock().unwrap().as_mut() {
+                writeln!(file, "{}", line).ok();
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = self.file.lock().unwrap().as_mut() {
+            file.flush().ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(level: Level, target: &str) -> Metadata<'_> {
+        Metadata::builder().level(level).target(target).build()
+    }
+
+    #[test]
+    fn file_keeps_dependency_debug_out() {
+        assert!(for_file(&metadata(Level::Debug, "omnidiff::scanner")));
+        assert!(!for_file(&metadata(Level::Debug, "ignore::walk")));
+        assert!(for_file(&metadata(Level::Warn, "trash")));
+        assert!(!for_file(&metadata(Level::Trace, "omnidiff::scanner")));
+    }
+}
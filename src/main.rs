@@ -1,10 +1,28 @@
 mod scanner;
 mod app;
+mod diff;
+mod export;
+mod cache;
+mod cli;
+mod threeway;
+mod duplicates;
+mod tree;
+mod content;
+mod archive;
+mod logging;
+mod manifest;
 
-use app::FolderCompareApp;
+use app::{FolderCompareApp, APP_ID};
 use eframe::egui;
 
 fn main() -> eframe::Result<()> {
+    // Any arguments switch to the headless CLI
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        std::process::exit(cli::run(&args));
+    }
+    logging::init(false);
+
     // Load icon
     let icon_bytes = include_bytes!("../AppIcon.png");
     let icon = load_icon(icon_bytes);
@@ -14,11 +32,15 @@ fn main() -> eframe::Result<()> {
             .with_inner_size([900.0, 700.0])
             .with_title("OmniDiff Pro")
             .with_icon(std::sync::Arc::new(icon)),
+        // The size above is only the first-launch default; later launches restore the last window
+        persist_window: true,
+        // Needed on every platform so the "follow system" theme can see the OS setting
+        follow_system_theme: true,
         ..Default::default()
     };
     
     eframe::run_native(
-        "OmniDiff Pro",
+        APP_ID,
         options,
         Box::new(|cc| Box::new(FolderCompareApp::new(cc))),
     )
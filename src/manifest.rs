@@ -0,0 +1,452 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::time::UNIX_EPOCH;
+
+use crossbeam_channel::Sender;
+use ignore::gitignore::Gitignore;
+use rayon::prelude::*;
+
+use crate::scanner::{self, CompareOptions, CompareResult, FileEntry, ProgressGate, ScanCounters, ScanStatus};
+
+/// Checksum list formats, told apart by the manifest's extension
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Algorithm {
+    Blake3, // .b3sum / .blake3, as written by b3sum
+    Sha256, // .sha256 / .sha256sum, as written by sha256sum or shasum -a 256
+}
+
+fn algorithm(path: &Path) -> Option<Algorithm> {
+    let name = path.file_name()?.to_string_lossy().to_lowercase();
+    if name.ends_with(".b3sum") || name.ends_with(".blake3") {
+        Some(Algorithm::Blake3)
+    } else if name.ends_with(".sha256") || name.ends_with(".sha256sum") {
+        Some(Algorithm::Sha256)
+    } else {
+        None
+    }
+}
+
+/// Whether `path` is a checksum manifest that can stand in for a folder
+pub fn is_manifest(path: &Path) -> bool {
+    algorithm(path).is_some() && path.is_file()
+}
+
+/// Extensions accepted by `is_manifest`, for file dialogs
+pub const EXTENSIONS: [&str; 4] = ["b3sum", "blake3", "sha256", "sha256sum"];
+
+/// Parse `hash  path` (GNU, `*path` for binary mode) and `SHA256 (path) = hash`
+/// (BSD) lines. Paths are relative to the manifest's folder; blank lines and
+/// `#` comments are skipped.
+fn parse(manifest: &Path) -> Result<Vec<(String, String)>, String> {
+    let text = std::fs::read_to_string(manifest).map_err(|e| format!("Could not read {}: {}", manifest.display(), e))?;
+    let is_hash = |s: &str| s.len() == 64 && s.bytes().all(|b| b.is_ascii_hexdigit());
+    let mut entries = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let bsd = line.split_once(" (")
+            .and_then(|(_, rest)| rest.rsplit_once(") = "))
+            .filter(|(_, hash)| is_hash(hash));
+        let (path, hash) = match bsd {
+            Some((path, hash)) => (path, hash),
+            None => match line.split_once(' ') {
+                Some((hash, path)) if is_hash(hash) => (path.strip_prefix([' ', '*']).unwrap_or(path), hash),
+                _ => return Err(format!("{} line {}: expected \"<hash>  <path>\"", manifest.display(), number + 1)),
+            },
+        };
+        let rel_path = path.replace('\\', "/");
+        let rel_path = rel_path.trim_start_matches("./");
+        entries.push((rel_path.to_owned(), hash.to_lowercase()));
+    }
+    Ok(entries)
+}
+
+/// Manifest lines as FileEntries carrying the listed hash. A manifest has no
+/// sizes or times, so those stay 0 until a live file is paired with them.
+fn load(manifest: &Path, excludes: &Gitignore, options: &CompareOptions, live_root: &Path) -> Result<HashMap<String, FileEntry>, String> {
+    let base = manifest.parent().unwrap_or(Path::new(""));
+    Ok(parse(manifest)?.into_iter()
+        // Listed files the scan would skip must not show up as missing. Size
+        // and date bounds can only be judged on a live copy, when there is one.
+        .filter(|(rel_path, _)| {
+            let live = live_root.join(rel_path);
+            options.filter.allows_path(rel_path, &live, excludes) && options.filter.accepts_extension(&live)
+                && std::fs::metadata(scanner::long_path(&live)).ok().is_none_or(|m| {
+                    let modified = m.modified().ok()
+                        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                        .map_or(0, |d| d.as_secs());
+                    !m.is_file() || options.filter.accepts(m.len(), modified)
+                })
+        })
+        .map(|(rel_path, hash)| (rel_path.clone(), FileEntry {
+            path: base.join(&rel_path),
+            rel_path,
+            size: 0,
+            modified: 0,
+            hash: Some(hash),
+            link_target: None,
+            mode: None,
+            owner: None,
+        }))
+        .collect())
+}
+
+fn hash_file(path: &Path, algorithm: Algorithm) -> io::Result<String> {
+    match algorithm {
+        Algorithm::Blake3 => scanner::calculate_hash(path),
+        Algorithm::Sha256 => {
+            let mut file = File::open(scanner::long_path(path))?;
+            let mut hasher = Sha256::new();
+            let mut buffer = vec![0; 256 * 1024];
+            loop {
+                let n = file.read(&mut buffer)?;
+                if n == 0 {
+                    break;
+                }
+                hasher.update(&buffer[..n]);
+            }
+            Ok(hex::encode(hasher.finalize()))
+        }
+    }
+}
+
+/// Verify the live folder on one side against the manifest on the other:
+/// unlisted files, listed files that are gone, and files whose hash changed.
+/// Renames, timestamps and folder tracking don't apply.
+pub fn verify(
+    source: &Path,
+    dest: &Path,
+    options: &CompareOptions,
+    cancel: &AtomicBool,
+    tx: &Sender<ScanStatus>
+) -> Result<CompareResult, String> {
+    let (manifest, live_root, manifest_is_source) = match (algorithm(source).filter(|_| source.is_file()), algorithm(dest).filter(|_| dest.is_file())) {
+        (Some(_), Some(_)) => return Err("Only one side can be a checksum manifest".into()),
+        (Some(_), None) => (source, dest, true),
+        (None, Some(_)) => (dest, source, false),
+        (None, None) => return Err("Neither side is a checksum manifest".into()),
+    };
+    let algorithm = algorithm(manifest).unwrap();
+
+    let excludes = scanner::build_excludes(live_root, &options.exclude_patterns)?;
+    let listed = load(manifest, &excludes, options, live_root)?;
+    tx.send(ScanStatus::ScanningBoth).ok();
+    let counters = ScanCounters::with_limit(options.scan_limit);
+    let mut live = scanner::scan_folder(live_root, &excludes, options.follow_symlinks, &options.filter, cancel, &counters, tx);
+    tx.send(ScanStatus::Scanned(counters.scanned.load(Ordering::Relaxed))).ok();
+    // A manifest kept inside the folder it describes doesn't list itself
+    if let Ok(manifest_canonical) = manifest.canonicalize() {
+        live.retain(|_, e| e.path.canonicalize().ok().as_ref() != Some(&manifest_canonical));
+    }
+    if cancel.load(Ordering::Relaxed) {
+        tx.send(ScanStatus::Cancelled).ok();
+        return Err("Cancelled".into());
+    }
+
+    let (source_files, dest_files) = if manifest_is_source { (listed, live) } else { (live, listed) };
+    let mut pairing = scanner::pair_trees(source_files, dest_files, options.case_insensitive);
+
+    let total = pairing.common.len();
+    let total_bytes: u64 = pairing.common.iter().map(|(src, dest)| src.size + dest.size).sum();
+    let (counter, bytes_done) = (AtomicUsize::new(0), AtomicU64::new(0));
+    let progress = ProgressGate::new();
+    let hash_errors = Mutex::new(Vec::new());
+    pairing.common.par_iter_mut().for_each(|pair| {
+        if cancel.load(Ordering::Relaxed) {
+            return;
+        }
+        let (live, listed) = if manifest_is_source { (&mut pair.1, &mut pair.0) } else { (&mut pair.0, &mut pair.1) };
+        listed.size = live.size;
+        match hash_file(&live.path, algorithm) {
+            Ok(hash) => live.hash = Some(hash),
+            Err(e) => hash_errors.lock().unwrap().push((pair.0.clone(), pair.1.clone(), e.to_string())),
+        }
+
+        let c = counter.fetch_add(1, Ordering::Relaxed) + 1;
+        let done = bytes_done.fetch_add(pair.0.size, Ordering::Relaxed) + pair.0.size;
+        if progress.ready(c == total) {
+            tx.send(ScanStatus::Bytes(done, total_bytes)).ok();
+            tx.send(ScanStatus::Hashing(c, total)).ok();
+        }
+    });
+
+    if cancel.load(Ordering::Relaxed) {
+        tx.send(ScanStatus::Cancelled).ok();
+        return Err("Cancelled".into());
+    }
+
+    // Unreadable live files have no hash and are reported as hash errors instead
+    let different_content = pairing.common.iter()
+        .filter(|(src, dest)| src.hash.is_some() && dest.hash.is_some() && src.hash != dest.hash)
+        .cloned()
+        .collect();
+    let mut hash_errors = hash_errors.into_inner().unwrap();
+    hash_errors.sort_by(|a, b| a.0.rel_path.cmp(&b.0.rel_path));
+    let mut result = scanner::build_result(pairing, different_content, Vec::new(), hash_errors, Vec::new(), options);
+    result.metadata_only = false;
+    result.filtered_count = counters.filtered.load(Ordering::Relaxed);
+    result.scan_errors = std::mem::take(&mut *counters.errors.lock().unwrap());
+    result.scan_errors.sort();
+    tx.send(ScanStatus::Complete).ok();
+    Ok(result)
+}
+
+/// What `write_manifest` produced
+#[derive(Debug, Clone)]
+pub struct ManifestReport {
+    pub path: PathBuf,
+    pub files: usize,
+    pub bytes: u64, // Total size of the listed files
+    pub errors: Vec<(PathBuf, String)>, // Left out because they could not be listed or read
+}
+
+/// Hash every file under `root` and write a b3sum-compatible manifest (`hash  rel_path`
+/// lines, sorted) to `out`. Unfollowed symlinks and the manifest itself are skipped.
+pub fn write_manifest(
+    root: PathBuf,
+    out: PathBuf,
+    options: &CompareOptions,
+    cancel: &AtomicBool,
+    tx: Sender<ScanStatus>
+) -> Result<ManifestReport, String> {
+    scanner::thread_pool(options.threads)?.install(|| {
+        let excludes = scanner::build_excludes(&root, &options.exclude_patterns)?;
+        tx.send(ScanStatus::ScanningBoth).ok();
+        let counters = ScanCounters::with_limit(options.scan_limit);
+        let files = scanner::scan_folder(&root, &excludes, options.follow_symlinks, &options.filter, cancel, &counters, &tx);
+        tx.send(ScanStatus::Scanned(counters.scanned.load(Ordering::Relaxed))).ok();
+
+        let out_canonical = out.canonicalize().ok();
+        let mut files: Vec<FileEntry> = files.into_values()
+            .filter(|e| e.link_target.is_none() && (out_canonical.is_none() || e.path.canonicalize().ok() != out_canonical))
+            .collect();
+        files.sort_by(|a, b| a.rel_path.cmp(&b.rel_path));
+
+        let total = files.len();
+        let total_bytes: u64 = files.iter().map(|e| e.size).sum();
+        let (counter, bytes_done) = (AtomicUsize::new(0), AtomicU64::new(0));
+        let progress = ProgressGate::new();
+        let errors = Mutex::new(std::mem::take(&mut *counters.errors.lock().unwrap()));
+        files.par_iter_mut().for_each(|entry| {
+            if cancel.load(Ordering::Relaxed) {
+                return;
+            }
+            match scanner::calculate_hash(&entry.path) {
+                Ok(hash) => entry.hash = Some(hash),
+                Err(e) => errors.lock().unwrap().push((entry.path.clone(), e.to_string())),
+            }
+            let c = counter.fetch_add(1, Ordering::Relaxed) + 1;
+            let done = bytes_done.fetch_add(entry.size, Ordering::Relaxed) + entry.size;
+            if progress.ready(c == total) {
+                tx.send(ScanStatus::Bytes(done, total_bytes)).ok();
+                tx.send(ScanStatus::Hashing(c, total)).ok();
+            }
+        });
+
+        if cancel.load(Ordering::Relaxed) {
+            tx.send(ScanStatus::Cancelled).ok();
+            return Err("Cancelled".into());
+        }
+
+        let write = || -> io::Result<()> {
+            let mut writer = BufWriter::new(File::create(&out)?);
+            for entry in &files {
+                if let Some(hash) = &entry.hash {
+                    writeln!(writer, "{}  {}", hash, entry.rel_path)?;
+                }
+            }
+            writer.flush()
+        };
+        write().map_err(|e| format!("Could not write {}: {}", out.display(), e))?;
+
+        let listed: Vec<&FileEntry> = files.iter().filter(|e| e.hash.is_some()).collect();
+        let mut errors = errors.into_inner().unwrap();
+        errors.sort();
+        tx.send(ScanStatus::Complete).ok();
+        Ok(ManifestReport {
+            files: listed.len(),
+            bytes: listed.iter().map(|e| e.size).sum(),
+            path: out,
+            errors,
+        })
+    })
+}
+
+/// Streaming SHA-256 (FIPS 180-4), for verifying .sha256 manifests
+struct Sha256 {
+    state: [u32; 8],
+    block: [u8; 64],
+    block_len: usize,
+    total_len: u64,
+}
+
+const SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+impl Sha256 {
+    fn new() -> Self {
+        Self {
+            state: [0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19],
+            block: [0; 64],
+            block_len: 0,
+            total_len: 0,
+        }
+    }
+
+    fn update(&mut self, mut data: &[u8]) {
+        self.total_len += data.len() as u64;
+        while !data.is_empty() {
+            let take = (64 - self.block_len).min(data.len());
+            self.block[self.block_len..self.block_len + take].copy_from_slice(&data[..take]);
+            self.block_len += take;
+            data = &data[take..];
+            if self.block_len == 64 {
+                let block = self.block;
+                self.compress(&block);
+                self.block_len = 0;
+            }
+        }
+    }
+
+    fn finalize(mut self) -> [u8; 32] {
+        let bit_len = self.total_len.wrapping_mul(8);
+        self.update(&[0x80]);
+        while self.block_len != 56 {
+            self.update(&[0]);
+        }
+        self.update(&bit_len.to_be_bytes());
+        let mut out = [0; 32];
+        for (chunk, word) in out.chunks_exact_mut(4).zip(self.state) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        out
+    }
+
+    fn compress(&mut self, block: &[u8; 64]) {
+        let mut w = [0u32; 64];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.state;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = h.wrapping_add(s1).wrapping_add(ch).wrapping_add(SHA256_K[i]).wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (state, value) in self.state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sha256_hex(data: &[u8]) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(data);
+        hex::encode(hasher.finalize())
+    }
+
+    #[test]
+    fn sha256_matches_nist_vectors() {
+        assert_eq!(sha256_hex(b""), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert_eq!(sha256_hex(b"abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert_eq!(
+            sha256_hex(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+    }
+
+    #[test]
+    fn sha256_streams_across_block_boundaries() {
+        let data = vec![b'a'; 1000];
+        let mut hasher = Sha256::new();
+        for chunk in data.chunks(7) {
+            hasher.update(chunk);
+        }
+        assert_eq!(hex::encode(hasher.finalize()), sha256_hex(&data));
+    }
+
+    #[test]
+    fn verify_skips_a_manifest_inside_the_folder() {
+        let dir = std::env::temp_dir().join(format!("omnidiff_test_manifest_inside_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.txt"), b"data").unwrap();
+        let manifest = dir.join("folder.b3sum");
+        std::fs::write(&manifest, format!("{}  a.txt\n", blake3::hash(b"data").to_hex())).unwrap();
+
+        let (tx, _rx) = crossbeam_channel::unbounded();
+        let result = verify(&manifest, &dir, &CompareOptions::default(), &AtomicBool::new(false), &tx).unwrap();
+        assert_eq!(result.identical_count, 1);
+        assert!(result.missing_in_source.is_empty(), "{:?}", result.missing_in_source);
+        assert!(result.missing_in_dest.is_empty() && result.different_content.is_empty());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn listed_files_follow_the_scan_filter() {
+        let dir = std::env::temp_dir().join(format!("omnidiff_test_manifest_filter_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        let mut lines = String::new();
+        for name in ["a.txt", ".env", "sub/deep.txt"] {
+            std::fs::write(dir.join(name), name).unwrap();
+            lines.push_str(&format!("{}  {}\n", blake3::hash(name.as_bytes()).to_hex(), name));
+        }
+        let manifest = std::env::temp_dir().join(format!("omnidiff_test_manifest_filter_{}.b3sum", std::process::id()));
+        std::fs::write(&manifest, lines).unwrap();
+
+        let (tx, _rx) = crossbeam_channel::unbounded();
+        let run = |options: CompareOptions| verify(&manifest, &dir, &options, &AtomicBool::new(false), &tx).unwrap();
+        let hidden_skipped = run(CompareOptions::default());
+        assert_eq!(hidden_skipped.identical_count, 2);
+        assert!(hidden_skipped.missing_in_dest.is_empty(), "{:?}", hidden_skipped.missing_in_dest);
+
+        let mut shallow = CompareOptions::default();
+        shallow.filter.max_depth = Some(1);
+        let shallow = run(shallow);
+        assert_eq!(shallow.identical_count, 1);
+        assert!(shallow.missing_in_dest.is_empty(), "{:?}", shallow.missing_in_dest);
+
+        let mut small = CompareOptions::default();
+        small.filter.max_size = Some(5);
+        let small = run(small);
+        assert_eq!(small.identical_count, 1);
+        assert!(small.missing_in_dest.is_empty(), "{:?}", small.missing_in_dest);
+
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::remove_file(&manifest).ok();
+    }
+}
@@ -1,253 +1,1949 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fs::File;
 use std::io::{Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::time::UNIX_EPOCH;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant, UNIX_EPOCH};
 
 use rayon::prelude::*;
 use walkdir::WalkDir;
-use crossbeam_channel::Sender;
+use crossbeam_channel::{RecvTimeoutError, Sender};
 use memmap2::Mmap;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone)]
+use crate::archive;
+use crate::cache::HashCache;
+use crate::manifest;
+
+#[derive(Debug, Clone, Serialize)]
 pub struct FileEntry {
     pub path: PathBuf,       // Full path
-    pub rel_path: String,    // Relative path key
+    pub rel_path: String,    // Relative path key ('/'-separated)
     pub size: u64,
     pub modified: u64,       // Timestamp
     pub hash: Option<String>,
+    pub link_target: Option<PathBuf>, // Set for symlinks recorded without being followed
+    pub mode: Option<u32>, // Unix permission bits (0o7777 mask); None on other platforms and in archives/manifests
+    pub owner: Option<(u32, u32)>, // Unix (uid, gid)
 }
 
 #[derive(Debug, Clone)]
 pub enum ScanStatus {
-    ScanningSource,
-    ScanningDest,
     ScanningBoth,
+    Scanned(usize), // files discovered so far across both trees
     Hashing(usize, usize), // current, total
-    Syncing(usize, usize), // current, total
+    Syncing(usize, usize, String), // current, total, folder of the latest operation ("" = root)
+    Bytes(u64, u64), // bytes processed, total bytes of the current hashing/sync phase
+    FileProgress(String, u64, u64), // rel_path, bytes hashed, size of a file past PROGRESS_HASH_THRESHOLD
+    Verified(usize), // copies whose hash matched their source, sent before Complete
+    Staged(PathBuf), // staging folder a sync copied into, sent before Complete
+    LimitReached(usize, Sender<bool>), // files listed so far; the walks hold until true (go on) or false (cancel) comes back
+    BackedUp(usize), // overwritten files moved to a backup folder first, sent before Complete
+    SyncError(PathBuf, String), // path, reason
+    Planned(SyncAction, SyncDirection, String), // dry-run operation, its direction, rel_path
     Complete,
-    Error(String),
+    Cancelled,
 }
 
 #[derive(Debug, Clone, Default)]
+pub struct CompareOptions {
+    pub check_content: bool,
+    pub exclude_patterns: Vec<String>, // gitignore syntax, one pattern per entry
+    pub case_insensitive: bool,
+    pub hash_cache: Option<PathBuf>, // Sidecar file remembering full hashes between runs
+    pub threads: usize, // Worker threads for scanning and hashing, 0 = all cores
+    pub hash_mode: HashMode,
+    pub follow_symlinks: bool,
+    pub filter: FileFilter,
+    pub mtime_tolerance: u64, // Seconds; shallow mode treats closer mtimes as equal
+    pub track_dirs: bool, // Also list folders missing in dest, so empty ones get synced
+    pub detect_renames: bool, // With check_content, pair missing and extra files holding the same bytes
+    pub partial_chunk: usize, // Bytes sampled from each end by the short-circuit hash, 0 = DEFAULT_PARTIAL_CHUNK
+    pub ignore_line_endings: bool, // With check_content, text pairs that only differ in CRLF vs LF are identical
+    pub scan_limit: usize, // Files listed before asking whether to go on, 0 = never ask
+    pub compare_link_targets: bool, // Unfollowed links differ when they point elsewhere, whatever the targets hold
+    pub compare_permissions: bool, // Identical files whose Unix mode differs go to permissions_differ
+    pub compare_owner: bool, // With compare_permissions, a different uid/gid counts too
+}
+
+/// Per-file criteria applied while scanning; files that fail never enter the maps
+#[derive(Debug, Clone)]
+pub struct FileFilter {
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    pub modified_after: Option<u64>, // Unix timestamp
+    pub include_extensions: Vec<String>, // Lowercase, without the dot; empty = everything
+    pub exclude_extensions: Vec<String>,
+    pub max_depth: Option<usize>, // Levels below the root; 1 = only the root's own files
+    pub skip_hidden: bool, // Dotfiles, plus hidden/system files on Windows; hidden folders are pruned
+}
+
+impl Default for FileFilter {
+    fn default() -> Self {
+        Self {
+            min_size: None,
+            max_size: None,
+            modified_after: None,
+            include_extensions: Vec::new(),
+            exclude_extensions: Vec::new(),
+            max_depth: None,
+            skip_hidden: true,
+        }
+    }
+}
+
+impl FileFilter {
+    pub fn accepts_extension(&self, path: &Path) -> bool {
+        let ext = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+        (self.include_extensions.is_empty() || self.include_extensions.contains(&ext))
+            && !self.exclude_extensions.contains(&ext)
+    }
+
+    pub fn accepts(&self, size: u64, modified: u64) -> bool {
+        self.min_size.is_none_or(|min| size >= min)
+            && self.max_size.is_none_or(|max| size <= max)
+            && self.modified_after.is_none_or(|after| modified >= after)
+    }
+
+    /// The excludes, hidden and depth rules of a folder walk, applied to a path
+    /// that has no directory entries of its own (archive members, manifest lines)
+    pub fn allows_path(&self, rel_path: &str, path: &Path, excludes: &Gitignore) -> bool {
+        let parts: Vec<&str> = rel_path.split('/').collect();
+        if self.max_depth.is_some_and(|depth| parts.len() > depth) {
+            return false;
+        }
+        if self.skip_hidden && parts.iter().any(|p| p.starts_with('.')) {
+            return false;
+        }
+        !excludes.matched_path_or_any_parents(path, false).is_ignore()
+    }
+
+    /// Whether the walk may yield (and descend into) `entry`; the root always passes
+    fn allows_entry(&self, entry: &walkdir::DirEntry, excludes: &Gitignore) -> bool {
+        entry.depth() == 0 || !(
+            excludes.matched(entry.path(), entry.file_type().is_dir()).is_ignore()
+                || (self.skip_hidden && is_hidden(entry))
+        )
+    }
+}
+
+fn is_hidden(entry: &walkdir::DirEntry) -> bool {
+    if entry.file_name().to_string_lossy().starts_with('.') {
+        return true;
+    }
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::fs::MetadataExt;
+        const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+        const FILE_ATTRIBUTE_SYSTEM: u32 = 0x4;
+        if let Ok(metadata) = entry.metadata() {
+            return metadata.file_attributes() & (FILE_ATTRIBUTE_HIDDEN | FILE_ATTRIBUTE_SYSTEM) != 0;
+        }
+    }
+    false
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum HashMode {
+    #[default]
+    Smart, // Size mismatch and head/tail hashes short-circuit the full hash
+    AlwaysFull, // Full-hash every common file and record both hashes
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum SyncDirection {
+    #[default]
+    SourceToDest,
+    DestToSource,
+    Bidirectional, // Copy missing files both ways, newer side wins for different files
+}
+
+#[derive(Debug, Clone)]
+pub struct SyncOptions {
+    pub direction: SyncDirection,
+    pub delete_extra: bool, // Ignored for bidirectional syncs
+    pub only_overwrite_older: bool, // Skip different files whose receiving copy is as new or newer
+    pub permanent_delete: bool, // Bypass the system trash for Mirror Mode deletions
+    pub preserve_timestamps: bool, // Carry modified/accessed times over to the copy
+    pub verify: bool, // Re-hash each copy and compare it with its source
+    pub retries: u32, // Extra attempts for copies failing with a transient IO error
+    pub bandwidth_limit: u64, // Bytes per second across all copies, 0 = unlimited
+    pub dry_run: bool, // Only report planned operations, never touch the disk
+    pub stage: bool, // Copy into a fresh STAGING_PREFIX folder of the receiving root; nothing live is overwritten or deleted
+    pub backup: bool, // Move each file about to be overwritten into a BACKUP_PREFIX folder of its root first
+    pub fix_permissions: bool, // Give permissions_differ files the sending side's mode (Unix only)
+    pub fix_owner: bool, // With fix_permissions, also chown them to the sending side's uid/gid
+    pub apply_permissions: bool, // Give every copied file its source's mode (Unix only)
+    pub apply_dir_permissions: bool, // Likewise for the folders copies land in, created or not
+}
+
+impl Default for SyncOptions {
+    fn default() -> Self {
+        Self {
+            direction: SyncDirection::default(),
+            delete_extra: false,
+            only_overwrite_older: false,
+            permanent_delete: false,
+            preserve_timestamps: false,
+            verify: false,
+            retries: 3,
+            bandwidth_limit: 0,
+            dry_run: false,
+            stage: false,
+            backup: false,
+            fix_permissions: false,
+            fix_owner: false,
+            apply_permissions: false,
+            apply_dir_permissions: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SyncAction {
+    Copy,
+    Update,
+    Delete,
+    Skip, // Different file left alone because the receiving copy is not older
+    Permissions, // Mode (and owner) set to match the sending side, content untouched
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct CompareResult {
     pub missing_in_dest: Vec<FileEntry>,
     pub missing_in_source: Vec<FileEntry>,
     pub different_content: Vec<(FileEntry, FileEntry)>, // (Source, Dest)
+    pub timestamp_only: Vec<(FileEntry, FileEntry)>, // Shallow mode: same size, different mtime
+    pub case_collisions: Vec<String>, // Names that only differ by case within one tree
+    pub identical_count: usize, // Common files that matched
+    #[serde(skip)]
+    pub identical: Vec<FileEntry>, // Source side of those files, for the tree view
+    pub filtered_count: usize, // Files skipped by the size/date/extension filters
+    pub dirs_missing_in_dest: Vec<String>, // Relative folder paths, only with track_dirs
+    pub scan_errors: Vec<(PathBuf, String)>, // Missing from the comparison because they could not be read
+    pub hash_errors: Vec<(FileEntry, FileEntry, String)>, // (Source, Dest, error): common files that could not be hashed
+    pub renamed: Vec<(FileEntry, FileEntry)>, // (Source, Dest): same content at different paths, only with detect_renames
+    pub permissions_differ: Vec<(FileEntry, FileEntry)>, // (Source, Dest): identical content, different mode or owner, only with compare_permissions
+    pub metadata_only: bool, // No file was opened; sizes and mtimes decided every verdict
+    #[serde(skip)]
+    pub dir_mtimes: [HashMap<String, u64>; 2], // Source and dest folder mtimes by rel path ("" = root), for refreshes
+}
+
+/// Progress counters shared by both scan_folder calls
+#[derive(Debug, Default)]
+pub struct ScanCounters {
+    pub scanned: AtomicUsize,
+    pub filtered: AtomicUsize,
+    pub errors: Mutex<Vec<(PathBuf, String)>>, // Entries that could not be listed or stat'ed
+    pub dirs: Mutex<HashMap<PathBuf, u64>>, // Mtimes of the folders whose files were listed
+    limit: usize, // See CompareOptions::scan_limit
+    listed: AtomicUsize, // Files yielded by the walks, counted before any filtering
+    held: AtomicBool, // Set while the user decides whether to go past the limit
+}
+
+impl ScanCounters {
+    pub fn with_limit(limit: usize) -> Self {
+        Self { limit, ..Default::default() }
+    }
+
+    /// Count one listed file. The walk crossing the limit asks the user and
+    /// every walk holds until they answer; a "no" cancels the whole run.
+    pub(crate) fn within_limit(&self, cancel: &AtomicBool, tx: &Sender<ScanStatus>) -> bool {
+        let listed = self.listed.fetch_add(1, Ordering::Relaxed) + 1;
+        if self.limit > 0 && listed == self.limit {
+            self.held.store(true, Ordering::Relaxed);
+            let (reply_tx, reply_rx) = crossbeam_channel::bounded(1);
+            tx.send(ScanStatus::LimitReached(listed, reply_tx)).ok();
+            // The reply sender is dropped if nobody is listening any more, which counts as a no
+            let go_on = loop {
+                match reply_rx.recv_timeout(PAUSE_POLL) {
+                    Ok(answer) => break answer,
+                    Err(RecvTimeoutError::Timeout) if !cancel.load(Ordering::Relaxed) => continue,
+                    Err(_) => break false,
+                }
+            };
+            if !go_on {
+                cancel.store(true, Ordering::Relaxed);
+            }
+            self.held.store(false, Ordering::Relaxed);
+        }
+        wait_while_paused(&self.held, cancel);
+        !cancel.load(Ordering::Relaxed)
+    }
+
+    pub fn error(&self, path: PathBuf, msg: String) {
+        let msg = with_length_hint(&path, msg);
+        warn!("Unreadable {}: {}", path.display(), msg);
+        self.errors.lock().unwrap().push((path, msg));
+    }
+}
+
+/// Longest path Windows handles without the `\\?\` extended-length prefix
+const MAX_PATH: usize = 260;
+
+/// `path` in the `\\?\` extended-length form on Windows once it gets near
+/// MAX_PATH, so deep trees can still be opened, copied and deleted
+#[cfg(windows)]
+pub fn long_path(path: &Path) -> PathBuf {
+    let raw = path.as_os_str().to_string_lossy();
+    // Folders are limited to 248 so an 8.3 name still fits below them
+    if raw.len() < MAX_PATH - 12 || raw.starts_with(r"\\?\") {
+        return path.to_path_buf();
+    }
+    // Verbatim paths skip normalization, so resolve "." / ".." and '/' first
+    let Ok(absolute) = std::path::absolute(path) else { return path.to_path_buf() };
+    let absolute = absolute.to_string_lossy();
+    match absolute.strip_prefix(r"\\") {
+        Some(unc) => PathBuf::from(format!(r"\\?\UNC\{}", unc)),
+        None => PathBuf::from(format!(r"\\?\{}", absolute)),
+    }
+}
+
+#[cfg(not(windows))]
+pub fn long_path(path: &Path) -> PathBuf {
+    path.to_path_buf()
+}
+
+#[cfg(unix)]
+fn unix_permissions(metadata: &std::fs::Metadata) -> (Option<u32>, Option<(u32, u32)>) {
+    use std::os::unix::fs::MetadataExt;
+    (Some(metadata.mode() & 0o7777), Some((metadata.uid(), metadata.gid())))
+}
+
+#[cfg(not(unix))]
+fn unix_permissions(_metadata: &std::fs::Metadata) -> (Option<u32>, Option<(u32, u32)>) {
+    (None, None)
+}
+
+/// Set `path`'s mode, and its owner when given
+#[cfg(unix)]
+fn set_permissions(path: &Path, mode: u32, owner: Option<(u32, u32)>) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+        .map_err(|e| format!("Could not set mode {:o}: {}", mode, e))?;
+    if let Some((uid, gid)) = owner {
+        std::os::unix::fs::chown(path, Some(uid), Some(gid))
+            .map_err(|e| format!("Could not change owner to {}:{}: {}", uid, gid, e))?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_permissions(_path: &Path, _mode: u32, _owner: Option<(u32, u32)>) -> Result<(), String> {
+    Err("Permissions can only be set on Unix".into())
+}
+
+/// Give `to` the permission bits of `from`; a no-op where there are none to copy
+fn copy_mode(from: &Path, to: &Path) -> Result<(), String> {
+    let metadata = std::fs::metadata(long_path(from)).map_err(|e| format!("could not read the source's permissions: {}", e))?;
+    match unix_permissions(&metadata).0 {
+        Some(mode) => set_permissions(&long_path(to), mode, None),
+        None => Ok(()),
+    }
+}
+
+/// Point out an over-long path as the likely cause of a Windows failure
+fn with_length_hint(path: &Path, msg: String) -> String {
+    let len = path.as_os_str().len();
+    if cfg!(windows) && len >= MAX_PATH {
+        format!("{} (path is {} characters, over the Windows limit of {})", msg, len, MAX_PATH)
+    } else {
+        msg
+    }
+}
+
+/// Nanoseconds rather than seconds, so a change right after a scan still shows
+fn dir_mtime(entry: &walkdir::DirEntry) -> Option<u64> {
+    let modified = entry.metadata().ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_nanos() as u64)
+}
+
+/// Depth limit for folders whose files are all within `filter.max_depth`
+fn dir_depth_limit(filter: &FileFilter) -> usize {
+    filter.max_depth.map_or(usize::MAX, |depth| depth.saturating_sub(1))
+}
+
+/// "a/b" for "a/b/c.txt", "" for files in the root
+fn parent_dir(rel_path: &str) -> &str {
+    rel_path.rsplit_once('/').map_or("", |(dir, _)| dir)
+}
+
+const PROGRESS_INTERVAL_MS: u64 = 100;
+
+/// Rate-limits progress events shared by parallel workers, so update
+/// frequency no longer depends on how many files there are or how big
+#[derive(Debug)]
+pub struct ProgressGate {
+    start: Instant,
+    last_ms: AtomicU64,
+}
+
+impl ProgressGate {
+    pub fn new() -> Self {
+        Self { start: Instant::now(), last_ms: AtomicU64::new(0) }
+    }
+
+    /// True when an update is due; `last` always passes so the final count is sent
+    pub fn ready(&self, last: bool) -> bool {
+        let now = self.start.elapsed().as_millis() as u64;
+        let prev = self.last_ms.load(Ordering::Relaxed);
+        if last {
+            self.last_ms.store(now, Ordering::Relaxed);
+            return true;
+        }
+        // Only one worker wins each interval
+        now >= prev + PROGRESS_INTERVAL_MS
+            && self.last_ms.compare_exchange(prev, now, Ordering::Relaxed, Ordering::Relaxed).is_ok()
+    }
 }
 
-/// Short-circuit hashing: first 16KB and last 16KB
-pub fn calculate_partial_hash(path: &Path) -> Option<[u8; 32]> {
-    let mut file = File::open(path).ok()?;
-    let len = file.metadata().ok()?.len();
+pub const DEFAULT_PARTIAL_CHUNK: usize = 16 * 1024;
+
+/// Short-circuit hashing: file length, first `chunk` bytes and last `chunk` bytes
+pub fn calculate_partial_hash(path: &Path, chunk: usize) -> std::io::Result<[u8; 32]> {
+    partial_hash_file(&mut File::open(long_path(path))?, chunk)
+}
+
+fn partial_hash_file(file: &mut File, chunk: usize) -> std::io::Result<[u8; 32]> {
+    let chunk = if chunk == 0 { DEFAULT_PARTIAL_CHUNK } else { chunk };
+    file.rewind()?;
+    let len = file.metadata()?.len();
     let mut hasher = blake3::Hasher::new();
-    let mut buffer = [0; 16384];
+    hasher.update(&len.to_le_bytes());
+    let mut buffer = Vec::with_capacity(chunk);
 
     // Read head
-    let head_count = file.read(&mut buffer).ok()?;
-    hasher.update(&buffer[..head_count]);
+    file.take(chunk as u64).read_to_end(&mut buffer)?;
+    hasher.update(&buffer);
 
     // Read tail if file is large enough to have a separate tail
-    if len > 32768 {
-        file.seek(SeekFrom::End(-16384)).ok()?;
-        let tail_count = file.read(&mut buffer).ok()?;
-        hasher.update(&buffer[..tail_count]);
+    if len > 2 * chunk as u64 {
+        buffer.clear();
+        file.seek(SeekFrom::End(-(chunk as i64)))?;
+        file.take(chunk as u64).read_to_end(&mut buffer)?;
+        hasher.update(&buffer);
     }
 
-    Some(hasher.finalize().into())
+    Ok(hasher.finalize().into())
 }
 
+/// Files below this size are read into memory instead of mapped
+const MMAP_THRESHOLD: u64 = 64 * 1024;
+/// Files from this size up are hashed on several threads. The split runs on
+/// the caller's rayon pool, so it shares the workers hashing other files.
+const RAYON_HASH_THRESHOLD: u64 = 32 * 1024 * 1024;
+
 /// Full hashing using memory mapping for maximum throughput
-pub fn calculate_hash(path: &Path) -> Option<String> {
-    let file = File::open(path).ok()?;
-    let mmap = unsafe { Mmap::map(&file).ok()? };
-    let hash = blake3::hash(&mmap);
-    Some(hash.to_hex().to_string())
+pub fn calculate_hash(path: &Path) -> std::io::Result<String> {
+    full_hash_file(&mut File::open(long_path(path))?, None)
+}
+
+/// Files from this size up report how far their full hash has got
+const PROGRESS_HASH_THRESHOLD: u64 = 512 * 1024 * 1024;
+/// Bytes hashed between two progress reports
+const PROGRESS_HASH_CHUNK: usize = 64 * 1024 * 1024;
+
+/// `calculate_hash` on a file that is already open, wherever its cursor is.
+/// `progress` gets (bytes hashed, size) while a huge file is read.
+fn full_hash_file(file: &mut File, progress: Option<&dyn Fn(u64, u64)>) -> std::io::Result<String> {
+    let len = file.metadata()?.len();
+
+    // Mapping a zero-length file fails (or is unsound) on some platforms
+    if len == 0 {
+        return Ok(blake3::hash(&[]).to_hex().to_string());
+    }
+
+    let hash = if len < MMAP_THRESHOLD {
+        let mut buffer = Vec::with_capacity(len as usize);
+        file.rewind()?;
+        file.read_to_end(&mut buffer)?;
+        blake3::hash(&buffer)
+    } else {
+        let mmap = unsafe { Mmap::map(&*file)? };
+        if let Some(progress) = progress.filter(|_| len >= PROGRESS_HASH_THRESHOLD) {
+            let mut hasher = blake3::Hasher::new();
+            let mut done = 0;
+            for chunk in mmap.chunks(PROGRESS_HASH_CHUNK) {
+                hasher.update_rayon(chunk);
+                done += chunk.len() as u64;
+                progress(done, len);
+            }
+            hasher.finalize()
+        } else if len >= RAYON_HASH_THRESHOLD {
+            blake3::Hasher::new().update_rayon(&mmap).finalize()
+        } else {
+            blake3::hash(&mmap)
+        }
+    };
+    Ok(hash.to_hex().to_string())
 }
 
-pub fn scan_folder(root: &Path) -> HashMap<String, FileEntry> {
+/// Files above this size are not re-read to look past line endings
+const EOL_COMPARE_MAX_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Whether two files hold the same text once every CRLF becomes LF.
+/// Anything with a NUL byte counts as binary and never matches this way.
+fn same_text_ignoring_eol(a: &Path, b: &Path) -> bool {
+    let read = |path: &Path| -> Option<Vec<u8>> {
+        if std::fs::metadata(path).ok()?.len() > EOL_COMPARE_MAX_SIZE {
+            return None;
+        }
+        let bytes = std::fs::read(path).ok()?;
+        (!bytes.contains(&0)).then_some(bytes)
+    };
+    let (Some(a), Some(b)) = (read(a), read(b)) else { return false };
+    let normalized = |bytes: &[u8]| -> Vec<u8> {
+        let mut out = Vec::with_capacity(bytes.len());
+        for (i, &byte) in bytes.iter().enumerate() {
+            if !(byte == b'\r' && bytes.get(i + 1) == Some(&b'\n')) {
+                out.push(byte);
+            }
+        }
+        out
+    };
+    normalized(&a) == normalized(&b)
+}
+
+/// Relative path key using forward slashes on every platform, so trees
+/// scanned on Windows and Unix key-match
+pub fn normalize_rel_path(rel: &Path) -> String {
+    rel.to_string_lossy().replace(std::path::MAIN_SEPARATOR, "/")
+}
+
+/// Compile gitignore-style patterns relative to `root`
+pub fn build_excludes(root: &Path, patterns: &[String]) -> Result<Gitignore, String> {
+    let mut builder = GitignoreBuilder::new(root);
+    for pattern in patterns {
+        builder.add_line(None, pattern).map_err(|e| e.to_string())?;
+    }
+    builder.build().map_err(|e| e.to_string())
+}
+
+pub fn scan_folder(
+    root: &Path,
+    excludes: &Gitignore,
+    follow_symlinks: bool,
+    filter: &FileFilter,
+    cancel: &AtomicBool,
+    counters: &ScanCounters,
+    tx: &Sender<ScanStatus>
+) -> HashMap<String, FileEntry> {
+    // When following links, walkdir tracks the ancestor chain and yields a loop
+    // error instead of descending into a directory it is already inside
     WalkDir::new(root)
+        .follow_links(follow_symlinks)
+        .max_depth(filter.max_depth.unwrap_or(usize::MAX))
         .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| e.file_type().is_file())
+        // Prune excluded and hidden entries during traversal so we never descend into them
+        .filter_entry(|e| filter.allows_entry(e, excludes))
+        .take_while(|_| !cancel.load(Ordering::Relaxed))
+        .filter_map(|e| e.map_err(|err| {
+            let path = err.path().unwrap_or(root).to_path_buf();
+            counters.error(path, err.to_string());
+        }).ok())
+        .inspect(|e| {
+            if e.file_type().is_dir() && e.depth() <= dir_depth_limit(filter) {
+                if let Some(mtime) = dir_mtime(e) {
+                    counters.dirs.lock().unwrap().insert(e.path().to_path_buf(), mtime);
+                }
+            }
+        })
+        .filter(|e| e.file_type().is_file() || e.file_type().is_symlink())
+        .take_while(|_| counters.within_limit(cancel, tx))
         .par_bridge()
         .filter_map(|entry| {
+            let c = counters.scanned.fetch_add(1, Ordering::Relaxed) + 1;
+            if c.is_multiple_of(1000) {
+                tx.send(ScanStatus::Scanned(c)).ok();
+            }
+
             let path = entry.path().to_path_buf();
-            let metadata = entry.metadata().ok()?;
+            if !filter.accepts_extension(&path) {
+                debug!("Skipped by extension filter: {}", path.display());
+                counters.filtered.fetch_add(1, Ordering::Relaxed);
+                return None;
+            }
+            let metadata = entry.metadata()
+                .map_err(|e| counters.error(path.clone(), e.to_string()))
+                .ok()?;
             let size = metadata.len();
             let modified = metadata.modified().unwrap_or(UNIX_EPOCH)
                 .duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+            if !filter.accepts(size, modified) {
+                debug!("Skipped by size/date filter: {}", path.display());
+                counters.filtered.fetch_add(1, Ordering::Relaxed);
+                return None;
+            }
 
-            let rel_path = path.strip_prefix(&root).ok()?.to_string_lossy().to_string();
+            let rel_path = normalize_rel_path(path.strip_prefix(root).ok()?);
+            let link_target = if entry.file_type().is_symlink() {
+                Some(std::fs::read_link(&path).map_err(|e| counters.error(path.clone(), e.to_string())).ok()?)
+            } else {
+                None
+            };
 
+            let (mode, owner) = unix_permissions(&metadata);
             Some((rel_path.clone(), FileEntry {
                 path,
                 rel_path,
                 size,
                 modified,
                 hash: None,
+                link_target,
+                mode,
+                owner,
             }))
         })
         .collect()
 }
 
+/// A folder, or an archive listed as if it were one
+fn scan_side(
+    root: &Path,
+    excludes: &Gitignore,
+    options: &CompareOptions,
+    cancel: &AtomicBool,
+    counters: &ScanCounters,
+    tx: &Sender<ScanStatus>
+) -> HashMap<String, FileEntry> {
+    if archive::is_archive(root) {
+        archive::scan_archive(root, excludes, &options.filter, options.check_content, cancel, counters, tx)
+    } else {
+        scan_folder(root, excludes, options.follow_symlinks, &options.filter, cancel, counters, tx)
+    }
+}
+
+/// Relative paths of every folder below `root`, honouring the excludes
+pub fn scan_dirs(root: &Path, excludes: &Gitignore, follow_symlinks: bool, filter: &FileFilter, cancel: &AtomicBool) -> Vec<String> {
+    WalkDir::new(root)
+        .follow_links(follow_symlinks)
+        .min_depth(1)
+        .max_depth(filter.max_depth.unwrap_or(usize::MAX))
+        .into_iter()
+        .filter_entry(|e| filter.allows_entry(e, excludes))
+        .take_while(|_| !cancel.load(Ordering::Relaxed))
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_dir())
+        .filter_map(|e| Some(normalize_rel_path(e.path().strip_prefix(root).ok()?)))
+        .collect()
+}
+
+/// Re-key a scanned tree by lowercased rel_path. Entries whose names collide
+/// once case is ignored keep their exact keys and are reported instead of merged.
+pub fn fold_case_keys(files: HashMap<String, FileEntry>, side: &str, collisions: &mut Vec<String>) -> HashMap<String, FileEntry> {
+    let mut groups: HashMap<String, Vec<FileEntry>> = HashMap::new();
+    for (rel_path, entry) in files {
+        groups.entry(rel_path.to_lowercase()).or_default().push(entry);
+    }
+
+    let mut folded = HashMap::with_capacity(groups.len());
+    for (key, mut entries) in groups {
+        if entries.len() == 1 {
+            folded.insert(key, entries.pop().unwrap());
+        } else {
+            entries.sort_by(|a, b| a.rel_path.cmp(&b.rel_path));
+            let names: Vec<_> = entries.iter().map(|e| e.rel_path.as_str()).collect();
+            collisions.push(format!("{}: {}", side, names.join(" ↔ ")));
+            for entry in entries {
+                folded.insert(entry.rel_path.clone(), entry);
+            }
+        }
+    }
+    folded
+}
+
+/// Refuse a source and destination that are the same folder or nested in
+/// one another: a mirror would delete from the tree it reads, a sync copy into itself.
+/// Paths that can't be resolved pass; opening them reports the problem. Only
+/// folders can nest, so an archive or manifest stored inside the other side is fine.
+pub fn check_distinct_roots(source: &Path, dest: &Path) -> Result<(), String> {
+    if !source.is_dir() || !dest.is_dir() {
+        return Ok(());
+    }
+    let (Ok(source), Ok(dest)) = (source.canonicalize(), dest.canonicalize()) else { return Ok(()) };
+    if source == dest {
+        Err(format!("Source and destination are the same folder: {}", source.display()))
+    } else if source.starts_with(&dest) {
+        Err(format!("The source folder is inside the destination: {}", source.display()))
+    } else if dest.starts_with(&source) {
+        Err(format!("The destination folder is inside the source: {}", dest.display()))
+    } else {
+        Ok(())
+    }
+}
+
+/// `found`, when given, receives each differing pair as soon as content
+/// verification finds it, ahead of the full result
 pub fn run_comparison(
     source: PathBuf,
     dest: PathBuf,
-    check_content: bool,
-    tx: Sender<ScanStatus>
+    options: &CompareOptions,
+    cancel: &AtomicBool,
+    tx: Sender<ScanStatus>,
+    found: Option<Sender<(FileEntry, FileEntry)>>
+) -> Result<CompareResult, String> {
+    check_distinct_roots(&source, &dest)?;
+    thread_pool(options.threads)?.install(|| compare(source, dest, options, cancel, tx, found))
+}
+
+/// A dedicated pool keeps the scan from saturating every core (or disk queue)
+pub fn thread_pool(threads: usize) -> Result<rayon::ThreadPool, String> {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+fn compare(
+    source: PathBuf,
+    dest: PathBuf,
+    options: &CompareOptions,
+    cancel: &AtomicBool,
+    tx: Sender<ScanStatus>,
+    found: Option<Sender<(FileEntry, FileEntry)>>
 ) -> Result<CompareResult, String> {
+    if manifest::is_manifest(&source) || manifest::is_manifest(&dest) {
+        return manifest::verify(&source, &dest, options, cancel, &tx);
+    }
+    let source_excludes = build_excludes(&source, &options.exclude_patterns)?;
+    let dest_excludes = build_excludes(&dest, &options.exclude_patterns)?;
+
     // 1. Parallel Scanning
     tx.send(ScanStatus::ScanningBoth).ok();
+    let counters = ScanCounters::with_limit(options.scan_limit);
     let (source_files, dest_files) = rayon::join(
-        || scan_folder(&source),
-        || scan_folder(&dest)
+        || scan_side(&source, &source_excludes, options, cancel, &counters, &tx),
+        || scan_side(&dest, &dest_excludes, options, cancel, &counters, &tx)
     );
+    tx.send(ScanStatus::Scanned(counters.scanned.load(Ordering::Relaxed))).ok();
 
-    // 2. Identify candidates for comparison
-    let mut missing_in_dest = Vec::new();
-    let mut missing_in_source = Vec::new();
-    let mut common_files = Vec::new();
+    if cancel.load(Ordering::Relaxed) {
+        tx.send(ScanStatus::Cancelled).ok();
+        return Err("Cancelled".into());
+    }
+
+    let mut result = classify(source_files, dest_files, options, cancel, &tx, found)?;
+    if options.track_dirs {
+        result.dirs_missing_in_dest = missing_dirs(&source, &dest, &source_excludes, &dest_excludes, options, cancel);
+    }
+    result.filtered_count = counters.filtered.load(Ordering::Relaxed);
+    result.scan_errors = std::mem::take(&mut *counters.errors.lock().unwrap());
+    result.scan_errors.sort();
+    let dirs = std::mem::take(&mut *counters.dirs.lock().unwrap());
+    result.dir_mtimes = [&source, &dest].map(|root| dirs.iter()
+        .filter_map(|(path, mtime)| Some((normalize_rel_path(path.strip_prefix(root).ok()?), *mtime)))
+        .collect());
+
+    tx.send(ScanStatus::Complete).ok();
+    Ok(result)
+}
+
+/// Two scanned trees sorted by which side each path exists on
+#[derive(Debug, Default)]
+pub struct Pairing {
+    pub missing_in_dest: Vec<FileEntry>,
+    pub missing_in_source: Vec<FileEntry>,
+    pub common: Vec<(FileEntry, FileEntry)>, // (source, destination) sharing a rel_path
+    pub case_collisions: Vec<String>,
+}
 
-    for (rel_path, src_entry) in &source_files {
-        if let Some(dest_entry) = dest_files.get(rel_path) {
-            common_files.push((src_entry, dest_entry));
+/// Match up two trees by rel_path (case-folded if asked). Pure: nothing is read from disk,
+/// and every list comes out sorted by rel_path.
+pub fn pair_trees(
+    mut source_files: HashMap<String, FileEntry>,
+    mut dest_files: HashMap<String, FileEntry>,
+    case_insensitive: bool
+) -> Pairing {
+    let mut pairing = Pairing::default();
+    if case_insensitive {
+        source_files = fold_case_keys(source_files, "Source", &mut pairing.case_collisions);
+        dest_files = fold_case_keys(dest_files, "Destination", &mut pairing.case_collisions);
+    }
+
+    for (rel_path, src_entry) in source_files {
+        match dest_files.remove(&rel_path) {
+            Some(dest_entry) => pairing.common.push((src_entry, dest_entry)),
+            None => pairing.missing_in_dest.push(src_entry),
+        }
+    }
+    pairing.missing_in_source.extend(dest_files.into_values());
+
+    pairing.missing_in_dest.sort_by(|a, b| a.rel_path.cmp(&b.rel_path));
+    pairing.missing_in_source.sort_by(|a, b| a.rel_path.cmp(&b.rel_path));
+    pairing.common.sort_by(|a, b| a.0.rel_path.cmp(&b.0.rel_path));
+    pairing
+}
+
+/// What a common pair's metadata says before any content is read
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Verdict {
+    Identical,
+    Different,
+    TimestampOnly,
+    NeedsHash, // Only the bytes can tell
+}
+
+/// Whether compare_link_targets applies to the pair and its link targets disagree
+fn link_targets_differ(src: &FileEntry, dest: &FileEntry, options: &CompareOptions) -> bool {
+    options.compare_link_targets
+        && (src.link_target.is_some() || dest.link_target.is_some())
+        && src.link_target != dest.link_target
+}
+
+/// Whether compare_permissions applies to the pair and its mode (or owner) disagrees.
+/// Entries without a recorded mode, like archive members, never differ.
+fn permissions_differ(src: &FileEntry, dest: &FileEntry, options: &CompareOptions) -> bool {
+    options.compare_permissions && src.mode.is_some() && dest.mode.is_some()
+        && (src.mode != dest.mode || (options.compare_owner && src.owner != dest.owner))
+}
+
+/// "755 1000:1000" style mode and owner, "—" when none was recorded
+pub fn permissions_text(entry: &FileEntry) -> String {
+    match (entry.mode, entry.owner) {
+        (Some(mode), Some((uid, gid))) => format!("{:o} {}:{}", mode, uid, gid),
+        (Some(mode), None) => format!("{:o}", mode),
+        _ => "—".to_owned(),
+    }
+}
+
+/// Link targets (if compared) and sizes decide, then mtimes within `mtime_tolerance`
+fn shallow_verdict(src: &FileEntry, dest: &FileEntry, options: &CompareOptions) -> Verdict {
+    if link_targets_differ(src, dest, options) || src.size != dest.size {
+        Verdict::Different
+    } else if src.modified.abs_diff(dest.modified) > options.mtime_tolerance {
+        Verdict::TimestampOnly
+    } else {
+        Verdict::Identical
+    }
+}
+
+/// The verdict for a common pair that needs no IO, or NeedsHash
+pub fn metadata_verdict(src: &FileEntry, dest: &FileEntry, options: &CompareOptions) -> Verdict {
+    if !options.check_content {
+        return shallow_verdict(src, dest, options);
+    }
+    // Unfollowed links are compared by their own metadata; hashing would read the target
+    if src.link_target.is_some() || dest.link_target.is_some() {
+        if src.size != dest.size || src.link_target.is_some() != dest.link_target.is_some() || link_targets_differ(src, dest, options) {
+            Verdict::Different
         } else {
-            missing_in_dest.push(src_entry.clone());
+            Verdict::Identical
         }
+    } else if options.hash_mode != HashMode::AlwaysFull && src.size != dest.size && !options.ignore_line_endings {
+        Verdict::Different
+    } else {
+        Verdict::NeedsHash
     }
+}
 
-    for (rel_path, dest_entry) in &dest_files {
-        if !source_files.contains_key(rel_path) {
-            missing_in_source.push(dest_entry.clone());
+/// Assemble a CompareResult: every common file not listed as different,
+/// timestamp-only or unreadable counts as identical, unless its permissions differ
+pub fn build_result(
+    pairing: Pairing,
+    different_content: Vec<(FileEntry, FileEntry)>,
+    timestamp_only: Vec<(FileEntry, FileEntry)>,
+    hash_errors: Vec<(FileEntry, FileEntry, String)>,
+    renamed: Vec<(FileEntry, FileEntry)>,
+    options: &CompareOptions
+) -> CompareResult {
+    let differing: HashSet<&str> = different_content.iter().chain(&timestamp_only)
+        .map(|(src, _)| src.rel_path.as_str())
+        .chain(hash_errors.iter().map(|(src, _, _)| src.rel_path.as_str()))
+        .collect();
+    let (mut identical, mut permissions) = (Vec::new(), Vec::new());
+    for (src, dest) in pairing.common.iter().filter(|(src, _)| !differing.contains(src.rel_path.as_str())) {
+        if permissions_differ(src, dest, options) {
+            debug!("{}: permissions differ ({:?} {:?} / {:?} {:?})", src.rel_path, src.mode, src.owner, dest.mode, dest.owner);
+            permissions.push((src.clone(), dest.clone()));
+        } else {
+            identical.push(src.clone());
         }
     }
 
+    CompareResult {
+        missing_in_dest: pairing.missing_in_dest,
+        missing_in_source: pairing.missing_in_source,
+        identical_count: identical.len(),
+        identical,
+        different_content,
+        timestamp_only,
+        case_collisions: pairing.case_collisions,
+        hash_errors,
+        renamed,
+        permissions_differ: permissions,
+        metadata_only: !options.check_content,
+        ..Default::default()
+    }
+}
+
+/// A whole metadata-only comparison of two scanned trees, without touching the
+/// filesystem or any channel. `options.check_content` is ignored.
+pub fn compare_metadata(
+    source_files: HashMap<String, FileEntry>,
+    dest_files: HashMap<String, FileEntry>,
+    options: &CompareOptions
+) -> CompareResult {
+    let pairing = pair_trees(source_files, dest_files, options.case_insensitive);
+    let (mut different_content, mut timestamp_only) = (Vec::new(), Vec::new());
+    for (src, dest) in &pairing.common {
+        let verdict = shallow_verdict(src, dest, options);
+        debug!("{}: {:?} (size {} / {}, mtime {} / {})", src.rel_path, verdict, src.size, dest.size, src.modified, dest.modified);
+        match verdict {
+            Verdict::Different => different_content.push((src.clone(), dest.clone())),
+            Verdict::TimestampOnly => timestamp_only.push((src.clone(), dest.clone())),
+            _ => {}
+        }
+    }
+    let mut result = build_result(pairing, different_content, timestamp_only, Vec::new(), Vec::new(), options);
+    result.metadata_only = true;
+    result
+}
+
+/// Steps 2+ of a comparison: pair up two scanned trees and verify the common files
+fn classify(
+    source_files: HashMap<String, FileEntry>,
+    dest_files: HashMap<String, FileEntry>,
+    options: &CompareOptions,
+    cancel: &AtomicBool,
+    tx: &Sender<ScanStatus>,
+    found: Option<Sender<(FileEntry, FileEntry)>>
+) -> Result<CompareResult, String> {
+    // Metadata only: sizes and mtimes decide, no file is ever opened
+    if !options.check_content {
+        return Ok(compare_metadata(source_files, dest_files, options));
+    }
+
+    // 2. Identify candidates for comparison
+    let mut pairing = pair_trees(source_files, dest_files, options.case_insensitive);
     let mut different_content = Vec::new();
+    let mut hash_errors = Vec::new();
+    let mut renamed = Vec::new();
+
+    let always_full = options.hash_mode == HashMode::AlwaysFull;
+    let report = |pair: &(FileEntry, FileEntry)| {
+        if let Some(found) = &found {
+            found.send(pair.clone()).ok();
+        }
+    };
+    let mut same_size_candidates = Vec::new();
+    for (src, dest) in &pairing.common {
+        let verdict = metadata_verdict(src, dest, options);
+        debug!("{}: {:?} by metadata (size {} / {})", src.rel_path, verdict, src.size, dest.size);
+        match verdict {
+            Verdict::NeedsHash => same_size_candidates.push((src, dest)),
+            Verdict::Different => {
+                different_content.push((src.clone(), dest.clone()));
+                report(different_content.last().unwrap());
+            }
+            _ => {}
+        }
+    }
+
+    let total_hash = same_size_candidates.len();
+    let total_bytes: u64 = same_size_candidates.iter().map(|(src, dest)| src.size + dest.size).sum();
+    let bytes_done = AtomicU64::new(0);
+    let cache = options.hash_cache.as_deref().map(HashCache::load);
+    // Archive members were hashed while they were listed; anything else reuses
+    // the handle the head/tail stage opened, so each file is opened once
+    let full_hash = |entry: &FileEntry, opened: Option<File>| {
+        let progress = |done, size| {
+            tx.send(ScanStatus::FileProgress(entry.rel_path.clone(), done, size)).ok();
+        };
+        let compute = || {
+            let mut file = match opened {
+                Some(file) => file,
+                None => File::open(long_path(&entry.path))?,
+            };
+            full_hash_file(&mut file, Some(&progress))
+        };
+        match (&entry.hash, &cache) {
+            (Some(hash), _) => Ok(hash.clone()),
+            (None, Some(cache)) => cache.hash_with(entry, compute),
+            (None, None) => compute(),
+        }
+    };
+    let counter = Arc::new(AtomicUsize::new(0));
+    let progress = ProgressGate::new();
+
+    // Only consulted once the bytes are known to differ
+    let same_text = |src: &FileEntry, dest: &FileEntry| {
+        options.ignore_line_endings && same_text_ignoring_eol(&src.path, &dest.path)
+    };
+
+    // Some(pair) when the contents differ; the error names the side that failed
+    let hash_pair = |src: &FileEntry, dest: &FileEntry| -> Result<Option<(FileEntry, FileEntry)>, String> {
+        let side = |label: &'static str| move |e: std::io::Error| format!("{}: {}", label, e);
+
+        // Stage 1: Head/Tail Short-circuit
+        let (mut src_file, mut dest_file) = (None, None);
+        if !always_full && src.hash.is_none() && dest.hash.is_none() {
+            let mut src_open = File::open(long_path(&src.path)).map_err(side("Source"))?;
+            let mut dest_open = File::open(long_path(&dest.path)).map_err(side("Destination"))?;
+            let src_partial = partial_hash_file(&mut src_open, options.partial_chunk).map_err(side("Source"))?;
+            let dest_partial = partial_hash_file(&mut dest_open, options.partial_chunk).map_err(side("Destination"))?;
+
+            if src_partial != dest_partial {
+                let differs = !same_text(src, dest);
+                debug!("{}: head/tail hashes differ{}", src.rel_path, if differs { "" } else { ", same text apart from line endings" });
+                return Ok(differs.then(|| (src.clone(), dest.clone())));
+            }
+            (src_file, dest_file) = (Some(src_open), Some(dest_open));
+        }
+
+        // Stage 2: Full content verify if partial match
+        let src_hash = full_hash(src, src_file.take()).map_err(side("Source"))?;
+        let dest_hash = full_hash(dest, dest_file.take()).map_err(side("Destination"))?;
+
+        debug!("{}: full hashes {} / {}", src.rel_path, src_hash, dest_hash);
+        if src_hash != dest_hash && !same_text(src, dest) {
+            let mut src_clone = src.clone();
+            src_clone.hash = Some(src_hash);
+            let mut dest_clone = dest.clone();
+            dest_clone.hash = Some(dest_hash);
+            Ok(Some((src_clone, dest_clone)))
+        } else {
+            Ok(None)
+        }
+    };
+    
+    let hashed: Vec<_> = same_size_candidates.into_par_iter()
+        .filter_map(|(src, dest)| {
+            if cancel.load(Ordering::Relaxed) {
+                return None;
+            }
 
-    if check_content {
-        let same_size_candidates: Vec<_> = common_files.into_iter()
-            .filter(|(src, dest)| {
-                if src.size != dest.size {
-                    different_content.push(((*src).clone(), (*dest).clone()));
-                    false
-                } else {
-                    true
+            let outcome = match hash_pair(src, dest) {
+                Ok(diff) => diff.inspect(report).map(Ok),
+                Err(msg) => {
+                    warn!("{}: could not hash: {}", src.rel_path, msg);
+                    Some(Err((src.clone(), dest.clone(), msg)))
                 }
-            })
-            .collect();
+            };
+
+            // Counted once hashed, so a huge file holds the bar back while it's read
+            let c = counter.fetch_add(1, Ordering::Relaxed) + 1;
+            let done = bytes_done.fetch_add(src.size + dest.size, Ordering::Relaxed) + src.size + dest.size;
+            if progress.ready(c == total_hash) {
+                tx.send(ScanStatus::Bytes(done, total_bytes)).ok();
+                tx.send(ScanStatus::Hashing(c, total_hash)).ok();
+            }
+            outcome
+        })
+        .collect();
 
-        let total_hash = same_size_candidates.len();
-        let counter = Arc::new(AtomicUsize::new(0));
+    // Files that were only moved would otherwise count as missing plus extra
+    if options.detect_renames && !cancel.load(Ordering::Relaxed) {
+        renamed = match_renames(&mut pairing.missing_in_dest, &mut pairing.missing_in_source, &|entry: &FileEntry| full_hash(entry, None), cancel, tx);
+    }
+
+    // Hashes computed before a cancel are still valid, so keep them
+    if let (Some(cache), Some(path)) = (&cache, &options.hash_cache) {
+        cache.save(path).ok();
+    }
         
-        let hashed_diffs: Vec<_> = same_size_candidates.into_par_iter()
-            .filter_map(|(src, dest)| {
-                let c = counter.fetch_add(1, Ordering::Relaxed) + 1;
-                if c % 50 == 0 || c == total_hash {
-                    tx.send(ScanStatus::Hashing(c, total_hash)).ok();
-                }
+    if cancel.load(Ordering::Relaxed) {
+        tx.send(ScanStatus::Cancelled).ok();
+        return Err("Cancelled".into());
+    }
+
+    for outcome in hashed {
+        match outcome {
+            Ok(pair) => different_content.push(pair),
+            Err(error) => hash_errors.push(error),
+        }
+    }
+    hash_errors.sort_by(|a, b| a.0.rel_path.cmp(&b.0.rel_path));
+
+    Ok(build_result(pairing, different_content, Vec::new(), hash_errors, renamed, options))
+}
+
+/// Re-compare only the folders whose mtime changed since `previous` was
+/// produced, keeping its verdicts for everything else. A folder's mtime moves
+/// when entries are added, removed or renamed, not when a file is rewritten
+/// in place, so such edits need a full comparison.
+pub fn run_refresh(
+    source: PathBuf,
+    dest: PathBuf,
+    previous: &CompareResult,
+    options: &CompareOptions,
+    cancel: &AtomicBool,
+    tx: Sender<ScanStatus>
+) -> Result<CompareResult, String> {
+    if [&source, &dest].iter().any(|p| archive::is_archive(p) || manifest::is_manifest(p)) {
+        return Err("Archives and manifests have no folder times to refresh from; run a full comparison".into());
+    }
+    thread_pool(options.threads)?.install(|| refresh(source, dest, previous, options, cancel, tx))
+}
+
+fn refresh(
+    source: PathBuf,
+    dest: PathBuf,
+    previous: &CompareResult,
+    options: &CompareOptions,
+    cancel: &AtomicBool,
+    tx: Sender<ScanStatus>
+) -> Result<CompareResult, String> {
+    let source_excludes = build_excludes(&source, &options.exclude_patterns)?;
+    let dest_excludes = build_excludes(&dest, &options.exclude_patterns)?;
+
+    tx.send(ScanStatus::ScanningBoth).ok();
+    let (source_dirs, dest_dirs) = rayon::join(
+        || dir_mtimes(&source, &source_excludes, options, cancel),
+        || dir_mtimes(&dest, &dest_excludes, options, cancel)
+    );
+
+    // A folder changed on either side is re-listed on both, so pairs stay together
+    let key = |dir: &str| if options.case_insensitive { dir.to_lowercase() } else { dir.to_owned() };
+    let mut dirty = HashSet::new();
+    for (now, before) in [(&source_dirs, &previous.dir_mtimes[0]), (&dest_dirs, &previous.dir_mtimes[1])] {
+        dirty.extend(now.iter().filter(|(dir, mtime)| before.get(*dir) != Some(mtime)).map(|(dir, _)| key(dir)));
+        dirty.extend(before.keys().filter(|dir| !now.contains_key(*dir)).map(|dir| key(dir)));
+    }
+    let clean = |rel_path: &str| !dirty.contains(&key(parent_dir(rel_path)));
+
+    // Only the files directly inside changed folders are listed again
+    let counters = ScanCounters::with_limit(options.scan_limit);
+    let filter = FileFilter { max_depth: Some(1), ..options.filter.clone() };
+    let rescan = |root: &Path, excludes: &Gitignore, dirs: &HashMap<String, u64>| -> HashMap<String, FileEntry> {
+        dirs.keys().filter(|dir| dirty.contains(&key(dir))).collect::<Vec<_>>().into_par_iter()
+            .flat_map_iter(|dir| scan_folder(&root.join(dir), excludes, options.follow_symlinks, &filter, cancel, &counters, &tx).into_values())
+            .map(|mut entry| {
+                entry.rel_path = normalize_rel_path(entry.path.strip_prefix(root).unwrap_or(&entry.path));
+                (entry.rel_path.clone(), entry)
+            })
+            .collect()
+    };
+    let (mut source_files, mut dest_files) = rayon::join(
+        || rescan(&source, &source_excludes, &source_dirs),
+        || rescan(&dest, &dest_excludes, &dest_dirs)
+    );
+    tx.send(ScanStatus::Scanned(counters.scanned.load(Ordering::Relaxed))).ok();
 
-                // Stage 1: Head/Tail Short-circuit
-                let src_partial = calculate_partial_hash(&src.path)?;
-                let dest_partial = calculate_partial_hash(&dest.path)?;
-                
-                if src_partial != dest_partial {
-                    return Some((src.clone(), dest.clone()));
+    if cancel.load(Ordering::Relaxed) {
+        tx.send(ScanStatus::Cancelled).ok();
+        return Err("Cancelled".into());
+    }
+
+    // Unpaired files from unchanged folders go back in, so they can pair with new ones
+    for entry in previous.missing_in_dest.iter().filter(|f| clean(&f.rel_path)) {
+        source_files.insert(entry.rel_path.clone(), entry.clone());
+    }
+    for entry in previous.missing_in_source.iter().filter(|f| clean(&f.rel_path)) {
+        dest_files.insert(entry.rel_path.clone(), entry.clone());
+    }
+    let mut renamed = Vec::new();
+    for (src, dest) in &previous.renamed {
+        match (clean(&src.rel_path), clean(&dest.rel_path)) {
+            (true, true) => renamed.push((src.clone(), dest.clone())),
+            (src_clean, dest_clean) => {
+                if src_clean {
+                    source_files.insert(src.rel_path.clone(), src.clone());
                 }
+                if dest_clean {
+                    dest_files.insert(dest.rel_path.clone(), dest.clone());
+                }
+            }
+        }
+    }
+
+    let mut result = classify(source_files, dest_files, options, cancel, &tx, None)?;
+    let kept = |pairs: &[(FileEntry, FileEntry)]| pairs.iter().filter(|(src, _)| clean(&src.rel_path)).cloned().collect::<Vec<_>>();
+    result.different_content.extend(kept(&previous.different_content));
+    result.timestamp_only.extend(kept(&previous.timestamp_only));
+    result.permissions_differ.extend(kept(&previous.permissions_differ));
+    result.identical.extend(previous.identical.iter().filter(|f| clean(&f.rel_path)).cloned());
+    result.identical_count = result.identical.len();
+    result.hash_errors.extend(previous.hash_errors.iter().filter(|(src, _, _)| clean(&src.rel_path)).cloned());
+    result.hash_errors.sort_by(|a, b| a.0.rel_path.cmp(&b.0.rel_path));
+    result.renamed.extend(renamed);
+    result.renamed.sort_by(|a, b| a.0.rel_path.cmp(&b.0.rel_path));
+    result.case_collisions.extend(previous.case_collisions.iter().cloned());
+    result.case_collisions.sort();
+    result.case_collisions.dedup();
+    // Not tracked per folder, so the full scan's count carries over
+    result.filtered_count = previous.filtered_count;
+
+    // Keep old errors unless re-listing their folder had the chance to report them again
+    let reproduced = |path: &Path| [&source, &dest].iter().any(|root| {
+        path.strip_prefix(root).is_ok_and(|rel| {
+            let rel = normalize_rel_path(rel);
+            dirty.contains(&key(if path.is_dir() { &rel } else { parent_dir(&rel) }))
+        })
+    });
+    result.scan_errors = previous.scan_errors.iter().filter(|(path, _)| !reproduced(path)).cloned().collect();
+    result.scan_errors.append(&mut counters.errors.lock().unwrap());
+    result.scan_errors.sort();
+    if options.track_dirs {
+        result.dirs_missing_in_dest = missing_dirs(&source, &dest, &source_excludes, &dest_excludes, options, cancel);
+    }
+    result.dir_mtimes = [source_dirs, dest_dirs];
+
+    tx.send(ScanStatus::Complete).ok();
+    Ok(result)
+}
 
-                // Stage 2: Full content verify if partial match
-                let src_hash = calculate_hash(&src.path)?;
-                let dest_hash = calculate_hash(&dest.path)?;
-
-                if src_hash != dest_hash {
-                    let mut src_clone = src.clone();
-                    src_clone.hash = Some(src_hash);
-                    let mut dest_clone = dest.clone();
-                    dest_clone.hash = Some(dest_hash);
-                    Some((src_clone, dest_clone))
-                } else {
-                    None
+/// Mtimes of the folders below `root` (and of `root` itself, as "") whose
+/// files a scan with these options would list
+fn dir_mtimes(root: &Path, excludes: &Gitignore, options: &CompareOptions, cancel: &AtomicBool) -> HashMap<String, u64> {
+    WalkDir::new(root)
+        .follow_links(options.follow_symlinks)
+        .max_depth(dir_depth_limit(&options.filter))
+        .into_iter()
+        .filter_entry(|e| options.filter.allows_entry(e, excludes))
+        .take_while(|_| !cancel.load(Ordering::Relaxed))
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_dir())
+        .filter_map(|e| Some((normalize_rel_path(e.path().strip_prefix(root).ok()?), dir_mtime(&e)?)))
+        .collect()
+}
+
+/// Folders in the source with no counterpart in the destination.
+/// Folders are only walked on request; files already imply their parents.
+fn missing_dirs(
+    source: &Path,
+    dest: &Path,
+    source_excludes: &Gitignore,
+    dest_excludes: &Gitignore,
+    options: &CompareOptions,
+    cancel: &AtomicBool
+) -> Vec<String> {
+    let (source_dirs, dest_dirs) = rayon::join(
+        || scan_dirs(source, source_excludes, options.follow_symlinks, &options.filter, cancel),
+        || scan_dirs(dest, dest_excludes, options.follow_symlinks, &options.filter, cancel)
+    );
+    let key = |dir: &String| if options.case_insensitive { dir.to_lowercase() } else { dir.clone() };
+    let dest_dirs: HashSet<String> = dest_dirs.iter().map(key).collect();
+    let mut missing: Vec<String> = source_dirs.into_iter().filter(|dir| !dest_dirs.contains(&key(dir))).collect();
+    missing.sort();
+    missing
+}
+
+/// Pair files missing in dest with byte-identical extra files, taking both
+/// out of the missing lists. Only sizes present on both sides are hashed.
+fn match_renames<H>(
+    missing_in_dest: &mut Vec<FileEntry>,
+    missing_in_source: &mut Vec<FileEntry>,
+    hash: &H,
+    cancel: &AtomicBool,
+    tx: &Sender<ScanStatus>
+) -> Vec<(FileEntry, FileEntry)>
+where
+    H: Fn(&FileEntry) -> std::io::Result<String> + Sync,
+{
+    // Empty files all match each other, so pairing them says nothing
+    let hashable = |e: &FileEntry| e.size > 0 && e.link_target.is_none();
+    let sizes = |files: &[FileEntry]| -> HashSet<u64> { files.iter().filter(|e| hashable(e)).map(|e| e.size).collect() };
+    let (src_sizes, dest_sizes) = (sizes(missing_in_dest), sizes(missing_in_source));
+    let wanted = |files: &[FileEntry], other: &HashSet<u64>| -> Vec<u64> {
+        files.iter().filter(|e| hashable(e) && other.contains(&e.size)).map(|e| e.size).collect()
+    };
+    let wanted: Vec<u64> = wanted(missing_in_dest, &dest_sizes).into_iter().chain(wanted(missing_in_source, &src_sizes)).collect();
+    let (total, total_bytes) = (wanted.len(), wanted.iter().sum::<u64>());
+
+    let counter = AtomicUsize::new(0);
+    let bytes_done = AtomicU64::new(0);
+    let progress = ProgressGate::new();
+    let hash_all = |files: &[FileEntry], other: &HashSet<u64>| -> Vec<Option<String>> {
+        files.par_iter()
+            .map(|e| {
+                if !hashable(e) || !other.contains(&e.size) || cancel.load(Ordering::Relaxed) {
+                    return None;
+                }
+                let hash = hash(e).ok();
+                let c = counter.fetch_add(1, Ordering::Relaxed) + 1;
+                let done = bytes_done.fetch_add(e.size, Ordering::Relaxed) + e.size;
+                if progress.ready(c == total) {
+                    tx.send(ScanStatus::Bytes(done, total_bytes)).ok();
+                    tx.send(ScanStatus::Hashing(c, total)).ok();
                 }
+                hash
             })
-            .collect();
-            
-        different_content.extend(hashed_diffs);
-    } else {
-        // Shallow comparison
-        for (src, dest) in common_files {
-            if src.size != dest.size || src.modified != dest.modified {
-                 different_content.push((src.clone(), dest.clone()));
+            .collect()
+    };
+    let src_hashes = hash_all(missing_in_dest, &dest_sizes);
+    let dest_hashes = hash_all(missing_in_source, &src_sizes);
+
+    let by_path = |files: &[FileEntry]| {
+        let mut order: Vec<usize> = (0..files.len()).collect();
+        order.sort_by(|&a, &b| files[a].rel_path.cmp(&files[b].rel_path));
+        order
+    };
+    // Buckets hold dest indices in reverse path order, so pop() takes the first
+    let mut by_content: HashMap<(u64, &str), Vec<usize>> = HashMap::new();
+    for j in by_path(missing_in_source).into_iter().rev() {
+        if let Some(h) = &dest_hashes[j] {
+            by_content.entry((missing_in_source[j].size, h.as_str())).or_default().push(j);
+        }
+    }
+    let pairs: Vec<(usize, usize)> = by_path(missing_in_dest).into_iter()
+        .filter_map(|i| {
+            let h = src_hashes[i].as_deref()?;
+            Some((i, by_content.get_mut(&(missing_in_dest[i].size, h))?.pop()?))
+        })
+        .collect();
+
+    let renamed = pairs.iter()
+        .map(|&(i, j)| {
+            let mut src = missing_in_dest[i].clone();
+            src.hash = src_hashes[i].clone();
+            let mut dest = missing_in_source[j].clone();
+            dest.hash = dest_hashes[j].clone();
+            (src, dest)
+        })
+        .collect();
+
+    let (src_taken, dest_taken): (HashSet<usize>, HashSet<usize>) = pairs.into_iter().unzip();
+    let mut i = 0;
+    missing_in_dest.retain(|_| { i += 1; !src_taken.contains(&(i - 1)) });
+    let mut j = 0;
+    missing_in_source.retain(|_| { j += 1; !dest_taken.contains(&(j - 1)) });
+    renamed
+}
+
+/// Extra copy attempts when a verified copy comes out different
+const VERIFY_RETRIES: usize = 1;
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(200);
+const PAUSE_POLL: Duration = Duration::from_millis(100);
+const THROTTLE_CHUNK: usize = 256 * 1024;
+
+/// Token bucket shared by all copy workers so their combined rate stays
+/// under the limit. Workers take tokens up front and sleep off any debt.
+struct Throttle {
+    bytes_per_sec: f64,
+    state: Mutex<(f64, Instant)>, // (available tokens, last refill)
+}
+
+impl Throttle {
+    fn new(bytes_per_sec: u64) -> Self {
+        Self { bytes_per_sec: bytes_per_sec as f64, state: Mutex::new((0.0, Instant::now())) }
+    }
+
+    fn take(&self, bytes: usize) {
+        let wait = {
+            let mut state = self.state.lock().unwrap();
+            let now = Instant::now();
+            // Idle time only banks up to one second's worth, to bound bursts
+            let (tokens, last) = *state;
+            let refilled = (tokens + now.duration_since(last).as_secs_f64() * self.bytes_per_sec).min(self.bytes_per_sec);
+            *state = (refilled - bytes as f64, now);
+            (bytes as f64 - refilled).max(0.0) / self.bytes_per_sec
+        };
+        if wait > 0.0 {
+            std::thread::sleep(Duration::from_secs_f64(wait));
+        }
+    }
+}
+
+/// Chunked stand-in for `fs::copy` that paces itself through `throttle`
+fn throttled_copy(from: &Path, to: &Path, throttle: &Throttle) -> std::io::Result<u64> {
+    use std::io::Write;
+    let mut reader = File::open(from)?;
+    let mut writer = File::create(to)?;
+    let mut buffer = vec![0; THROTTLE_CHUNK];
+    let mut copied = 0;
+    loop {
+        let n = reader.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        throttle.take(n);
+        writer.write_all(&buffer[..n])?;
+        copied += n as u64;
+    }
+    // fs::copy carries permissions over, keep doing so
+    std::fs::set_permissions(to, reader.metadata()?.permissions())?;
+    Ok(copied)
+}
+
+/// Park the calling worker while `paused` is set; a cancel releases it
+fn wait_while_paused(paused: &AtomicBool, cancel: &AtomicBool) {
+    while paused.load(Ordering::Relaxed) && !cancel.load(Ordering::Relaxed) {
+        std::thread::sleep(PAUSE_POLL);
+    }
+}
+
+/// Errors worth another attempt, e.g. a network share hiccup.
+/// Anything else (permission denied, missing file, full disk) fails at once.
+fn is_transient(err: &std::io::Error) -> bool {
+    use std::io::ErrorKind::*;
+    matches!(err.kind(), Interrupted | WouldBlock | TimedOut | ConnectionReset | ConnectionAborted | BrokenPipe | UnexpectedEof)
+}
+
+/// `fs::copy` retried on transient errors, doubling the delay each time
+fn copy_with_retry(from: &Path, to: &Path, retries: u32, throttle: Option<&Throttle>) -> std::io::Result<u64> {
+    let mut attempt = 0;
+    loop {
+        let res = match throttle {
+            Some(throttle) => throttled_copy(from, to, throttle),
+            None => std::fs::copy(from, to),
+        };
+        match res {
+            Err(e) if attempt < retries && is_transient(&e) => {
+                std::thread::sleep(RETRY_BASE_DELAY * 2u32.pow(attempt));
+                attempt += 1;
             }
+            res => return res,
         }
     }
-    
-    tx.send(ScanStatus::Complete).ok();
+}
 
-    Ok(CompareResult {
-        missing_in_dest,
-        missing_in_source,
-        different_content,
-    })
+/// Copy one file, creating parent folders and applying the copy options
+fn copy_file(from: &Path, to: &Path, options: &SyncOptions, throttle: Option<&Throttle>) -> Result<(), String> {
+    let (from, to) = (&long_path(from), &long_path(to));
+    // Ensure parent directory exists
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Could not create folder {}: {}", parent.display(), e))?;
+    }
+
+    copy_with_retry(from, to, options.retries, throttle).map_err(|e| format!("Copy failed: {}", e))?;
+
+    if options.verify {
+        let source_hash = calculate_hash(from)
+            .map_err(|e| format!("Copied, but could not hash the source to verify it: {}", e))?;
+        let mut attempts = 0;
+        while calculate_hash(to).ok().as_ref() != Some(&source_hash) {
+            if attempts == VERIFY_RETRIES {
+                return Err("Verification failed: the copy does not match the source".into());
+            }
+            attempts += 1;
+            copy_with_retry(from, to, options.retries, throttle).map_err(|e| format!("Copy failed on verification retry: {}", e))?;
+        }
+    }
+
+    // std::fs::copy normally carries the mode along; setting it explicitly also reports when that fails
+    if options.apply_permissions {
+        copy_mode(from, to).map_err(|e| format!("Copied, but {}", e))?;
+    }
+
+    if options.preserve_timestamps {
+        let metadata = std::fs::metadata(from)
+            .map_err(|e| format!("Copied, but could not read source timestamps: {}", e))?;
+        let atime = filetime::FileTime::from_last_access_time(&metadata);
+        let mtime = filetime::FileTime::from_last_modification_time(&metadata);
+        filetime::set_file_times(to, atime, mtime)
+            .map_err(|e| format!("Copied, but could not preserve timestamps: {}", e))?;
+    }
+
+    Ok(())
+}
+
+/// Name prefix of the folders staged syncs write into
+pub const STAGING_PREFIX: &str = "__omnidiff_staging_";
+/// Name prefix of the folders holding the previous versions of overwritten files
+pub const BACKUP_PREFIX: &str = "__omnidiff_backup_";
+
+/// Move `path` to `backup`, copying when a rename can't cross devices
+fn backup_file(path: &Path, backup: &Path) -> Result<(), String> {
+    let (path, backup) = (&long_path(path), &long_path(backup));
+    let res = backup.parent().map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| std::fs::rename(path, backup).or_else(|_| std::fs::copy(path, backup).map(|_| ())));
+    res.map_err(|e| format!("Backup failed, file left unchanged: {}", e))
 }
 
+/// Put the backed-up file back after its replacement could not be copied, so
+/// the destination is not left without it. A copy that landed but failed a later
+/// step ("Copied, but ...") stays, and the error names where the old version is.
+fn restore_backup(backup: &Path, to: &Path, err: String) -> String {
+    if err.starts_with("Copied, but") {
+        return format!("{} (previous version kept at {})", err, backup.display());
+    }
+    match std::fs::rename(long_path(backup), long_path(to)) {
+        Ok(()) => format!("{} (previous version restored)", err),
+        Err(e) => format!("{} (could not restore the previous version from {}: {})", err, backup.display(), e),
+    }
+}
+
+/// Apply the comparison in the chosen direction, returning every file operation that failed.
+/// While `paused` is set no new operation starts; ones already underway finish.
 pub fn run_sync(
-    _source_root: PathBuf,
+    source_root: PathBuf,
     dest_root: PathBuf,
     results: &CompareResult,
-    delete_extra: bool,
+    options: &SyncOptions,
+    cancel: &AtomicBool,
+    paused: &AtomicBool,
     tx: Sender<ScanStatus>
-) -> Result<(), String> {
-    let mut tasks = Vec::new();
+) -> Result<Vec<(PathBuf, String)>, String> {
+    use SyncDirection::*;
+
+    if [&source_root, &dest_root].iter().any(|p| archive::is_archive(p) || manifest::is_manifest(p)) {
+        return Err("Archives and manifests can only be compared, not synced".into());
+    }
+    check_distinct_roots(&source_root, &dest_root)?;
+
+    let mut tasks = Vec::new(); // (from, to, size, backup of the file being replaced, rel folder)
+    let mut errors = Vec::new();
+    let plan = |action: SyncAction, direction: SyncDirection, rel_path: &str| {
+        if options.dry_run {
+            tx.send(ScanStatus::Planned(action, direction, rel_path.to_owned())).ok();
+        }
+    };
+    let to_dest = matches!(options.direction, SourceToDest | Bidirectional);
+    let to_source = matches!(options.direction, DestToSource | Bidirectional);
+
+    // Staged copies and backups keep their relative paths below a timestamped folder of the receiving root
+    let stamp = chrono::Local::now().format("%Y-%m-%d_%H%M%S").to_string();
+    let [dest_target, source_target] = [&dest_root, &source_root]
+        .map(|root| if options.stage { root.join(format!("{}{}", STAGING_PREFIX, stamp)) } else { root.clone() });
+    let backup_dir = format!("{}{}", BACKUP_PREFIX, stamp);
 
-    // 1. Prepare Copy Tasks (Missing in Dest)
-    for entry in &results.missing_in_dest {
-        let dest_path = dest_root.join(&entry.rel_path);
-        tasks.push((entry.path.clone(), dest_path, true)); // (from, to, is_copy)
+    // 0. Folders missing in dest; created up front since they are cheap
+    let mut dir_tasks = Vec::new();
+    if to_dest {
+        for rel_dir in &results.dirs_missing_in_dest {
+            plan(SyncAction::Copy, SourceToDest, &format!("{}/", rel_dir));
+            dir_tasks.push(dest_target.join(rel_dir));
+        }
+    }
+
+    // 1. Prepare Copy Tasks (Missing on the receiving side)
+    // Renamed files are synced like the missing/extra pair they were detected from
+    let missing_in_dest: Vec<&FileEntry> = results.missing_in_dest.iter()
+        .chain(results.renamed.iter().map(|(src, _)| src))
+        .collect();
+    let missing_in_source: Vec<&FileEntry> = results.missing_in_source.iter()
+        .chain(results.renamed.iter().map(|(_, dest)| dest))
+        .collect();
+    if to_dest {
+        for entry in &missing_in_dest {
+            plan(SyncAction::Copy, SourceToDest, &entry.rel_path);
+            tasks.push((entry.path.clone(), dest_target.join(&entry.rel_path), entry.size, None, parent_dir(&entry.rel_path)));
+        }
+    }
+    if to_source {
+        for entry in &missing_in_source {
+            plan(SyncAction::Copy, DestToSource, &entry.rel_path);
+            tasks.push((entry.path.clone(), source_target.join(&entry.rel_path), entry.size, None, parent_dir(&entry.rel_path)));
+        }
     }
 
     // 2. Prepare Update Tasks (Different Content)
-    for (src, _dest) in &results.different_content {
-        let dest_path = dest_root.join(&src.rel_path);
-        tasks.push((src.path.clone(), dest_path, true));
+    // Overwrite the existing file in place, its name may differ by case
+    for (src, dest) in results.different_content.iter().chain(&results.timestamp_only) {
+        let direction = match options.direction {
+            Bidirectional if src.modified > dest.modified => SourceToDest,
+            Bidirectional if src.modified < dest.modified => DestToSource,
+            Bidirectional => {
+                let msg = "Conflict: both sides have the same modification time, skipped".to_owned();
+                tx.send(ScanStatus::SyncError(dest.path.clone(), msg.clone())).ok();
+                errors.push((dest.path.clone(), msg));
+                continue;
+            }
+            direction => direction,
+        };
+        let (sending, receiving, receiving_root, receiving_target) = match direction {
+            DestToSource => (dest, src, &source_root, &source_target),
+            _ => (src, dest, &dest_root, &dest_target),
+        };
+        if options.only_overwrite_older && sending.modified <= receiving.modified {
+            plan(SyncAction::Skip, direction, &src.rel_path);
+            continue;
+        }
+        plan(SyncAction::Update, direction, &src.rel_path);
+        let to = if options.stage { receiving_target.join(&receiving.rel_path) } else { receiving.path.clone() };
+        // Staging never touches the live copy, so there's nothing to back up
+        let backup = (options.backup && !options.stage).then(|| receiving_root.join(&backup_dir).join(&receiving.rel_path));
+        tasks.push((sending.path.clone(), to, sending.size, backup, parent_dir(&receiving.rel_path)));
     }
 
-    // 3. Prepare Delete Tasks (Extra in Dest - Optional)
+    // 2b. Prepare Permission Fixes (same content, different mode or owner)
+    // Nothing is copied, so staging has nothing to stage; links would pass the change to their target
+    let mut permission_tasks = Vec::new(); // (path, mode, owner to set, rel folder)
+    if options.fix_permissions && !options.stage {
+        for (src, dest) in results.permissions_differ.iter().filter(|(src, dest)| src.link_target.is_none() && dest.link_target.is_none()) {
+            let direction = match options.direction {
+                Bidirectional if src.modified < dest.modified => DestToSource,
+                Bidirectional => SourceToDest,
+                direction => direction,
+            };
+            let (sending, receiving) = if direction == DestToSource { (dest, src) } else { (src, dest) };
+            let Some(mode) = sending.mode else { continue };
+            plan(SyncAction::Permissions, direction, &src.rel_path);
+            let owner = sending.owner.filter(|_| options.fix_owner && sending.owner != receiving.owner);
+            permission_tasks.push((receiving.path.clone(), mode, owner, parent_dir(&receiving.rel_path)));
+        }
+    }
+
+    // 3. Prepare Delete Tasks (Extra on the receiving side - Optional)
     let mut delete_tasks = Vec::new();
-    if delete_extra {
-        for entry in &results.missing_in_source {
-            delete_tasks.push(entry.path.clone());
+    let mut emptied_dirs = BTreeSet::new(); // Relative folders that may be left empty
+    if options.delete_extra && !options.stage {
+        let extra = match options.direction {
+            SourceToDest => &missing_in_source,
+            DestToSource => &missing_in_dest,
+            Bidirectional => &Vec::new(),
+        };
+        for entry in extra {
+            plan(SyncAction::Delete, options.direction, &entry.rel_path);
+            delete_tasks.push((entry.path.clone(), parent_dir(&entry.rel_path)));
+            emptied_dirs.extend(Path::new(&entry.rel_path).ancestors().skip(1)
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .map(Path::to_path_buf));
+        }
+    }
+
+    if options.dry_run {
+        tx.send(ScanStatus::Complete).ok();
+        return Ok(errors);
+    }
+
+    for dir in dir_tasks {
+        if let Err(e) = std::fs::create_dir_all(long_path(&dir)) {
+            let msg = with_length_hint(&dir, format!("Could not create folder: {}", e));
+            tx.send(ScanStatus::SyncError(dir.clone(), msg.clone())).ok();
+            errors.push((dir, msg));
+        }
+    }
+
+    // Sending and receiving folder of every copy, up to (not including) the roots
+    let mut folder_modes = BTreeSet::new();
+    if options.apply_dir_permissions {
+        for (from, to, _, _, rel_dir) in &tasks {
+            let levels = if rel_dir.is_empty() { 0 } else { rel_dir.split('/').count() };
+            folder_modes.extend(from.ancestors().skip(1).zip(to.ancestors().skip(1))
+                .take(levels)
+                .map(|(from, to)| (from.to_path_buf(), to.to_path_buf())));
+        }
+        if to_dest {
+            folder_modes.extend(results.dirs_missing_in_dest.iter().map(|rel_dir| (source_root.join(rel_dir), dest_target.join(rel_dir))));
         }
     }
 
-    let total = tasks.len() + delete_tasks.len();
+    // Folder by folder, so the progress shows where the sync is (or where it got stuck)
+    tasks.sort_by_key(|task| task.4);
+    delete_tasks.sort_by_key(|task| task.1);
+
+    let total = tasks.len() + permission_tasks.len() + delete_tasks.len();
     let counter = AtomicUsize::new(0);
+    let total_bytes: u64 = tasks.iter().map(|(_, _, size, _, _)| size).sum();
+    let bytes_done = AtomicU64::new(0);
+    let verified = AtomicUsize::new(0);
+    let backed_up = AtomicUsize::new(0);
+    let progress = ProgressGate::new();
+    let throttle = (options.bandwidth_limit > 0).then(|| Throttle::new(options.bandwidth_limit));
 
     // Run Copy/Update in Parallel
-    tasks.into_par_iter().for_each(|(from, to, _)| {
-        let c = counter.fetch_add(1, Ordering::Relaxed) + 1;
-        if c % 10 == 0 || c == total {
-            tx.send(ScanStatus::Syncing(c, total)).ok();
+    // The counter only advances once an operation finishes, so on cancel it
+    // reflects exactly how many operations were carried out.
+    let copy_errors: Vec<(PathBuf, String)> = tasks.into_par_iter()
+        .filter_map(|(from, to, size, backup, rel_dir)| {
+            wait_while_paused(paused, cancel);
+            if cancel.load(Ordering::Relaxed) {
+                return None;
+            }
+
+            let res = match &backup {
+                Some(backup) => backup_file(&to, backup)
+                    .and_then(|_| copy_file(&from, &to, options, throttle.as_ref()).map_err(|e| restore_backup(backup, &to, e))),
+                None => copy_file(&from, &to, options, throttle.as_ref()),
+            };
+            // Restored backups are back in place and no longer count
+            if backup.as_ref().is_some_and(|b| long_path(b).exists()) {
+                backed_up.fetch_add(1, Ordering::Relaxed);
+            }
+            match &res {
+                Ok(()) => debug!("Copied {} -> {}", from.display(), to.display()),
+                Err(e) => warn!("Copy {} -> {} failed: {}", from.display(), to.display(), e),
+            }
+            if options.verify && res.is_ok() {
+                verified.fetch_add(1, Ordering::Relaxed);
+            }
+
+            let c = counter.fetch_add(1, Ordering::Relaxed) + 1;
+            let done = bytes_done.fetch_add(size, Ordering::Relaxed) + size;
+            if progress.ready(c == total) {
+                tx.send(ScanStatus::Bytes(done, total_bytes)).ok();
+                tx.send(ScanStatus::Syncing(c, total, rel_dir.to_owned())).ok();
+            }
+
+            res.err().map(|msg| {
+                let msg = with_length_hint(&to, msg);
+                tx.send(ScanStatus::SyncError(to.clone(), msg.clone())).ok();
+                (to, msg)
+            })
+        })
+        .collect();
+    errors.extend(copy_errors);
+
+    // Deepest first is not needed: changing a folder's mode doesn't stop its children from being changed
+    for (from, to) in folder_modes {
+        if cancel.load(Ordering::Relaxed) {
+            break;
         }
+        if !to.is_dir() {
+            continue;
+        }
+        if let Err(e) = copy_mode(&from, &to) {
+            let msg = with_length_hint(&to, format!("Folder permissions not applied: {}", e));
+            warn!("{}: {}", to.display(), msg);
+            tx.send(ScanStatus::SyncError(to.clone(), msg.clone())).ok();
+            errors.push((to, msg));
+        }
+    }
 
-        // Ensure parent directory exists
-        if let Some(parent) = to.parent() {
-            let _ = std::fs::create_dir_all(parent);
+    for (path, mode, owner, rel_dir) in permission_tasks {
+        wait_while_paused(paused, cancel);
+        if cancel.load(Ordering::Relaxed) {
+            break;
         }
-        
-        let _ = std::fs::copy(from, to);
-    });
+        let res = set_permissions(&long_path(&path), mode, owner);
+        match &res {
+            Ok(()) => debug!("Set mode {:o}{} on {}", mode, owner.map_or(String::new(), |(uid, gid)| format!(" and owner {}:{}", uid, gid)), path.display()),
+            Err(e) => warn!("Permissions of {} not changed: {}", path.display(), e),
+        }
+        let c = counter.fetch_add(1, Ordering::Relaxed) + 1;
+        if progress.ready(c == total) {
+            tx.send(ScanStatus::Syncing(c, total, rel_dir.to_owned())).ok();
+        }
+        if let Err(msg) = res {
+            tx.send(ScanStatus::SyncError(path.clone(), msg.clone())).ok();
+            errors.push((path, msg));
+        }
+    }
 
     // Run Deletions in Parallel (if any)
-    delete_tasks.into_par_iter().for_each(|path| {
-        let c = counter.fetch_add(1, Ordering::Relaxed) + 1;
-        if c % 10 == 0 || c == total {
-            tx.send(ScanStatus::Syncing(c, total)).ok();
+    let delete_errors: Vec<(PathBuf, String)> = delete_tasks.into_par_iter()
+        .filter_map(|(path, rel_dir)| {
+            wait_while_paused(paused, cancel);
+            if cancel.load(Ordering::Relaxed) {
+                return None;
+            }
+
+            // Never fall back to a permanent delete when trashing fails
+            let res = if options.permanent_delete {
+                std::fs::remove_file(long_path(&path)).map_err(|e| format!("Delete failed: {}", e))
+            } else {
+                trash::delete(&path).map_err(|e| format!("Could not move to trash (file kept): {}", e))
+            };
+            match &res {
+                Ok(()) => debug!("Deleted {}{}", path.display(), if options.permanent_delete { "" } else { " (to trash)" }),
+                Err(e) => warn!("Delete {} failed: {}", path.display(), e),
+            }
+
+            let c = counter.fetch_add(1, Ordering::Relaxed) + 1;
+            if progress.ready(c == total) {
+                tx.send(ScanStatus::Syncing(c, total, rel_dir.to_owned())).ok();
+            }
+
+            res.err().map(|msg| {
+                let msg = with_length_hint(&path, msg);
+                tx.send(ScanStatus::SyncError(path.clone(), msg.clone())).ok();
+                (path, msg)
+            })
+        })
+        .collect();
+    errors.extend(delete_errors);
+
+    // Remove folders the deletions emptied, deepest first: reverse order
+    // puts "a/b" before "a". Only folders absent on the sending side go,
+    // and anything still holding files (e.g. a failed delete) is kept.
+    if !emptied_dirs.is_empty() && !cancel.load(Ordering::Relaxed) {
+        let (sending_root, receiving_root) = match options.direction {
+            DestToSource => (&dest_root, &source_root),
+            _ => (&source_root, &dest_root),
+        };
+        for rel_dir in emptied_dirs.iter().rev() {
+            let dir = receiving_root.join(rel_dir);
+            let is_empty = std::fs::read_dir(&dir).map(|mut d| d.next().is_none()).unwrap_or(false);
+            if !is_empty || sending_root.join(rel_dir).is_dir() {
+                continue;
+            }
+            if let Err(e) = std::fs::remove_dir(long_path(&dir)) {
+                let msg = format!("Could not remove empty folder: {}", e);
+                tx.send(ScanStatus::SyncError(dir.clone(), msg.clone())).ok();
+                errors.push((dir, msg));
+            }
         }
-        let _ = std::fs::remove_file(path);
-    });
+    }
 
+    if cancel.load(Ordering::Relaxed) {
+        // Report the exact number of processed operations before stopping
+        tx.send(ScanStatus::Syncing(counter.load(Ordering::Relaxed), total, String::new())).ok();
+        tx.send(ScanStatus::Cancelled).ok();
+        return Err("Cancelled".into());
+    }
+
+    if options.verify {
+        tx.send(ScanStatus::Verified(verified.load(Ordering::Relaxed))).ok();
+    }
+    if options.backup {
+        tx.send(ScanStatus::BackedUp(backed_up.load(Ordering::Relaxed))).ok();
+    }
+    for target in [dest_target, source_target] {
+        if options.stage && target.is_dir() {
+            tx.send(ScanStatus::Staged(target)).ok();
+        }
+    }
     tx.send(ScanStatus::Complete).ok();
-    Ok(())
+    Ok(errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(rel_path: &str, size: u64, modified: u64) -> FileEntry {
+        FileEntry {
+            path: PathBuf::from(rel_path),
+            rel_path: rel_path.to_owned(),
+            size,
+            modified,
+            hash: None,
+            link_target: None,
+            mode: None,
+            owner: None,
+        }
+    }
+
+    fn tree(entries: Vec<FileEntry>) -> HashMap<String, FileEntry> {
+        entries.into_iter().map(|e| (e.rel_path.clone(), e)).collect()
+    }
+
+    /// A scratch folder unique to this test run, removed by the caller
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("omnidiff_test_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn native_separators_key_like_forward_slashes() {
+        let native = Path::new("sub").join("deep").join("file.txt");
+        assert_eq!(normalize_rel_path(&native), "sub/deep/file.txt");
+
+        // One side scanned with native separators, the other already using '/'
+        let source = tree(vec![entry(&normalize_rel_path(&native), 3, 0)]);
+        let dest = tree(vec![entry("sub/deep/file.txt", 3, 0)]);
+        let pairing = pair_trees(source, dest, false);
+        assert_eq!(pairing.common.len(), 1);
+        assert!(pairing.missing_in_dest.is_empty() && pairing.missing_in_source.is_empty());
+    }
+
+    fn names(files: &[FileEntry]) -> Vec<&str> {
+        files.iter().map(|f| f.rel_path.as_str()).collect()
+    }
+
+    #[test]
+    fn names_differing_in_case_pair_only_when_case_insensitive() {
+        let source = || tree(vec![entry("Docs/Readme.md", 10, 100)]);
+        let dest = || tree(vec![entry("docs/README.md", 10, 100)]);
+
+        let strict = pair_trees(source(), dest(), false);
+        assert!(strict.common.is_empty());
+        assert_eq!(names(&strict.missing_in_dest), ["Docs/Readme.md"]);
+        assert_eq!(names(&strict.missing_in_source), ["docs/README.md"]);
+
+        let folded = pair_trees(source(), dest(), true);
+        assert_eq!(folded.common.len(), 1);
+        assert!(folded.missing_in_dest.is_empty() && folded.missing_in_source.is_empty());
+        assert!(folded.case_collisions.is_empty());
+    }
+
+    #[test]
+    fn case_insensitive_collisions_are_reported_and_kept_apart() {
+        let source = tree(vec![entry("a.txt", 1, 0), entry("A.txt", 2, 0)]);
+        let dest = tree(vec![entry("a.txt", 1, 0)]);
+        let result = compare_metadata(source, dest, &CompareOptions { case_insensitive: true, ..Default::default() });
+        assert_eq!(result.case_collisions, ["Source: A.txt ↔ a.txt"]);
+        assert_eq!(result.identical_count, 1);
+        assert_eq!(names(&result.missing_in_dest), ["A.txt"]);
+        assert!(result.missing_in_source.is_empty());
+    }
+
+    #[test]
+    fn zero_byte_files_compare_by_size_and_mtime() {
+        let source = tree(vec![entry("empty", 0, 50), entry("grew", 0, 50)]);
+        let dest = tree(vec![entry("empty", 0, 50), entry("grew", 1, 50)]);
+        let result = compare_metadata(source, dest, &CompareOptions::default());
+        assert_eq!(result.identical_count, 1);
+        assert_eq!(result.different_content.len(), 1);
+        assert_eq!(result.different_content[0].0.rel_path, "grew");
+    }
+
+    #[test]
+    fn all_distinct_trees_share_nothing() {
+        let source = tree(vec![entry("a", 1, 0), entry("sub/b", 2, 0)]);
+        let dest = tree(vec![entry("c", 1, 0)]);
+        let result = compare_metadata(source, dest, &CompareOptions::default());
+        assert_eq!(names(&result.missing_in_dest), ["a", "sub/b"]);
+        assert_eq!(names(&result.missing_in_source), ["c"]);
+        assert_eq!(result.identical_count, 0);
+        assert!(result.different_content.is_empty() && result.timestamp_only.is_empty());
+    }
+
+    #[test]
+    fn mtime_tolerance_is_inclusive() {
+        let compare = |tolerance| {
+            let source = tree(vec![entry("f", 5, 100)]);
+            let dest = tree(vec![entry("f", 5, 102)]);
+            compare_metadata(source, dest, &CompareOptions { mtime_tolerance: tolerance, ..Default::default() })
+        };
+        let within = compare(2);
+        assert_eq!(within.identical_count, 1);
+        assert!(within.timestamp_only.is_empty());
+        let outside = compare(1);
+        assert_eq!(outside.identical_count, 0);
+        assert_eq!(outside.timestamp_only.len(), 1);
+    }
+
+    #[test]
+    fn failed_copy_puts_the_backup_back() {
+        let dir = temp_dir("restore_backup");
+        let (to, backup) = (dir.join("file.txt"), dir.join("backup").join("file.txt"));
+        std::fs::write(&to, b"old").unwrap();
+        backup_file(&to, &backup).unwrap();
+        assert!(!to.exists());
+
+        let err = restore_backup(&backup, &to, "Copy failed: disk full".into());
+        assert!(err.contains("restored"), "{}", err);
+        assert_eq!(std::fs::read(&to).unwrap(), b"old");
+        assert!(!backup.exists());
+
+        backup_file(&to, &backup).unwrap();
+        std::fs::write(&to, b"new").unwrap();
+        let err = restore_backup(&backup, &to, "Copied, but could not preserve timestamps".into());
+        assert!(err.contains(&backup.display().to_string()), "{}", err);
+        assert_eq!(std::fs::read(&to).unwrap(), b"new");
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn mixed_separators_in_one_path_normalize() {
+        assert_eq!(normalize_rel_path(Path::new(r"sub\deep/file.txt")), "sub/deep/file.txt");
+    }
+
+    #[test]
+    fn tiny_files_hash_without_mmap() {
+        let dir = temp_dir("tiny_files");
+        let (empty, one) = (dir.join("empty"), dir.join("one"));
+        std::fs::write(&empty, b"").unwrap();
+        std::fs::write(&one, b"x").unwrap();
+        assert_eq!(calculate_hash(&empty).unwrap(), blake3::hash(b"").to_hex().to_string());
+        assert_eq!(calculate_hash(&one).unwrap(), blake3::hash(b"x").to_hex().to_string());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn distinct_trees_finish_without_hashing() {
+        let dir = temp_dir("distinct_trees");
+        let (source, dest) = (dir.join("source"), dir.join("dest"));
+        for (root, name) in [(&source, "a.txt"), (&dest, "b.txt")] {
+            std::fs::create_dir_all(root).unwrap();
+            std::fs::write(root.join(name), b"data").unwrap();
+        }
+        let options = CompareOptions { check_content: true, ..Default::default() };
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let result = run_comparison(source, dest, &options, &AtomicBool::new(false), tx, None).unwrap();
+
+        let events: Vec<ScanStatus> = rx.try_iter().collect();
+        assert!(!events.iter().any(|e| matches!(e, ScanStatus::Hashing(..))));
+        assert!(matches!(events.last(), Some(ScanStatus::Complete)));
+        assert_eq!((result.missing_in_dest.len(), result.missing_in_source.len(), result.identical_count), (1, 1, 0));
+        std::fs::remove_dir_all(&dir).ok();
+    }
 }
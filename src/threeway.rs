@@ -0,0 +1,176 @@
+use std::collections::{BTreeSet, HashMap};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use crossbeam_channel::Sender;
+use log::warn;
+use rayon::prelude::*;
+use serde::Serialize;
+
+use crate::scanner::{self, CompareOptions, FileEntry, ProgressGate, ScanCounters, ScanStatus};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum Side {
+    Base,
+    Mine,
+    Theirs,
+}
+
+impl Side {
+    pub const ALL: [Side; 3] = [Side::Base, Side::Mine, Side::Theirs];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Side::Base => "base",
+            Side::Mine => "mine",
+            Side::Theirs => "theirs",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum ThreeWayStatus {
+    Identical,       // Present and equal in all three
+    Unique(Side),    // Only present in one folder
+    MissingIn(Side), // The other two copies are equal
+    ChangedIn(Side), // The other two copies are equal, this one differs
+    Conflict,        // No two present copies agree
+    Unreadable(Side), // This copy could not be hashed, so nothing is concluded
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ThreeWayEntry {
+    pub rel_path: String,
+    pub copies: [Option<FileEntry>; 3], // Indexed like Side::ALL
+    pub status: ThreeWayStatus,
+    pub error: Option<String>, // Why the copy of an Unreadable entry could not be hashed
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ThreeWayResult {
+    pub entries: Vec<ThreeWayEntry>, // Sorted by rel_path
+    pub scan_errors: Vec<(PathBuf, String)>, // Missing from the comparison because they could not be listed or read
+}
+
+impl ThreeWayResult {
+    pub fn count(&self, status: ThreeWayStatus) -> usize {
+        self.entries.iter().filter(|e| e.status == status).count()
+    }
+}
+
+pub fn run_three_way(
+    base: PathBuf,
+    mine: PathBuf,
+    theirs: PathBuf,
+    options: &CompareOptions,
+    cancel: &AtomicBool,
+    tx: Sender<ScanStatus>
+) -> Result<ThreeWayResult, String> {
+    scanner::thread_pool(options.threads)?.install(|| compare_three(&[base, mine, theirs], options, cancel, &tx))
+}
+
+fn compare_three(
+    roots: &[PathBuf; 3],
+    options: &CompareOptions,
+    cancel: &AtomicBool,
+    tx: &Sender<ScanStatus>
+) -> Result<ThreeWayResult, String> {
+    let excludes = roots.iter()
+        .map(|root| scanner::build_excludes(root, &options.exclude_patterns))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    tx.send(ScanStatus::ScanningBoth).ok();
+    let counters = ScanCounters::with_limit(options.scan_limit);
+    let trees: Vec<HashMap<String, FileEntry>> = roots.par_iter().zip(&excludes)
+        .map(|(root, excludes)| scanner::scan_folder(root, excludes, options.follow_symlinks, &options.filter, cancel, &counters, tx))
+        .collect();
+    tx.send(ScanStatus::Scanned(counters.scanned.load(Ordering::Relaxed))).ok();
+
+    if cancel.load(Ordering::Relaxed) {
+        tx.send(ScanStatus::Cancelled).ok();
+        return Err("Cancelled".into());
+    }
+
+    let rel_paths: BTreeSet<&String> = trees.iter().flat_map(|t| t.keys()).collect();
+    let total = rel_paths.len();
+    let counter = AtomicUsize::new(0);
+    let progress = ProgressGate::new();
+
+    let entries: Vec<ThreeWayEntry> = rel_paths.into_par_iter()
+        .filter_map(|rel_path| {
+            if cancel.load(Ordering::Relaxed) {
+                return None;
+            }
+            let c = counter.fetch_add(1, Ordering::Relaxed) + 1;
+            if progress.ready(c == total) {
+                tx.send(ScanStatus::Hashing(c, total)).ok();
+            }
+
+            let mut copies = [0, 1, 2].map(|i| trees[i].get(rel_path).cloned());
+            let (status, error) = match classify(&mut copies, options) {
+                Ok(status) => (status, None),
+                Err((side, msg)) => {
+                    warn!("{} ({}): could not hash: {}", rel_path, side.label(), msg);
+                    (ThreeWayStatus::Unreadable(side), Some(msg))
+                }
+            };
+            Some(ThreeWayEntry { rel_path: rel_path.clone(), copies, status, error })
+        })
+        .collect();
+
+    if cancel.load(Ordering::Relaxed) {
+        tx.send(ScanStatus::Cancelled).ok();
+        return Err("Cancelled".into());
+    }
+
+    let mut scan_errors = std::mem::take(&mut *counters.errors.lock().unwrap());
+    scan_errors.sort();
+    tx.send(ScanStatus::Complete).ok();
+    Ok(ThreeWayResult { entries, scan_errors })
+}
+
+/// Group the present copies by content, hashing only when sizes leave it open.
+/// A copy that can't be hashed is returned as the error with its side.
+fn classify(copies: &mut [Option<FileEntry>; 3], options: &CompareOptions) -> Result<ThreeWayStatus, (Side, String)> {
+    if options.check_content {
+        let sizes: Vec<u64> = copies.iter().flatten().map(|e| e.size).collect();
+        for (side, entry) in Side::ALL.into_iter().zip(copies.iter_mut()) {
+            let Some(entry) = entry else { continue };
+            if sizes.iter().filter(|&&s| s == entry.size).count() > 1 {
+                entry.hash = Some(scanner::calculate_hash(&entry.path).map_err(|e| (side, e.to_string()))?);
+            }
+        }
+    }
+
+    let same = |a: &FileEntry, b: &FileEntry| {
+        a.size == b.size && if options.check_content {
+            a.hash.is_some() && a.hash == b.hash
+        } else {
+            a.modified.abs_diff(b.modified) <= options.mtime_tolerance
+        }
+    };
+
+    let present: Vec<usize> = (0..3).filter(|&i| copies[i].is_some()).collect();
+    let get = |i: usize| copies[i].as_ref().unwrap();
+    Ok(match present.as_slice() {
+        [only] => ThreeWayStatus::Unique(Side::ALL[*only]),
+        [a, b] => {
+            let missing = (0..3).find(|i| !present.contains(i)).unwrap();
+            if same(get(*a), get(*b)) {
+                ThreeWayStatus::MissingIn(Side::ALL[missing])
+            } else {
+                ThreeWayStatus::Conflict
+            }
+        }
+        _ => {
+            let (base, mine, theirs) = (get(0), get(1), get(2));
+            match (same(base, mine), same(base, theirs), same(mine, theirs)) {
+                (true, true, _) => ThreeWayStatus::Identical,
+                (true, false, _) => ThreeWayStatus::ChangedIn(Side::Theirs),
+                (false, true, _) => ThreeWayStatus::ChangedIn(Side::Mine),
+                (false, false, true) => ThreeWayStatus::ChangedIn(Side::Base),
+                (false, false, false) => ThreeWayStatus::Conflict,
+            }
+        }
+    })
+}
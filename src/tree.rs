@@ -0,0 +1,140 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use crate::scanner::{self, CompareResult};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NodeStatus {
+    OnlyInSource,
+    OnlyInDest,
+    Different,
+    TimestampOnly,
+    Permissions,
+    Unreadable, // Could not be listed or hashed, so no verdict
+    Identical,
+}
+
+impl NodeStatus {
+    pub const ALL: [NodeStatus; 7] = [
+        NodeStatus::OnlyInSource,
+        NodeStatus::OnlyInDest,
+        NodeStatus::Different,
+        NodeStatus::TimestampOnly,
+        NodeStatus::Permissions,
+        NodeStatus::Unreadable,
+        NodeStatus::Identical,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            NodeStatus::OnlyInSource => "only in source",
+            NodeStatus::OnlyInDest => "only in destination",
+            NodeStatus::Different => "different",
+            NodeStatus::TimestampOnly => "timestamp only",
+            NodeStatus::Permissions => "permissions differ",
+            NodeStatus::Unreadable => "unreadable",
+            NodeStatus::Identical => "identical",
+        }
+    }
+}
+
+/// One folder of the merged source/destination hierarchy
+#[derive(Debug, Default)]
+pub struct TreeDir {
+    pub dirs: BTreeMap<String, TreeDir>,
+    pub files: BTreeMap<String, NodeStatus>,
+    pub counts: [usize; 7], // Files anywhere below, indexed like NodeStatus::ALL
+}
+
+impl TreeDir {
+    pub fn count(&self, status: NodeStatus) -> usize {
+        self.counts[status as usize]
+    }
+
+    /// Files below this folder that are not identical
+    pub fn differences(&self) -> usize {
+        self.counts.iter().sum::<usize>() - self.count(NodeStatus::Identical)
+    }
+
+    fn insert(&mut self, rel_path: &str, status: NodeStatus) {
+        let mut dir = self;
+        dir.counts[status as usize] += 1;
+        let mut parts = rel_path.split('/').peekable();
+        while let Some(part) = parts.next() {
+            if parts.peek().is_none() {
+                dir.files.insert(part.to_owned(), status);
+            } else {
+                dir = dir.dirs.entry(part.to_owned()).or_default();
+                dir.counts[status as usize] += 1;
+            }
+        }
+    }
+}
+
+/// Merge every compared file into one tree with per-folder rollups.
+/// `source` and `dest` are the compared roots, used to place scan errors.
+pub fn build(results: &CompareResult, source: &Path, dest: &Path) -> TreeDir {
+    let mut root = TreeDir::default();
+    let files = results.missing_in_dest.iter().map(|f| (f, NodeStatus::OnlyInSource))
+        .chain(results.missing_in_source.iter().map(|f| (f, NodeStatus::OnlyInDest)))
+        .chain(results.renamed.iter().flat_map(|(src, dest)| [(src, NodeStatus::OnlyInSource), (dest, NodeStatus::OnlyInDest)]))
+        .chain(results.different_content.iter().map(|(src, _)| (src, NodeStatus::Different)))
+        .chain(results.timestamp_only.iter().map(|(src, _)| (src, NodeStatus::TimestampOnly)))
+        .chain(results.permissions_differ.iter().map(|(src, _)| (src, NodeStatus::Permissions)))
+        .chain(results.identical.iter().map(|f| (f, NodeStatus::Identical)));
+    for (file, status) in files {
+        root.insert(&file.rel_path, status);
+    }
+
+    // A path unreadable on both sides is still one entry
+    let unreadable: BTreeSet<String> = results.hash_errors.iter().map(|(src, _, _)| src.rel_path.clone())
+        .chain(results.scan_errors.iter().filter_map(|(path, _)| {
+            let rel = path.strip_prefix(source).or_else(|_| path.strip_prefix(dest)).ok()?;
+            Some(scanner::normalize_rel_path(rel)).filter(|rel| !rel.is_empty())
+        }))
+        .collect();
+    for rel_path in &unreadable {
+        root.insert(rel_path, NodeStatus::Unreadable);
+    }
+    root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::FileEntry;
+    use std::path::PathBuf;
+
+    fn entry(rel_path: &str) -> FileEntry {
+        FileEntry {
+            path: PathBuf::from(rel_path),
+            rel_path: rel_path.to_owned(),
+            size: 1,
+            modified: 0,
+            hash: None,
+            link_target: None,
+            mode: None,
+            owner: None,
+        }
+    }
+
+    #[test]
+    fn unreadable_files_are_counted() {
+        let results = CompareResult {
+            identical: vec![entry("a/ok.txt")],
+            hash_errors: vec![(entry("a/locked.bin"), entry("a/locked.bin"), "denied".into())],
+            scan_errors: vec![
+                (PathBuf::from("/src/a/gone.txt"), "denied".into()),
+                (PathBuf::from("/dst/a/gone.txt"), "denied".into()),
+                (PathBuf::from("/dst/b/hidden"), "denied".into()),
+            ],
+            ..Default::default()
+        };
+        let root = build(&results, Path::new("/src"), Path::new("/dst"));
+        assert_eq!(root.count(NodeStatus::Unreadable), 3);
+        assert_eq!(root.differences(), 3);
+        assert_eq!(root.dirs["a"].count(NodeStatus::Unreadable), 2);
+        assert_eq!(root.dirs["a"].files["gone.txt"], NodeStatus::Unreadable);
+        assert_eq!(root.dirs["b"].files["hidden"], NodeStatus::Unreadable);
+    }
+}